@@ -0,0 +1,535 @@
+use crate::attitude::*;
+use crate::centralbody::*;
+use crate::ephemeris::*;
+use crate::magnetic::*;
+use nalgebra::*;
+
+#[derive(Clone, Debug)]
+pub struct ReactionWheel {
+    pub axis: Vector3<f64>, // spin axis unit vector in the body frame
+    pub inertia: f64,       // kg.m^2 about the spin axis
+    pub speed: f64,         // rad/s relative to the body
+    pub max_torque: f64,    // N.m
+    pub max_speed: f64,     // rad/s
+    pub max_momentum: f64,  // N.m.s
+    pub command: f64,       // N.m, motor torque held over the current step
+}
+
+#[derive(Clone, Debug)]
+pub struct Magnetorquer {
+    pub axis: Vector3<f64>, // dipole axis unit vector in the body frame
+    pub max_dipole: f64,    // A.m^2
+    pub command: f64,       // A.m^2, dipole held over the current step
+}
+
+#[derive(Clone, Debug)]
+pub enum PointingMode {
+    Inertial(Matrix3<f64>), // fixed target attitude [RN]
+    Nadir,                  // body z to nadir, body y to the negative orbit normal
+    Sun,                    // body z to the sun
+}
+
+#[derive(Clone, Debug)]
+pub enum ControlLaw {
+    Pd { kp: f64, kd: f64 },                 // mrp attitude error
+    QuaternionFeedback { kp: f64, kd: f64 }, // quaternion vector part attitude error
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttitudeEstimator {
+    Triad,
+    QMethod,
+    Quest,
+    Esoq2,
+}
+
+#[derive(Clone, Debug)]
+pub struct Adcs {
+    // attitude controller evaluated once per step from the satellite's attitude, commands are
+    // held constant over the step (zero order hold)
+    pub mode: PointingMode,
+    pub law: ControlLaw,
+    pub wheels: Vec<ReactionWheel>,
+    pub magnetorquers: Vec<Magnetorquer>,
+    pub dump_gain: f64, // 1/s, magnetorquer momentum dumping gain when wheels are present
+    pub field: MagneticModel,
+    pub estimator: Option<AttitudeEstimator>, // attitude from sensed vectors, true when None
+}
+
+impl ReactionWheel {
+    pub fn new(
+        axis: Vector3<f64>,
+        inertia: f64,
+        max_torque: f64,
+        max_speed: f64,
+        max_momentum: f64,
+    ) -> Self {
+        Self {
+            axis: axis.normalize(),
+            inertia,
+            speed: 0.,
+            max_torque,
+            max_speed,
+            max_momentum,
+            command: 0.,
+        }
+    }
+
+    pub fn speed_limit(&self) -> f64 {
+        // rad/s, whichever of the speed and momentum limits is reached first
+        self.max_speed.min(self.max_momentum / self.inertia)
+    }
+
+    pub fn effective_command(&self, speed: f64) -> f64 {
+        // no torque once the wheel is saturated and the command would spin it up further
+        let limit = self.speed_limit();
+        if (speed >= limit && self.command > 0.) || (speed <= -limit && self.command < 0.) {
+            0.
+        } else {
+            self.command
+        }
+    }
+}
+
+impl Magnetorquer {
+    pub fn new(axis: Vector3<f64>, max_dipole: f64) -> Self {
+        Self {
+            axis: axis.normalize(),
+            max_dipole,
+            command: 0.,
+        }
+    }
+}
+
+impl AttitudeEstimator {
+    pub fn estimate(&self, observations: &[VectorObservation]) -> Attitude {
+        // triad only uses the first two observations
+        match self {
+            AttitudeEstimator::Triad => triad(&observations[0], &observations[1]),
+            AttitudeEstimator::QMethod => davenport_q(observations),
+            AttitudeEstimator::Quest => quest(observations),
+            AttitudeEstimator::Esoq2 => esoq2(observations),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AttitudeEstimator::Triad => "TRIAD",
+            AttitudeEstimator::QMethod => "q-Method",
+            AttitudeEstimator::Quest => "QUEST",
+            AttitudeEstimator::Esoq2 => "ESOQ2",
+        }
+    }
+}
+
+impl Adcs {
+    pub fn new(mode: PointingMode, law: ControlLaw) -> Self {
+        Self {
+            mode,
+            law,
+            wheels: vec![],
+            magnetorquers: vec![],
+            dump_gain: 0.,
+            field: MagneticModel::Dipole,
+            estimator: None,
+        }
+    }
+
+    pub fn reference(&self, state: &Vector6<f64>, time: &f64) -> (Matrix3<f64>, Vector3<f64>) {
+        // target attitude [RN] and its angular velocity in the inertial frame (rad/s)
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        let v_vec: Vector3<f64> = state.fixed_rows::<3>(3).into();
+        match &self.mode {
+            PointingMode::Inertial(rn) => (*rn, Vector3::zeros()),
+            PointingMode::Nadir => {
+                let h_vec = r_vec.cross(&v_vec);
+                let r3 = -r_vec.normalize();
+                let r2 = -h_vec.normalize();
+                let r1 = r2.cross(&r3);
+                let rn = Matrix3::from_rows(&[r1.transpose(), r2.transpose(), r3.transpose()]);
+                (rn, h_vec / r_vec.norm_squared())
+            }
+            PointingMode::Sun => {
+                let r3 = (sun_position(time2jd(time)) - r_vec).normalize();
+                let r1 = if r3[2].abs() < 0.9 {
+                    Vector3::z().cross(&r3).normalize()
+                } else {
+                    Vector3::x().cross(&r3).normalize()
+                };
+                let r2 = r3.cross(&r1);
+                let rn = Matrix3::from_rows(&[r1.transpose(), r2.transpose(), r3.transpose()]);
+                (rn, Vector3::zeros())
+            }
+        }
+    }
+
+    pub fn observations(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        dcm: &Matrix3<f64>,
+        time: &f64,
+    ) -> Vec<VectorObservation> {
+        // ideal sun sensor (when lit), earth sensor and magnetometer readings, most accurate
+        // first so triad matches the sun direction exactly
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        let mut inertial = vec![];
+        let sun_hat = (sun_position(time2jd(time)) - r_vec).normalize();
+        let along = r_vec.dot(&sun_hat);
+        if along >= 0. || (r_vec - along * sun_hat).norm() >= central_body.equatorial_radius {
+            inertial.push((sun_hat, 1.));
+        }
+        inertial.push((-r_vec, 1.));
+        inertial.push((self.field.field(central_body, &r_vec, time), 0.1));
+        inertial
+            .iter()
+            .map(|(vec, weight)| VectorObservation::new(dcm * vec, *vec, *weight))
+            .collect()
+    }
+
+    pub fn wheel_momentum(&self, speeds: &[f64]) -> Vector3<f64> {
+        // N.m.s, total wheel angular momentum in the body frame
+        self.wheels
+            .iter()
+            .zip(speeds.iter())
+            .fold(Vector3::zeros(), |h, (wheel, speed)| {
+                h + wheel.axis * (wheel.inertia * speed)
+            })
+    }
+
+    pub fn wheel_accelerations(&self, speeds: &[f64]) -> Vec<f64> {
+        self.wheels
+            .iter()
+            .zip(speeds.iter())
+            .map(|(wheel, speed)| wheel.effective_command(*speed) / wheel.inertia)
+            .collect()
+    }
+
+    pub fn update(
+        &mut self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        dcm: &Matrix3<f64>,
+        omega: &Vector3<f64>,
+        inertia: &Matrix3<f64>,
+        time: &f64,
+    ) {
+        // compute the actuator commands for the current step
+        let (rn, omega_rn) = self.reference(state, time);
+        let estimate = match self.estimator {
+            Some(estimator) => {
+                estimator
+                    .estimate(&self.observations(central_body, state, dcm, time))
+                    .dcm
+            }
+            None => *dcm,
+        };
+        let br = estimate * rn.transpose();
+        let omega_br = omega - estimate * omega_rn;
+        let error = match self.law {
+            ControlLaw::Pd { .. } => Mrps::dcm2mrp(&br).sigma,
+            ControlLaw::QuaternionFeedback { .. } => Quaternions::dcm2quat(&br)
+                .quaternion
+                .fixed_rows::<3>(0)
+                .into(),
+        };
+        let (kp, kd) = match self.law {
+            ControlLaw::Pd { kp, kd } | ControlLaw::QuaternionFeedback { kp, kd } => (kp, kd),
+        };
+
+        // required torque on the body with the gyroscopic terms cancelled
+        let speeds: Vec<f64> = self.wheels.iter().map(|wheel| wheel.speed).collect();
+        let h_wheels = self.wheel_momentum(&speeds);
+        let torque = -kp * error - kd * omega_br + omega.cross(&(inertia * omega + h_wheels));
+
+        // wheels react against the body, the pseudo inverse spreads the torque across them
+        let magnetic_torque = if self.wheels.is_empty() {
+            torque
+        } else {
+            let axes = Matrix3xX::from_columns(
+                &self
+                    .wheels
+                    .iter()
+                    .map(|wheel| wheel.axis)
+                    .collect::<Vec<_>>(),
+            );
+            let commands = -axes.pseudo_inverse(1e-12).unwrap() * torque;
+            for (wheel, command) in self.wheels.iter_mut().zip(commands.iter()) {
+                wheel.command = command.clamp(-wheel.max_torque, wheel.max_torque);
+            }
+            -self.dump_gain * h_wheels
+        };
+
+        // magnetorquers can only torque perpendicular to the field, m = B x L / |B|^2 with the
+        // field taken into the body frame through the same attitude the error came from
+        if !self.magnetorquers.is_empty() {
+            let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+            let b_body = estimate * self.field.field(central_body, &r_vec, time);
+            let b_sq = b_body.norm_squared();
+            let dipole = if b_sq > 0. {
+                b_body.cross(&magnetic_torque) / b_sq
+            } else {
+                Vector3::zeros()
+            };
+            for rod in self.magnetorquers.iter_mut() {
+                rod.command = rod.axis.dot(&dipole).clamp(-rod.max_dipole, rod.max_dipole);
+            }
+        }
+    }
+
+    pub fn torque(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        dcm: &Matrix3<f64>,
+        omega: &Vector3<f64>,
+        speeds: &[f64],
+        time: &f64,
+    ) -> Vector3<f64> {
+        // N.m on the body from the held commands, including the wheel gyroscopic coupling
+        let mut torque = -omega.cross(&self.wheel_momentum(speeds));
+        for (wheel, speed) in self.wheels.iter().zip(speeds.iter()) {
+            torque -= wheel.axis * wheel.effective_command(*speed);
+        }
+        if !self.magnetorquers.is_empty() {
+            let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+            let b_body = dcm * self.field.field(central_body, &r_vec, time);
+            let dipole = self
+                .magnetorquers
+                .iter()
+                .fold(Vector3::zeros(), |m, rod| m + rod.axis * rod.command);
+            torque += dipole.cross(&b_body);
+        }
+        torque
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn earth() -> CentralBody {
+        let mut central_body = CentralBody::new();
+        central_body.mu = 398600.4418;
+        central_body.equatorial_radius = 6378.137;
+        central_body
+    }
+
+    fn orthogonal_wheels() -> Vec<ReactionWheel> {
+        [Vector3::x(), Vector3::y(), Vector3::z()]
+            .iter()
+            .map(|axis| ReactionWheel::new(*axis, 0.05, 0.2, 600., 5.))
+            .collect()
+    }
+
+    fn pd() -> ControlLaw {
+        ControlLaw::Pd { kp: 0.5, kd: 3. }
+    }
+
+    fn settle(adcs: &mut Adcs, sigma: Vector3<f64>, seconds: f64) -> (f64, Vector3<f64>) {
+        // closed loop from rest at attitude sigma with the commands held over 0.5 s steps,
+        // returns the remaining rotation angle (rad) to the target and the body rate
+        let central_body = earth();
+        let inertia = Matrix3::from_diagonal(&vector![10., 12., 8.]);
+        let state = Vector6::new(7000., 0., 0., 0., 7.5, 0.);
+        let n_wheels = adcs.wheels.len();
+        let mut x = DVector::zeros(6 + n_wheels);
+        x.fixed_rows_mut::<3>(0).copy_from(&sigma);
+        let dt = 0.5;
+        for k in 0..(seconds / dt) as usize {
+            let time = k as f64 * dt;
+            let sigma: Vector3<f64> = x.fixed_rows::<3>(0).into();
+            let omega: Vector3<f64> = x.fixed_rows::<3>(3).into();
+            for (i, wheel) in adcs.wheels.iter_mut().enumerate() {
+                wheel.speed = x[6 + i];
+            }
+            let dcm = Mrps::new(sigma).mrp2dcm();
+            adcs.update(&central_body, &state, &dcm, &omega, &inertia, &time);
+
+            let dxdt = |x: &DVector<f64>| -> DVector<f64> {
+                let sigma: Vector3<f64> = x.fixed_rows::<3>(0).into();
+                let omega: Vector3<f64> = x.fixed_rows::<3>(3).into();
+                let speeds: Vec<f64> = x.rows(6, n_wheels).iter().copied().collect();
+                let dcm = Mrps::new(sigma).mrp2dcm();
+                let torque = adcs.torque(&central_body, &state, &dcm, &omega, &speeds, &time);
+                let mut x_dot = DVector::zeros(x.len());
+                x_dot
+                    .fixed_rows_mut::<3>(0)
+                    .copy_from(&Mrps::kinematics(&sigma, &omega));
+                x_dot
+                    .fixed_rows_mut::<3>(3)
+                    .copy_from(&rigid_body_dynamics(&inertia, &omega, &torque));
+                for (i, accel) in adcs.wheel_accelerations(&speeds).iter().enumerate() {
+                    x_dot[6 + i] = *accel;
+                }
+                x_dot
+            };
+            let k1 = dxdt(&x);
+            let k2 = dxdt(&(&x + &k1 * (dt / 2.)));
+            let k3 = dxdt(&(&x + &k2 * (dt / 2.)));
+            let k4 = dxdt(&(&x + &k3 * dt));
+            x += (k1 + (k2 + k3) * 2. + k4) * (dt / 6.);
+            let sigma: Vector3<f64> = x.fixed_rows::<3>(0).into();
+            if sigma.norm() > 1. {
+                x.fixed_rows_mut::<3>(0)
+                    .copy_from(&Mrps::new(sigma).shadow().sigma);
+            }
+        }
+        let sigma: Vector3<f64> = x.fixed_rows::<3>(0).into();
+        let (rn, _) = adcs.reference(&state, &seconds);
+        let br = Mrps::new(sigma).mrp2dcm() * rn.transpose();
+        (
+            PrincipalRotation::dcm2prv(&br).angle,
+            x.fixed_rows::<3>(3).into(),
+        )
+    }
+
+    #[test]
+    fn saturated_wheels_stop_spinning_up() {
+        // the momentum limit (0.01 N.m.s at 0.01 kg.m^2) is reached before the speed limit
+        let mut wheel = ReactionWheel::new(vector![0., 0., 2.], 0.01, 0.1, 100., 0.01);
+        assert_eq!(wheel.axis, Vector3::z());
+        assert_eq!(wheel.speed_limit(), 1.);
+        wheel.command = 0.05;
+        assert_eq!(wheel.effective_command(0.5), 0.05);
+        assert_eq!(wheel.effective_command(1.), 0.);
+        assert_eq!(wheel.effective_command(-1.), 0.05);
+        wheel.command = -0.05;
+        assert_eq!(wheel.effective_command(1.), -0.05);
+        assert_eq!(wheel.effective_command(-1.2), 0.);
+
+        // the body feels the motor reaction and the gyroscopic coupling of the wheel momentum
+        let mut adcs = Adcs::new(PointingMode::Inertial(Matrix3::identity()), pd());
+        wheel.command = 0.05;
+        adcs.wheels = vec![wheel];
+        let omega = vector![0.1, 0., 0.];
+        let torque = adcs.torque(
+            &earth(),
+            &Vector6::new(7000., 0., 0., 0., 7.5, 0.),
+            &Matrix3::identity(),
+            &omega,
+            &[0.5],
+            &0.,
+        );
+        let expected = -Vector3::z() * 0.05 - omega.cross(&(Vector3::z() * 0.005));
+        assert!((torque - expected).norm() < 1e-15);
+        assert_eq!(adcs.wheel_accelerations(&[0.5]), vec![5.]);
+        assert_eq!(adcs.wheel_accelerations(&[1.]), vec![0.]);
+    }
+
+    #[test]
+    fn wheel_commands_are_limited_to_the_motor_torque() {
+        let mut adcs = Adcs::new(PointingMode::Inertial(Matrix3::identity()), pd());
+        adcs.wheels = orthogonal_wheels();
+        let dcm = Mrps::new(vector![0.5, -0.3, 0.2]).mrp2dcm();
+        let inertia = Matrix3::from_diagonal(&vector![10., 12., 8.]);
+        adcs.update(
+            &earth(),
+            &Vector6::new(7000., 0., 0., 0., 7.5, 0.),
+            &dcm,
+            &vector![0.3, 0., 0.],
+            &inertia,
+            &0.,
+        );
+        assert!(adcs.wheels.iter().all(|wheel| wheel.command.abs() <= 0.2));
+        assert!(adcs.wheels.iter().any(|wheel| wheel.command.abs() == 0.2));
+    }
+
+    #[test]
+    fn pd_control_settles_on_the_target() {
+        let mut adcs = Adcs::new(PointingMode::Inertial(Matrix3::identity()), pd());
+        adcs.wheels = orthogonal_wheels();
+        // 40 deg about an oblique axis
+        let sigma = vector![1., 2., -1.].normalize() * 10_f64.to_radians().tan();
+        let (angle, omega) = settle(&mut adcs, sigma, 600.);
+        assert!(angle < 1e-6, "{}", angle);
+        assert!(omega.norm() < 1e-7);
+    }
+
+    #[test]
+    fn quaternion_feedback_settles_on_the_target() {
+        let target = Mrps::new(vector![0.1, 0., 0.3]).mrp2dcm();
+        let mut adcs = Adcs::new(
+            PointingMode::Inertial(target),
+            ControlLaw::QuaternionFeedback { kp: 1., kd: 3. },
+        );
+        adcs.wheels = orthogonal_wheels();
+        let sigma = vector![-0.2, 0.1, 0.];
+        assert!(
+            PrincipalRotation::dcm2prv(&(Mrps::new(sigma).mrp2dcm() * target.transpose())).angle
+                > 1.
+        );
+        let (angle, omega) = settle(&mut adcs, sigma, 600.);
+        assert!(angle < 1e-6, "{}", angle);
+        assert!(omega.norm() < 1e-7);
+    }
+
+    #[test]
+    fn magnetorquers_give_the_torque_across_the_field() {
+        // without wheels the rods take the whole control torque, m = B x L / |B|^2 leaves the
+        // part of L perpendicular to B
+        let mut adcs = Adcs::new(PointingMode::Inertial(Matrix3::identity()), pd());
+        adcs.magnetorquers = [Vector3::x(), Vector3::y(), Vector3::z()]
+            .iter()
+            .map(|axis| Magnetorquer::new(*axis, 1e3))
+            .collect();
+        let central_body = earth();
+        let state = Vector6::new(5000., 2000., 4000., 0., 7.5, 0.);
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        let sigma = vector![0.02, -0.01, 0.03];
+        let dcm = Mrps::new(sigma).mrp2dcm();
+        let inertia = Matrix3::from_diagonal(&vector![10., 12., 8.]);
+        let omega = Vector3::zeros();
+        adcs.update(&central_body, &state, &dcm, &omega, &inertia, &0.);
+
+        let b_body = dcm * dipole_field(&central_body, &r_vec, &0.);
+        let b_hat = b_body.normalize();
+        let demand = -0.5 * sigma;
+        let dipole = Vector3::from_iterator(adcs.magnetorquers.iter().map(|rod| rod.command));
+        assert!((dipole - b_body.cross(&demand) / b_body.norm_squared()).norm() < 1e-9);
+        let torque = adcs.torque(&central_body, &state, &dcm, &omega, &[], &0.);
+        let expected = demand - demand.dot(&b_hat) * b_hat;
+        assert!((torque - expected).norm() < 1e-12 * demand.norm());
+
+        // a weak rod clips its dipole
+        adcs.magnetorquers[0].max_dipole = 1e-3;
+        adcs.update(&central_body, &state, &dcm, &omega, &inertia, &0.);
+        assert_eq!(adcs.magnetorquers[0].command.abs(), 1e-3);
+    }
+
+    #[test]
+    fn estimated_attitude_drives_the_magnetorquers() {
+        // ideal sensors make every estimator reproduce the commands flown on the true attitude
+        let central_body = earth();
+        let state = Vector6::new(5000., 2000., 4000., -3., 6., 0.5);
+        let dcm = Mrps::new(vector![0.1, -0.2, 0.15]).mrp2dcm();
+        let inertia = Matrix3::from_diagonal(&vector![10., 12., 8.]);
+        let omega = vector![0.01, 0., -0.02];
+        let commands = |estimator: Option<AttitudeEstimator>| -> Vec<f64> {
+            let mut adcs = Adcs::new(PointingMode::Nadir, pd());
+            adcs.magnetorquers = [Vector3::x(), Vector3::y(), Vector3::z()]
+                .iter()
+                .map(|axis| Magnetorquer::new(*axis, 1e6))
+                .collect();
+            adcs.estimator = estimator;
+            adcs.update(&central_body, &state, &dcm, &omega, &inertia, &1e7);
+            adcs.magnetorquers.iter().map(|rod| rod.command).collect()
+        };
+        let truth = commands(None);
+        for estimator in [
+            AttitudeEstimator::Triad,
+            AttitudeEstimator::QMethod,
+            AttitudeEstimator::Quest,
+            AttitudeEstimator::Esoq2,
+        ] {
+            let estimated = commands(Some(estimator));
+            for (a, b) in estimated.iter().zip(truth.iter()) {
+                assert!(
+                    (a - b).abs() < 1e-6 * b.abs().max(1.),
+                    "{}",
+                    estimator.name()
+                );
+            }
+        }
+    }
+}
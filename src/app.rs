@@ -1,17 +1,30 @@
+use crate::adcs::{Adcs, AttitudeEstimator, ControlLaw, Magnetorquer, PointingMode, ReactionWheel};
+use crate::attitude::{Attitude, EulerAngles, Quaternions};
 #[allow(unused_imports)]
 use crate::centralbody::*;
 #[allow(unused_imports)]
 use crate::dynamical_system::*;
 #[allow(unused_imports)]
 use crate::eoms::*;
-use crate::orbitalelements::OrbitalElements;
+use crate::epoch::*;
+use crate::frames::*;
+use crate::geodetic::GroundStation;
+use crate::lambert::*;
+use crate::magnetic::{IgrfModel, MagneticModel, IGRF_FILE};
+use crate::maneuver::{ManeuverFrame, ManeuverPlan};
+use crate::math::*;
+use crate::mean_elements::*;
+use crate::orbitalelements::*;
 #[allow(unused_imports)]
 use crate::otherbody::*;
 #[allow(unused_imports)]
 use crate::satbody::*;
+use crate::sgp4::GravityConstants;
+use crate::tle::{Tle, TLE_FILE};
 use egui::*;
 #[allow(unused_imports)]
 use nalgebra::*;
+use std::rc::Rc;
 
 pub struct MyApp {
     central_body: CentralBody,
@@ -23,9 +36,44 @@ pub struct MyApp {
     timeflag: bool,
     storeflag: bool,
     defaultdays: f64,
+    start_epoch: String, // iso 8601, utc unless a scale is given
+    propagator: Propagator,
     coes_window: bool,
-    reference_body_id_input: String,
-    selected_target_body: Option<String>,
+    element_set: ElementSet,
+    coes_input: OrbitalElements, // km and deg, converted when applied
+    equinoctial_input: EquinoctialElements, // km and deg
+    mee_input: ModifiedEquinoctialElements, // km and deg
+    cartesian_input: Vector6<f64>, // km and km/s
+    mean_theory: Option<MeanElementTheory>, // None for osculating elements
+    coes_reference: Option<usize>, // index into other_bodies, None for the central body
+    coes_target: Option<CoesTarget>,
+    lambert_window: bool,
+    lambert_input: LambertInput,
+    tle_constants: GravityConstants,
+    ground_stations: Vec<GroundStation>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ElementSet {
+    Cartesian,
+    Classical,
+    Equinoctial,
+    ModifiedEquinoctial,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CoesTarget {
+    Satellite(usize),
+    Other(usize),
+}
+
+struct LambertInput {
+    satellite: Option<usize>, // index into sat_bodies
+    target: Vector6<f64>,     // km and km/s, relative to the central body
+    tof: f64,                 // s
+    max_revs: usize,
+    prograde: bool,
+    rendezvous: bool, // also match the target velocity on arrival
 }
 
 impl Default for MyApp {
@@ -40,9 +88,28 @@ impl Default for MyApp {
             timeflag: true,
             storeflag: true,
             defaultdays: 2.,
+            start_epoch: String::from("2000-01-01T12:00:00 TT"), // J2000
+            propagator: Propagator::Numerical,
             coes_window: false,
-            reference_body_id_input: "9999".to_string(), // default to central body
-            selected_target_body: None,
+            element_set: ElementSet::Classical,
+            coes_input: OrbitalElements::new(),
+            equinoctial_input: EquinoctialElements::new(),
+            mee_input: ModifiedEquinoctialElements::new(),
+            cartesian_input: Vector6::zeros(),
+            mean_theory: None,
+            coes_reference: None, // default to central body
+            coes_target: None,
+            lambert_window: false,
+            lambert_input: LambertInput {
+                satellite: None,
+                target: Vector6::zeros(),
+                tof: 3600.,
+                max_revs: 0,
+                prograde: true,
+                rendezvous: false,
+            },
+            tle_constants: GravityConstants::Wgs72,
+            ground_stations: vec![],
         }
     }
 }
@@ -57,6 +124,18 @@ impl eframe::App for MyApp {
                     self.default_scenario(self.defaultdays);
                 }
             });
+            ui.horizontal(|ui| {
+                ui.label("Start Epoch:");
+                ui.text_edit_singleline(&mut self.start_epoch);
+                match Epoch::parse(&self.start_epoch, &self.central_body.time_data) {
+                    Ok(epoch) => ui.label(format!(
+                        "{}, JD {:.6} TT",
+                        epoch.to_iso(TimeScale::Utc, &self.central_body.time_data),
+                        epoch.jd(TimeScale::Tt, &self.central_body.time_data)
+                    )),
+                    Err(e) => ui.colored_label(Color32::RED, e.to_string()),
+                };
+            });
             // CentralBody UI
             ui.group(|ui| {
                 ui.label("Central Body");
@@ -80,6 +159,8 @@ impl eframe::App for MyApp {
                         .speed(1e2)
                         .suffix(" km"),
                 );
+                ui.label("Flattening");
+                ui.add(egui::DragValue::new(&mut self.central_body.flattening).speed(1e-5));
                 ui.label("Rotational Speed");
                 ui.add(
                     egui::DragValue::new(&mut self.central_body.omega)
@@ -101,12 +182,23 @@ impl eframe::App for MyApp {
                     .text("Max Degree")
                     .logarithmic(true),
                 );
+                ui.horizontal(|ui| {
+                    ui.label("Orientation:");
+                    let model = &mut self.central_body.orientation.model;
+                    ui.radio_value(model, FrameModel::Rotation, "Uniform Rotation");
+                    ui.radio_value(model, FrameModel::Iau76Fk5, "IAU-76/FK5");
+                    ui.radio_value(model, FrameModel::Iau2006Cio, "IAU 2006/2000B CIO");
+                });
                 if ui.button("Default Earth Values").clicked() {
                     self.central_body.name = String::from("Earth");
                     self.central_body.mass = 5.97219e24; // kg
-                    self.central_body.mu = 3.986004418000000e+5; // km^3/s^2
+                    self.central_body.mu = 3.986004418e5; // km^3/s^2
                     self.central_body.equatorial_radius = 6378.137; // km
+                    self.central_body.flattening = 1. / 298.257223563; // wgs84
                     self.central_body.omega = 7.292115e-5;
+                    self.central_body.prime_meridian =
+                        280.46061837504 * std::f64::consts::PI / 180.;
+                    self.central_body.zonals = EARTH_ZONALS.to_vec();
                 }
             });
 
@@ -170,73 +262,108 @@ impl eframe::App for MyApp {
                                     .speed(0.1)
                                     .suffix(" kg"),
                             );
+                            ui.label("Dry Mass");
+                            ui.add(
+                                egui::DragValue::new(&mut sat_body.maneuvers.dry_mass)
+                                    .speed(0.1)
+                                    .clamp_range(0.0..=f64::MAX)
+                                    .suffix(" kg"),
+                            );
                             ui.horizontal(|ui| {
                                 ui.label("Initial State:");
                                 for state in sat_body.state.iter_mut() {
                                     ui.add(egui::DragValue::new(state).speed(1e-3).suffix(" km"));
                                 }
                             });
+                            if let Some(sgp4) = &sat_body.sgp4 {
+                                let tle = &sgp4.tle;
+                                ui.horizontal(|ui| {
+                                    ui.label(format!(
+                                        "TLE {} epoch {}{:012.8}",
+                                        tle.satnum,
+                                        tle.epoch_year % 100,
+                                        tle.epoch_days
+                                    ));
+                                    if ui.button("Start at TLE Epoch").clicked() {
+                                        let time_data = &self.central_body.time_data;
+                                        let epoch = tle.epoch(time_data);
+                                        self.start_epoch = epoch.to_iso(TimeScale::Utc, time_data);
+                                        match sgp4.inertial_state(&epoch.j2000_seconds(), time_data)
+                                        {
+                                            Ok(state) => sat_body
+                                                .state
+                                                .fixed_rows_mut::<6>(0)
+                                                .copy_from(&state),
+                                            Err(e) => println!("SGP4 error: {:?}", e),
+                                        }
+                                    }
+                                });
+                            }
+                            perturbation_editor(ui, &mut sat_body.perturbations);
+                            attitude_editor(ui, sat_body);
                             if ui.button("Remove").clicked() {
                                 to_remove_other.push(index);
                             }
                         });
                     });
                 }
-                if ui.button("Add Satellite Body").clicked() {
-                    self.sat_bodies.push(SatBody::new());
-                }
+                ui.horizontal(|ui| {
+                    if ui.button("Add Satellite Body").clicked() {
+                        self.sat_bodies.push(SatBody::new());
+                    }
+                    // element sets from the tle file, initialized at the start epoch
+                    if ui.button("Import TLEs").clicked() {
+                        self.import_tles();
+                    }
+                    ui.radio_value(&mut self.tle_constants, GravityConstants::Wgs72, "WGS-72");
+                    ui.radio_value(&mut self.tle_constants, GravityConstants::Wgs84, "WGS-84");
+                });
                 to_remove_other.reverse();
                 for index in to_remove_other {
                     self.sat_bodies.remove(index);
                 }
             });
+            // Ground stations UI
+            ui.group(|ui| {
+                ui.label("Ground Stations");
+                let mut to_remove = Vec::new();
+                for (index, station) in self.ground_stations.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ground_station_editor(ui, station);
+                        if ui.button("Remove").clicked() {
+                            to_remove.push(index);
+                        }
+                    });
+                }
+                if ui.button("Add Ground Station").clicked() {
+                    self.ground_stations.push(GroundStation::new());
+                }
+                to_remove.reverse();
+                for index in to_remove {
+                    self.ground_stations.remove(index);
+                }
+            });
             // COES window
-            if ui.button("Input COEs").clicked() {
+            if ui.button("Input Orbital Elements").clicked() {
                 self.coes_window = true;
             }
             if self.coes_window {
-                egui::Window::new("Input Classical Orbital Elements").show(ctx, |ui| {
-                    // Fields for the COEs
-                    let mut coes: Vector6<f64> = Vector6::zeros(); // Or wherever you want to store them
-                    ui.label("Classical Orbital Elements:");
-                    for element in coes.iter_mut() {
-                        ui.add(egui::DragValue::new(element).speed(0.01));
-                    }
-
-                    let mut reference_body_id: usize = 9999; // default to central body
-                    ui.horizontal(|ui| {
-                        ui.label("Reference Body ID (9999 for Central Body):");
-                        ui.add(egui::DragValue::new(&mut reference_body_id).speed(1.0));
-                    });
-
-                    if ui.button("Calculate State from COEs").clicked() {
-                        let ref_state = if reference_body_id != 9999 {
-                            self.other_bodies[reference_body_id].state.clone()
-                        } else {
-                            Vector6::zeros() // Assuming central body state is (0,0,0,0,0,0)
-                        };
-                        // Here, you should convert the COEs to state and add ref_state.
-                        coes = Vector6::zeros(); // conversion function
-                    }
-
-                    let mut target_body_id: usize = 0;
-                    ui.horizontal(|ui| {
-                        ui.label("Target Body ID:");
-                        ui.add(egui::DragValue::new(&mut target_body_id).speed(1.0));
-                    });
-
-                    if ui.button("Copy State to Target Body").clicked() {
-                        if target_body_id < self.other_bodies.len() {
-                            self.other_bodies[target_body_id].state = coes.clone();
-                        }
-                        // If you want to handle SatBodies separately, add another condition here
-                        self.coes_window = false; // Close the window
-                    }
-
-                    if ui.button("Close").clicked() {
-                        self.coes_window = false; // Just to close without doing anything
-                    }
-                });
+                let mut open = true;
+                egui::Window::new("Input Orbital Elements")
+                    .open(&mut open)
+                    .show(ctx, |ui| self.coes_editor(ui));
+                self.coes_window &= open;
+            }
+            // Lambert targeting window
+            if ui.button("Lambert Targeting").clicked() {
+                self.lambert_window = true;
+            }
+            if self.lambert_window {
+                let mut open = true;
+                egui::Window::new("Lambert Targeting")
+                    .open(&mut open)
+                    .show(ctx, |ui| self.lambert_editor(ui));
+                self.lambert_window &= open;
             }
             ui.group(|ui| {
                 ui.label("Dynamical System Configuration");
@@ -255,11 +382,54 @@ impl eframe::App for MyApp {
                     );
                 });
 
+                ui.horizontal(|ui| {
+                    ui.label("Propagator:");
+                    ui.radio_value(&mut self.propagator, Propagator::Numerical, "Numerical");
+                    ui.radio_value(&mut self.propagator, Propagator::Keplerian, "Keplerian");
+                    ui.radio_value(
+                        &mut self.propagator,
+                        Propagator::MeanElements,
+                        "Mean elements",
+                    );
+                });
+
                 ui.checkbox(&mut self.writeflag, "Write Flag");
                 ui.checkbox(&mut self.timeflag, "Time Flag");
                 ui.checkbox(&mut self.storeflag, "Store Flag");
 
+                // the J gravity model and the mean elements only have the zonals to work with,
+                // which are unset for anything but the earth preset
+                let j_model = self.central_body.max_order > 1 && self.central_body.max_deg == 0;
+                let mean_elements = self.propagator == Propagator::MeanElements;
+                let zonal_error = if !j_model && !mean_elements {
+                    None
+                } else if self.central_body.zonal(2) == 0. {
+                    Some(String::from(
+                        "J gravity and mean elements need a nonzero J2 for the central body",
+                    ))
+                } else if j_model && self.central_body.max_order > MAX_ZONAL_ORDER {
+                    Some(format!(
+                        "J gravity goes up to J{}, use spherical harmonics for higher orders",
+                        MAX_ZONAL_ORDER
+                    ))
+                } else {
+                    None
+                };
+                // a massless satellite or an analytic propagator would drop thrust, drag and
+                // radiation pressure without notice
+                let setup_error = zonal_error.or_else(|| {
+                    self.sat_bodies
+                        .iter()
+                        .find_map(|sat_body| sat_body.setup_error(self.propagator))
+                });
+                if let Some(message) = &setup_error {
+                    ui.colored_label(Color32::RED, message);
+                }
                 if ui.button("Run Propagation").clicked() {
+                    if let Some(message) = setup_error {
+                        println!("{}", message);
+                        return;
+                    }
                     // create spherical harmonic tables if needed
                     if self.central_body.max_order > 1 && self.central_body.max_deg > 0 {
                         let filename = if self.central_body.max_order > 361 {
@@ -278,6 +448,10 @@ impl eframe::App for MyApp {
                             .expect("Could not read file");
                     }
 
+                    self.read_leap_seconds();
+                    self.read_orientation_files();
+                    let start_time = self.start_time();
+
                     let mut satellite_references: Vec<&mut SatBody> =
                         self.sat_bodies.iter_mut().collect();
                     let mut otherbody_references: Vec<&mut OtherBody> =
@@ -306,22 +480,42 @@ impl eframe::App for MyApp {
                     };
 
                     let mut sys_temp = DynamicalSystem {
-                        maxsteps: self.maxsteps as usize,
+                        maxsteps: self.maxsteps,
                         step_width: self.step_width,
-                        time: 0.,
+                        time: start_time,
                         eoms: &mut gravity,
                         writeflag: self.writeflag,
                         timeflag: self.timeflag,
                         storeflag: self.storeflag,
                         time_history: vec![],
+                        propagator: self.propagator,
                     };
 
                     sys_temp.propagate();
+                    // the next run continues from where this one stopped
+                    self.start_epoch = Epoch::from_j2000_seconds(sys_temp.time)
+                        .to_iso(TimeScale::Utc, &self.central_body.time_data);
                     if sys_temp.writeflag && sys_temp.storeflag {
                         match sys_temp.writebinary() {
                             Ok(_) => println!("Writing succesful"),
                             Err(e) => println!("Error during writing: {}", e),
                         }
+                        match sys_temp.writemaneuvers() {
+                            Ok(_) => println!("Writing succesful"),
+                            Err(e) => println!("Error during writing: {}", e),
+                        }
+                        match sys_temp.writegroundtracks() {
+                            Ok(_) => println!("Writing succesful"),
+                            Err(e) => println!("Error during writing: {}", e),
+                        }
+                        match sys_temp.writeresiduals() {
+                            Ok(_) => println!("Writing succesful"),
+                            Err(e) => println!("Error during writing: {}", e),
+                        }
+                        match sys_temp.writelookangles(&self.ground_stations) {
+                            Ok(_) => println!("Writing succesful"),
+                            Err(e) => println!("Error during writing: {}", e),
+                        }
                         // match sys_temp.writefiles() {
                         //     Ok(_) => println!("Writing succesful"),
                         //     Err(e) => println!("Error during writing: {}", e),
@@ -329,13 +523,15 @@ impl eframe::App for MyApp {
                     }
                     for sat in self.sat_bodies.iter_mut() {
                         if let Some(last_state) = sat.state_history.last() {
-                            sat.state = Vector6::from_vec(last_state.clone());
+                            sat.state = DVector::from_vec(last_state.clone());
                         }
                         sat.state_history = vec![];
+                        sat.time_history = vec![];
+                        sat.attitude_history = vec![];
                     }
                     for other in self.other_bodies.iter_mut() {
                         if let Some(last_state) = other.state_history.last() {
-                            other.state = Vector6::from_vec(last_state.clone());
+                            other.state = DVector::from_vec(last_state.clone());
                         }
                         other.state_history = vec![];
                     }
@@ -345,19 +541,807 @@ impl eframe::App for MyApp {
     }
 }
 
+impl ElementSet {
+    fn name(&self) -> &'static str {
+        match self {
+            ElementSet::Cartesian => "Cartesian",
+            ElementSet::Classical => "Classical",
+            ElementSet::Equinoctial => "Equinoctial",
+            ElementSet::ModifiedEquinoctial => "Modified Equinoctial",
+        }
+    }
+}
+
+fn theory_name(theory: Option<MeanElementTheory>) -> &'static str {
+    match theory {
+        None => "Osculating",
+        Some(MeanElementTheory::BrouwerLyddane) => "Brouwer-Lyddane Mean",
+        Some(MeanElementTheory::Kozai) => "Kozai Mean",
+    }
+}
+
+fn perturbation_editor(ui: &mut Ui, perturbations: &mut Vec<Perturbations>) {
+    // toggles for the environmental forces and torques, models start with typical values
+    ui.horizontal(|ui| {
+        ui.label("Perturbations:");
+        let mut toggle = |ui: &mut Ui,
+                          label: &str,
+                          is_model: fn(&Perturbations) -> bool,
+                          model: fn() -> Perturbations| {
+            let mut enabled = perturbations.iter().any(is_model);
+            if ui.checkbox(&mut enabled, label).changed() {
+                if enabled {
+                    perturbations.push(model());
+                } else {
+                    perturbations.retain(|perturbation| !is_model(perturbation));
+                }
+            }
+        };
+        toggle(
+            ui,
+            "Drag",
+            |p| matches!(p, Perturbations::Aerodynamic(_)),
+            || Perturbations::Aerodynamic(AeroAccel::new(2.2, 1.)),
+        );
+        toggle(
+            ui,
+            "Solar Radiation",
+            |p| matches!(p, Perturbations::SolarRadiation(_)),
+            || Perturbations::SolarRadiation(SolarRadiationAccel::new(1., 1.5)),
+        );
+        toggle(
+            ui,
+            "Gravity Gradient",
+            |p| matches!(p, Perturbations::GravityGradient(_)),
+            || Perturbations::GravityGradient(GravityGradientTorque),
+        );
+    });
+    for perturbation in perturbations.iter_mut() {
+        match perturbation {
+            Perturbations::Aerodynamic(model) => {
+                ui.horizontal(|ui| {
+                    ui.label("Drag Cd:");
+                    ui.add(DragValue::new(&mut model.cd).speed(0.01));
+                    ui.label("Area:");
+                    ui.add(DragValue::new(&mut model.area).speed(0.01).suffix(" m^2"));
+                });
+            }
+            Perturbations::SolarRadiation(model) => {
+                ui.horizontal(|ui| {
+                    ui.label("SRP Cr:");
+                    ui.add(DragValue::new(&mut model.cr).speed(0.01));
+                    ui.label("Area:");
+                    ui.add(DragValue::new(&mut model.area).speed(0.01).suffix(" m^2"));
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn ground_station_editor(ui: &mut Ui, station: &mut GroundStation) {
+    // geodetic location on the central body's ellipsoid, angles edited in degrees
+    ui.label("Name");
+    ui.text_edit_singleline(&mut station.name);
+    let location = &mut station.location;
+    let mut lat = location.lat.to_degrees();
+    let mut lon = location.lon.to_degrees();
+    let mut min_elevation = station.min_elevation.to_degrees();
+    ui.label("Latitude");
+    if ui
+        .add(
+            DragValue::new(&mut lat)
+                .speed(0.01)
+                .clamp_range(-90.0..=90.)
+                .suffix(" deg"),
+        )
+        .changed()
+    {
+        location.lat = lat.to_radians();
+    }
+    ui.label("Longitude");
+    if ui
+        .add(
+            DragValue::new(&mut lon)
+                .speed(0.01)
+                .clamp_range(-180.0..=180.)
+                .suffix(" deg"),
+        )
+        .changed()
+    {
+        location.lon = lon.to_radians();
+    }
+    ui.label("Altitude");
+    ui.add(DragValue::new(&mut location.alt).speed(1e-3).suffix(" km"));
+    ui.label("Elevation Mask");
+    if ui
+        .add(
+            DragValue::new(&mut min_elevation)
+                .speed(0.1)
+                .clamp_range(-90.0..=90.)
+                .suffix(" deg"),
+        )
+        .changed()
+    {
+        station.min_elevation = min_elevation.to_radians();
+    }
+}
+
+fn attitude_editor(ui: &mut Ui, sat_body: &mut SatBody) {
+    // attitude is integrated when enabled, the adcs starts as a pd controller pointing at nadir
+    let mut enabled = sat_body.attitude.is_some();
+    if ui.checkbox(&mut enabled, "Attitude").changed() {
+        if enabled {
+            sat_body.attitude = Some(Attitude::new(Quaternions::new(vector![0., 0., 0., 1.])));
+        } else {
+            sat_body.attitude = None;
+            sat_body.adcs = None;
+        }
+    }
+    let Some(attitude) = &mut sat_body.attitude else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        ui.label("Initial Attitude (3-2-1):");
+        let mut angles = EulerAngles::dcm2euler(&attitude.dcm, [3, 2, 1])
+            .angle
+            .map(f64::to_degrees);
+        let mut changed = false;
+        for angle in angles.iter_mut() {
+            changed |= ui
+                .add(DragValue::new(angle).speed(0.1).suffix(" deg"))
+                .changed();
+        }
+        if changed {
+            attitude.set(EulerAngles::new(angles.map(f64::to_radians), [3, 2, 1]));
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Angular Velocity:");
+        for omega in sat_body.omega.iter_mut() {
+            ui.add(DragValue::new(omega).speed(1e-3).suffix(" rad/s"));
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Principal Inertia:");
+        for i in 0..3 {
+            ui.add(
+                DragValue::new(&mut sat_body.inertia[(i, i)])
+                    .speed(0.01)
+                    .clamp_range(1e-6..=f64::MAX)
+                    .suffix(" kg.m^2"),
+            );
+        }
+    });
+
+    let mut enabled = sat_body.adcs.is_some();
+    if ui.checkbox(&mut enabled, "ADCS").changed() {
+        sat_body.adcs = if enabled {
+            Some(Adcs::new(
+                PointingMode::Nadir,
+                ControlLaw::Pd { kp: 0.1, kd: 0.6 },
+            ))
+        } else {
+            None
+        };
+    }
+    let Some(adcs) = &mut sat_body.adcs else {
+        return;
+    };
+    ui.horizontal(|ui| {
+        ui.label("Pointing:");
+        if ui
+            .radio(
+                matches!(adcs.mode, PointingMode::Inertial(_)),
+                "Hold Attitude",
+            )
+            .clicked()
+        {
+            adcs.mode = PointingMode::Inertial(attitude.dcm);
+        }
+        if ui
+            .radio(matches!(adcs.mode, PointingMode::Nadir), "Nadir")
+            .clicked()
+        {
+            adcs.mode = PointingMode::Nadir;
+        }
+        if ui
+            .radio(matches!(adcs.mode, PointingMode::Sun), "Sun")
+            .clicked()
+        {
+            adcs.mode = PointingMode::Sun;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Control Law:");
+        let (kp, kd) = match adcs.law {
+            ControlLaw::Pd { kp, kd } | ControlLaw::QuaternionFeedback { kp, kd } => (kp, kd),
+        };
+        if ui
+            .radio(matches!(adcs.law, ControlLaw::Pd { .. }), "MRP PD")
+            .clicked()
+        {
+            adcs.law = ControlLaw::Pd { kp, kd };
+        }
+        if ui
+            .radio(
+                matches!(adcs.law, ControlLaw::QuaternionFeedback { .. }),
+                "Quaternion Feedback",
+            )
+            .clicked()
+        {
+            adcs.law = ControlLaw::QuaternionFeedback { kp, kd };
+        }
+        match &mut adcs.law {
+            ControlLaw::Pd { kp, kd } | ControlLaw::QuaternionFeedback { kp, kd } => {
+                ui.label("Kp:");
+                ui.add(DragValue::new(kp).speed(1e-3).suffix(" N.m"));
+                ui.label("Kd:");
+                ui.add(DragValue::new(kd).speed(1e-3).suffix(" N.m.s"));
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Attitude Determination:");
+        ui.radio_value(&mut adcs.estimator, None, "True Attitude");
+        for estimator in [
+            AttitudeEstimator::Triad,
+            AttitudeEstimator::QMethod,
+            AttitudeEstimator::Quest,
+            AttitudeEstimator::Esoq2,
+        ] {
+            ui.radio_value(&mut adcs.estimator, Some(estimator), estimator.name());
+        }
+    });
+    ui.horizontal(|ui| {
+        // one actuator along each body axis
+        let mut wheels = !adcs.wheels.is_empty();
+        if ui.checkbox(&mut wheels, "Reaction Wheels").changed() {
+            adcs.wheels = if wheels {
+                [Vector3::x(), Vector3::y(), Vector3::z()]
+                    .iter()
+                    .map(|axis| ReactionWheel::new(*axis, 0.01, 0.1, 600., 6.))
+                    .collect()
+            } else {
+                vec![]
+            };
+        }
+        let mut magnetorquers = !adcs.magnetorquers.is_empty();
+        if ui.checkbox(&mut magnetorquers, "Magnetorquers").changed() {
+            adcs.magnetorquers = if magnetorquers {
+                [Vector3::x(), Vector3::y(), Vector3::z()]
+                    .iter()
+                    .map(|axis| Magnetorquer::new(*axis, 10.))
+                    .collect()
+            } else {
+                vec![]
+            };
+        }
+        if !adcs.wheels.is_empty() && !adcs.magnetorquers.is_empty() {
+            ui.label("Momentum Dump Gain:");
+            ui.add(
+                DragValue::new(&mut adcs.dump_gain)
+                    .speed(1e-4)
+                    .suffix(" 1/s"),
+            );
+        }
+    });
+    ui.horizontal(|ui| {
+        // used by the magnetorquers and the magnetometer
+        ui.label("Magnetic Field:");
+        if ui
+            .radio(matches!(adcs.field, MagneticModel::Dipole), "Dipole")
+            .clicked()
+        {
+            adcs.field = MagneticModel::Dipole;
+        }
+        if ui
+            .radio(matches!(adcs.field, MagneticModel::Igrf(_)), "IGRF-13")
+            .clicked()
+        {
+            match IgrfModel::read(IGRF_FILE) {
+                Ok(model) => adcs.field = MagneticModel::Igrf(Rc::new(model)),
+                Err(e) => println!("Error reading {}: {}", IGRF_FILE, e),
+            }
+        }
+    });
+}
+
 impl MyApp {
+    fn coes_editor(&mut self, ui: &mut Ui) {
+        // initial conditions in any element set relative to a reference body, applied to a
+        // satellite or other body
+        ComboBox::from_label("Element Set")
+            .selected_text(self.element_set.name())
+            .show_ui(ui, |ui| {
+                for set in [
+                    ElementSet::Cartesian,
+                    ElementSet::Classical,
+                    ElementSet::Equinoctial,
+                    ElementSet::ModifiedEquinoctial,
+                ] {
+                    ui.selectable_value(&mut self.element_set, set, set.name());
+                }
+            });
+
+        let element_row = |ui: &mut Ui, label: &str, value: &mut f64, speed: f64, suffix: &str| {
+            ui.label(label);
+            ui.add(DragValue::new(value).speed(speed).suffix(suffix));
+            ui.end_row();
+        };
+        Grid::new("coes_grid")
+            .num_columns(2)
+            .show(ui, |ui| match self.element_set {
+                ElementSet::Cartesian => {
+                    let state = &mut self.cartesian_input;
+                    for (i, label) in ["x", "y", "z"].iter().enumerate() {
+                        element_row(ui, label, &mut state[i], 1., " km");
+                    }
+                    for (i, label) in ["vx", "vy", "vz"].iter().enumerate() {
+                        element_row(ui, label, &mut state[i + 3], 1e-3, " km/s");
+                    }
+                }
+                ElementSet::Classical => {
+                    let coes = &mut self.coes_input;
+                    element_row(ui, "Semi-major Axis", &mut coes.sma, 1., " km");
+                    ui.label("Eccentricity");
+                    ui.add(
+                        DragValue::new(&mut coes.ecc)
+                            .speed(1e-3)
+                            .clamp_range(0.0..=f64::MAX),
+                    );
+                    ui.end_row();
+                    ui.label("Inclination");
+                    ui.add(
+                        DragValue::new(&mut coes.inc)
+                            .speed(0.1)
+                            .clamp_range(0.0..=180.0)
+                            .suffix(" deg"),
+                    );
+                    ui.end_row();
+                    element_row(ui, "RAAN", &mut coes.raan, 0.1, " deg");
+                    element_row(ui, "Argument of Periapsis", &mut coes.aop, 0.1, " deg");
+                    element_row(ui, "True Anomaly", &mut coes.ta, 0.1, " deg");
+                }
+                ElementSet::Equinoctial => {
+                    let elements = &mut self.equinoctial_input;
+                    element_row(ui, "Semi-major Axis", &mut elements.sma, 1., " km");
+                    element_row(ui, "h = e sin(w + RAAN)", &mut elements.h, 1e-3, "");
+                    element_row(ui, "k = e cos(w + RAAN)", &mut elements.k, 1e-3, "");
+                    element_row(ui, "p = tan(i/2) sin(RAAN)", &mut elements.p, 1e-3, "");
+                    element_row(ui, "q = tan(i/2) cos(RAAN)", &mut elements.q, 1e-3, "");
+                    element_row(ui, "Mean Longitude", &mut elements.mean_lon, 0.1, " deg");
+                }
+                ElementSet::ModifiedEquinoctial => {
+                    let elements = &mut self.mee_input;
+                    element_row(ui, "Semi-latus Rectum", &mut elements.param, 1., " km");
+                    element_row(ui, "f = e cos(w + RAAN)", &mut elements.f, 1e-3, "");
+                    element_row(ui, "g = e sin(w + RAAN)", &mut elements.g, 1e-3, "");
+                    element_row(ui, "h = tan(i/2) cos(RAAN)", &mut elements.h, 1e-3, "");
+                    element_row(ui, "k = tan(i/2) sin(RAAN)", &mut elements.k, 1e-3, "");
+                    element_row(ui, "True Longitude", &mut elements.true_lon, 0.1, " deg");
+                }
+            });
+
+        let central_name = format!("Central Body ({})", self.central_body.name);
+        let reference_name = |reference: Option<usize>| -> String {
+            match reference {
+                None => central_name.clone(),
+                Some(index) => format!("{} (other {})", self.other_bodies[index].name, index),
+            }
+        };
+        if matches!(self.coes_reference, Some(index) if index >= self.other_bodies.len()) {
+            self.coes_reference = None;
+        }
+        ComboBox::from_label("Reference Body")
+            .selected_text(reference_name(self.coes_reference))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.coes_reference, None, reference_name(None));
+                for index in 0..self.other_bodies.len() {
+                    ui.selectable_value(
+                        &mut self.coes_reference,
+                        Some(index),
+                        reference_name(Some(index)),
+                    );
+                }
+            });
+
+        // mean elements are about the central body's zonal harmonics
+        if self.coes_reference.is_none() {
+            ComboBox::from_label("Elements")
+                .selected_text(theory_name(self.mean_theory))
+                .show_ui(ui, |ui| {
+                    for theory in [
+                        None,
+                        Some(MeanElementTheory::BrouwerLyddane),
+                        Some(MeanElementTheory::Kozai),
+                    ] {
+                        ui.selectable_value(&mut self.mean_theory, theory, theory_name(theory));
+                    }
+                });
+        }
+
+        let target_name = |target: Option<CoesTarget>| -> String {
+            match target {
+                None => String::from("Select a body"),
+                Some(CoesTarget::Satellite(index)) => {
+                    format!("{} (satellite {})", self.sat_bodies[index].name, index)
+                }
+                Some(CoesTarget::Other(index)) => {
+                    format!("{} (other {})", self.other_bodies[index].name, index)
+                }
+            }
+        };
+        let target_valid = match self.coes_target {
+            None => true,
+            Some(CoesTarget::Satellite(index)) => index < self.sat_bodies.len(),
+            Some(CoesTarget::Other(index)) => index < self.other_bodies.len(),
+        };
+        if !target_valid {
+            self.coes_target = None;
+        }
+        ComboBox::from_label("Target Body")
+            .selected_text(target_name(self.coes_target))
+            .show_ui(ui, |ui| {
+                for index in 0..self.sat_bodies.len() {
+                    let target = Some(CoesTarget::Satellite(index));
+                    ui.selectable_value(&mut self.coes_target, target, target_name(target));
+                }
+                for index in 0..self.other_bodies.len() {
+                    let target = Some(CoesTarget::Other(index));
+                    ui.selectable_value(&mut self.coes_target, target, target_name(target));
+                }
+            });
+
+        // the reference body cannot orbit itself
+        let self_reference = matches!(
+            (self.coes_target, self.coes_reference),
+            (Some(CoesTarget::Other(target)), Some(reference)) if target == reference
+        );
+        let invalid = match self.element_set {
+            ElementSet::Cartesian => (self.cartesian_input.fixed_rows::<3>(0).norm() == 0.)
+                .then_some("Position must be nonzero"),
+            ElementSet::Classical => {
+                let coes = &self.coes_input;
+                (!((coes.ecc < 1. && coes.sma > 0.) || (coes.ecc > 1. && coes.sma < 0.)))
+                    .then_some("Semi-major axis must be positive for e < 1 and negative for e > 1")
+            }
+            ElementSet::Equinoctial => {
+                let elements = &self.equinoctial_input;
+                (elements.sma <= 0. || elements.h.powi(2) + elements.k.powi(2) >= 1.)
+                    .then_some("Equinoctial elements need an elliptic orbit (a > 0, e < 1)")
+            }
+            ElementSet::ModifiedEquinoctial => {
+                (self.mee_input.param <= 0.).then_some("Semi-latus rectum must be positive")
+            }
+        };
+        let conic_valid = invalid.is_none();
+        if let Some(message) = invalid {
+            ui.colored_label(Color32::RED, message);
+        }
+        if self_reference {
+            ui.colored_label(Color32::RED, "Target and reference body are the same");
+        }
+
+        ui.horizontal(|ui| {
+            let can_apply = self.coes_target.is_some() && conic_valid && !self_reference;
+            if ui
+                .add_enabled(can_apply, Button::new("Apply to Target Body"))
+                .clicked()
+            {
+                self.apply_coes();
+            }
+            if ui
+                .add_enabled(
+                    self.coes_target.is_some() && !self_reference,
+                    Button::new("Load from Target Body"),
+                )
+                .clicked()
+            {
+                self.load_coes();
+            }
+        });
+    }
+
+    fn lambert_editor(&mut self, ui: &mut Ui) {
+        // transfers from a satellite's initial state to a target state about the central body,
+        // the chosen solution is added as impulsive burns at the start epoch (and on arrival)
+        let input = &mut self.lambert_input;
+        if input
+            .satellite
+            .is_some_and(|index| index >= self.sat_bodies.len())
+        {
+            input.satellite = None;
+        }
+        let sat_bodies = &self.sat_bodies;
+        let satellite_name = |satellite: Option<usize>| -> String {
+            match satellite {
+                None => String::from("Select a satellite"),
+                Some(index) => format!("{} (satellite {})", sat_bodies[index].name, index),
+            }
+        };
+        ComboBox::from_label("Satellite")
+            .selected_text(satellite_name(input.satellite))
+            .show_ui(ui, |ui| {
+                for index in 0..sat_bodies.len() {
+                    let satellite = Some(index);
+                    ui.selectable_value(&mut input.satellite, satellite, satellite_name(satellite));
+                }
+            });
+
+        Grid::new("lambert_grid").num_columns(2).show(ui, |ui| {
+            for (i, label) in ["Target x", "Target y", "Target z"].iter().enumerate() {
+                ui.label(*label);
+                ui.add(DragValue::new(&mut input.target[i]).speed(1.).suffix(" km"));
+                ui.end_row();
+            }
+            for (i, label) in ["Target vx", "Target vy", "Target vz"].iter().enumerate() {
+                ui.label(*label);
+                ui.add(
+                    DragValue::new(&mut input.target[i + 3])
+                        .speed(1e-3)
+                        .suffix(" km/s"),
+                );
+                ui.end_row();
+            }
+            ui.label("Time of Flight");
+            ui.add(
+                DragValue::new(&mut input.tof)
+                    .speed(10.)
+                    .clamp_range(0.0..=f64::MAX)
+                    .suffix(" sec"),
+            );
+            ui.end_row();
+            ui.label("Max Revolutions");
+            ui.add(DragValue::new(&mut input.max_revs).clamp_range(0..=10));
+            ui.end_row();
+        });
+        ui.checkbox(&mut input.prograde, "Prograde");
+        ui.checkbox(&mut input.rendezvous, "Match Target Velocity");
+
+        let Some(index) = input.satellite else {
+            return;
+        };
+        let sat_body = &sat_bodies[index];
+        let r1 = Vector3::new(sat_body.state[0], sat_body.state[1], sat_body.state[2]);
+        let v0 = Vector3::new(sat_body.state[3], sat_body.state[4], sat_body.state[5]);
+        let r2: Vector3<f64> = input.target.fixed_rows::<3>(0).into();
+        let v_target: Vector3<f64> = input.target.fixed_rows::<3>(3).into();
+        let solutions = lambert(
+            &r1,
+            &r2,
+            input.tof,
+            self.central_body.mu,
+            input.prograde,
+            input.max_revs,
+        );
+        if solutions.is_empty() {
+            ui.colored_label(
+                Color32::RED,
+                "No transfer, the time of flight must be positive and the positions not collinear",
+            );
+            return;
+        }
+        let mut chosen = None;
+        Grid::new("lambert_solutions")
+            .num_columns(4)
+            .show(ui, |ui| {
+                ui.label("Revolutions");
+                ui.label("Departure dv");
+                ui.label("Arrival dv");
+                ui.end_row();
+                for solution in solutions.iter() {
+                    let branch = match solution.branch {
+                        LambertBranch::Single => "",
+                        LambertBranch::Left => " left",
+                        LambertBranch::Right => " right",
+                    };
+                    ui.label(format!("{}{}", solution.revs, branch));
+                    ui.label(format!("{:.6} km/s", (solution.v1 - v0).norm()));
+                    if input.rendezvous {
+                        ui.label(format!("{:.6} km/s", (v_target - solution.v2).norm()));
+                    } else {
+                        ui.label("-");
+                    }
+                    if ui.button("Add Burns").clicked() {
+                        chosen = Some(*solution);
+                    }
+                    ui.end_row();
+                }
+            });
+        let rendezvous = input.rendezvous;
+        let tof = input.tof;
+        if let Some(solution) = chosen {
+            let departure = self.start_time();
+            let maneuvers = &mut self.sat_bodies[index].maneuvers;
+            maneuvers.add_impulsive(departure, solution.v1 - v0, ManeuverFrame::Inertial);
+            if rendezvous {
+                maneuvers.add_impulsive(
+                    departure + tof,
+                    v_target - solution.v2,
+                    ManeuverFrame::Inertial,
+                );
+            }
+        }
+    }
+
+    fn coes_frame(&self) -> (f64, Vector6<f64>) {
+        // gravitational parameter of the relative orbit and the reference body state
+        let (mut mu, reference_state) = match self.coes_reference {
+            None => (self.central_body.mu, Vector6::zeros()),
+            Some(index) => (
+                self.other_bodies[index].mu,
+                self.other_bodies[index].state.fixed_rows::<6>(0).into(),
+            ),
+        };
+        if let Some(CoesTarget::Other(index)) = self.coes_target {
+            mu += self.other_bodies[index].mu;
+        }
+        (mu, reference_state)
+    }
+
+    fn apply_coes(&mut self) {
+        let (mu, reference_state) = self.coes_frame();
+        let deg = std::f64::consts::PI / 180.;
+        let initial = match self.element_set {
+            ElementSet::Cartesian => InitialConditions::Cartesian(self.cartesian_input),
+            ElementSet::Classical => InitialConditions::Classical(OrbitalElements {
+                inc: self.coes_input.inc * deg,
+                raan: self.coes_input.raan * deg,
+                aop: self.coes_input.aop * deg,
+                ta: self.coes_input.ta * deg,
+                param: 0.,
+                ..self.coes_input
+            }),
+            ElementSet::Equinoctial => InitialConditions::Equinoctial(EquinoctialElements {
+                mean_lon: self.equinoctial_input.mean_lon * deg,
+                ..self.equinoctial_input
+            }),
+            ElementSet::ModifiedEquinoctial => {
+                InitialConditions::ModifiedEquinoctial(ModifiedEquinoctialElements {
+                    true_lon: self.mee_input.true_lon * deg,
+                    ..self.mee_input
+                })
+            }
+        };
+        let mut state = initial.to_state(mu);
+        if let (Some(theory), None) = (self.mean_theory, self.coes_reference) {
+            let osc = mean2osc(&state2coes(&state, mu), &self.central_body, theory);
+            state = coes2state(&osc, mu);
+        }
+        let state = state + reference_state;
+        // only position and velocity are replaced, auxiliary states are kept
+        let target_state = match self.coes_target {
+            Some(CoesTarget::Satellite(index)) => &mut self.sat_bodies[index].state,
+            Some(CoesTarget::Other(index)) => &mut self.other_bodies[index].state,
+            None => return,
+        };
+        target_state.fixed_rows_mut::<6>(0).copy_from(&state);
+    }
+
+    fn load_coes(&mut self) {
+        // fill every element set from the target's current state
+        let (mu, reference_state) = self.coes_frame();
+        let target_state: Vector6<f64> = match self.coes_target {
+            Some(CoesTarget::Satellite(index)) => {
+                self.sat_bodies[index].state.fixed_rows::<6>(0).into()
+            }
+            Some(CoesTarget::Other(index)) => {
+                self.other_bodies[index].state.fixed_rows::<6>(0).into()
+            }
+            None => return,
+        };
+        let mut state = target_state - reference_state;
+        if let (Some(theory), None) = (self.mean_theory, self.coes_reference) {
+            let mean = osc2mean(&state2coes(&state, mu), &self.central_body, theory);
+            state = coes2state(&mean, mu);
+        }
+        let deg = 180. / std::f64::consts::PI;
+
+        self.cartesian_input = state;
+        let coes = state2coes(&state, mu);
+        self.coes_input = OrbitalElements {
+            inc: coes.inc * deg,
+            raan: coes.raan * deg,
+            aop: coes.aop * deg,
+            ta: coes.ta * deg,
+            ..coes
+        };
+        let mee = state2mee(&state, mu);
+        self.mee_input = ModifiedEquinoctialElements {
+            true_lon: mee.true_lon * deg,
+            ..mee
+        };
+        if coes.ecc < 1. {
+            let elements = mee2equinoctial(&mee);
+            self.equinoctial_input = EquinoctialElements {
+                mean_lon: elements.mean_lon * deg,
+                ..elements
+            };
+        }
+    }
+
+    fn read_orientation_files(&mut self) {
+        // earth orientation parameters and the full nutation series for the precession-nutation
+        // models when the files are present, zero parameters and the built in terms otherwise
+        let model = self.central_body.orientation.model;
+        if model == FrameModel::Rotation {
+            return;
+        }
+        let (finals, iau2000a) = match model {
+            FrameModel::Iau2006Cio => (FINALS_2000A_FILE, true),
+            _ => (FINALS_FILE, false),
+        };
+        if std::path::Path::new(finals).exists() {
+            if let Err(e) = self.central_body.read_eop(finals, iau2000a) {
+                println!("Error reading earth orientation parameters: {}", e);
+            }
+        }
+    }
+
+    fn read_leap_seconds(&mut self) {
+        if std::path::Path::new(LEAP_SECOND_FILE).exists() {
+            if let Err(e) = self
+                .central_body
+                .time_data
+                .read_leap_seconds(LEAP_SECOND_FILE)
+            {
+                println!("Error reading leap seconds: {}", e);
+            }
+        }
+    }
+
+    fn import_tles(&mut self) {
+        let tles = match Tle::read(TLE_FILE) {
+            Ok(tles) => tles,
+            Err(e) => {
+                println!("Error reading {}: {}", TLE_FILE, e);
+                return;
+            }
+        };
+        self.read_leap_seconds();
+        let start_time = self.start_time();
+        for tle in tles.iter() {
+            match SatBody::from_tle(
+                tle,
+                self.tle_constants,
+                &start_time,
+                &self.central_body.time_data,
+            ) {
+                Ok(satellite) => self.sat_bodies.push(satellite),
+                Err(e) => println!("SGP4 error for {}: {:?}", tle.satnum, e),
+            }
+        }
+    }
+
+    fn start_time(&self) -> f64 {
+        // simulation time (tt s past J2000) of the start epoch, J2000 if it does not parse
+        match Epoch::parse(&self.start_epoch, &self.central_body.time_data) {
+            Ok(epoch) => epoch.j2000_seconds(),
+            Err(e) => {
+                println!("Invalid start epoch, using J2000: {}", e);
+                0.
+            }
+        }
+    }
+
     fn default_scenario(&mut self, days: f64) {
         let earth = CentralBody {
             name: String::from("Earth"),
-            mass: 5.97219e24,            // kg
-            mu: 3.986004418000000e+5,    // km^3/s^2
-            equatorial_radius: 6378.137, // km
-            omega: 7.292115e-5,          // rad/s
+            mass: 5.97219e24,                                              // kg
+            mu: 3.986004418e5,                                             // km^3/s^2
+            equatorial_radius: 6378.137,                                   // km
+            flattening: 1. / 298.257223563,                                // wgs84
+            omega: 7.292115e-5,                                            // rad/s
+            prime_meridian: 280.46061837504 * std::f64::consts::PI / 180., // gmst at J2000
             max_order: 4, // [0,0] for spherical, [2,0] for J2, [2+,1+] for spherical harmonics
             max_deg: 4,   // order >= degree
             c: vec![vec![]],
             s: vec![vec![]],
-            eci2ecef: Matrix3::zeros(),
+            zonals: EARTH_ZONALS.to_vec(),
+            radiation: RadiationGrid::new(),
+            time_data: TimeData::new(),
+            orientation: EarthOrientation::new(),
         };
         // if earth.max_order > 1 && earth.max_deg > 0 {
         //     let filename = if earth.max_order > 361 {
@@ -371,7 +1355,6 @@ impl MyApp {
         //         .read_sph_coefs(filename, earth.max_order, earth.max_deg)
         //         .expect("Could not read file");
         // }
-        let time_0 = 0.;
         let moon_distance_from_earth = 384400.; // meters
         let moonv0 = (earth.mu / moon_distance_from_earth).sqrt();
         let moon1 = OtherBody {
@@ -379,8 +1362,7 @@ impl MyApp {
             mu: 4.9048695e3,
             id: 1,
             pos_old: Vector3::zeros(),
-            propagate_flag: true,
-            state: vector![moon_distance_from_earth, 0., 0., 0., moonv0, 0.,], // Assuming moon starts on the x-axis and other velocities will be set elsewhere
+            state: dvector![moon_distance_from_earth, 0., 0., 0., moonv0, 0.,], // Assuming moon starts on the x-axis and other velocities will be set elsewhere
             state_history: vec![],
             name: String::from("moon1"),
             coes: OrbitalElements {
@@ -399,8 +1381,7 @@ impl MyApp {
             mu: 4.9048695e3,
             id: 2,
             pos_old: Vector3::zeros(),
-            propagate_flag: true,
-            state: vector![-moon_distance_from_earth, 0., 0., 0., -moonv0, 0.,], // Assuming moon starts on the x-axis and other velocities will be set elsewhere
+            state: dvector![-moon_distance_from_earth, 0., 0., 0., -moonv0, 0.,], // Assuming moon starts on the x-axis and other velocities will be set elsewhere
             state_history: vec![],
             name: String::from("moon2"),
             coes: OrbitalElements {
@@ -423,7 +1404,7 @@ impl MyApp {
         let sat1 = SatBody {
             name: String::from("sat1"),
             mass: 100.,
-            state: vector![
+            state: dvector![
                 moon_distance_from_earth + sat_distance_from_moon_center,
                 0.,
                 0.,
@@ -431,9 +1412,9 @@ impl MyApp {
                 moonv0 + v0_sat,
                 0.,
             ],
-            propagate_flag: true,
+            aux_dxdt: None,
             state_history: vec![],
-            time_history: vec![time_0],
+            time_history: vec![],
             coes: OrbitalElements {
                 sma: 0.,
                 ecc: 0.,
@@ -443,11 +1424,20 @@ impl MyApp {
                 ta: 0.,
                 param: 0.,
             },
+            maneuvers: ManeuverPlan::new(),
+            perturbations: vec![],
+            attitude: None,
+            omega: Vector3::zeros(),
+            inertia: Matrix3::identity(),
+            attitude_history: vec![],
+            panels: vec![],
+            adcs: None,
+            sgp4: None,
         };
         let sat2 = SatBody {
             name: String::from("sat2"), // match struct name
             mass: 100.,                 //kg
-            state: vector![
+            state: dvector![
                 earth.equatorial_radius + 1000.,
                 0.,
                 0.,
@@ -455,9 +1445,9 @@ impl MyApp {
                 v0 * 0.5.sin(),
                 v0 * 0.5.cos(),
             ], // m, m/s
-            propagate_flag: true,
+            aux_dxdt: None,
             state_history: vec![],
-            time_history: vec![time_0],
+            time_history: vec![],
             coes: OrbitalElements {
                 sma: 0.,
                 ecc: 0.,
@@ -467,12 +1457,21 @@ impl MyApp {
                 ta: 0.,
                 param: 0.,
             },
+            maneuvers: ManeuverPlan::new(),
+            perturbations: vec![],
+            attitude: None,
+            omega: Vector3::zeros(),
+            inertia: Matrix3::identity(),
+            attitude_history: vec![],
+            panels: vec![],
+            adcs: None,
+            sgp4: None,
         };
 
         let sat3 = SatBody {
             name: String::from("sat3"),
             mass: 100.,
-            state: vector![
+            state: dvector![
                 -(moon_distance_from_earth + sat_distance_from_moon_center),
                 0.,
                 0.,
@@ -480,9 +1479,9 @@ impl MyApp {
                 -(moonv0 + v0_sat),
                 0.,
             ],
-            propagate_flag: true,
+            aux_dxdt: None,
             state_history: vec![],
-            time_history: vec![time_0],
+            time_history: vec![],
             coes: OrbitalElements {
                 sma: 0.,
                 ecc: 0.,
@@ -492,6 +1491,15 @@ impl MyApp {
                 ta: 0.,
                 param: 0.,
             },
+            maneuvers: ManeuverPlan::new(),
+            perturbations: vec![],
+            attitude: None,
+            omega: Vector3::zeros(),
+            inertia: Matrix3::identity(),
+            attitude_history: vec![],
+            panels: vec![],
+            adcs: None,
+            sgp4: None,
         };
 
         self.central_body = earth;
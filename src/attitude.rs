@@ -4,8 +4,19 @@ use nalgebra::*;
 pub struct Attitude {
     pub euler: Option<EulerAngles>,
     pub quat: Option<Quaternions>,
+    pub mrp: Option<Mrps>,
+    pub crp: Option<Crps>,
+    pub prv: Option<PrincipalRotation>,
     pub dcm: Matrix3<f64>,
-    pub main_rep: String,
+    pub main_rep: AttitudeRep,
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttitudeRep {
+    Euler,
+    Quat,
+    Mrp,
+    Crp,
+    Prv,
 }
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -20,6 +31,22 @@ pub struct EulerAngles {
     pub sequence: [usize; 3],
     name: String,
 }
+#[derive(Debug)]
+pub struct Mrps {
+    // modified rodrigues parameters, sigma = e tan(phi/4)
+    pub sigma: Vector3<f64>,
+}
+#[derive(Debug)]
+pub struct Crps {
+    // classical rodrigues parameters (gibbs vector), q = e tan(phi/2), singular at phi = 180 deg
+    pub q: Vector3<f64>,
+}
+#[derive(Debug)]
+pub struct PrincipalRotation {
+    // principal rotation vector, unit axis e and angle phi (rad)
+    pub axis: Vector3<f64>,
+    pub angle: f64,
+}
 
 #[allow(dead_code)]
 #[allow(unused_variables)]
@@ -39,41 +66,68 @@ impl EulerAngles {
         // A space or inertial sequence takes sequential rotations on the inertial axis to get a
         //     [BN] dcm (from inertial to body)
 
-        fn r1(t: f64) -> Matrix3<f64> {
-            matrix![
-                1.0, 0.0, 0.0;
-                0.0, t.cos(), t.sin();
-                0.0, -t.sin(), t.cos();
-            ]
-        }
-
-        fn r2(t: f64) -> Matrix3<f64> {
-            matrix![
-                t.cos(), 0.0, -t.sin();
-                0.0, 1.0, 0.0;
-                t.sin(), 0.0, t.cos();
-            ]
-        }
-
-        fn r3(t: f64) -> Matrix3<f64> {
-            matrix![
-                t.cos(), t.sin(), 0.0;
-                -t.sin(), t.cos(), 0.0;
-                0.0, 0.0, 1.0;
-            ]
-        }
-        let r_functions = [r1, r2, r3];
         let mut rot = Matrix3::identity(); // Identity matrix
 
         for i in (0..3).rev() {
-            rot = rot * r_functions[self.sequence[i] - 1](self.angle[i]);
+            rot *= axis_rotation(self.sequence[i], self.angle[i]);
         }
         rot
     }
-    pub fn dcm2euler(&self, dcm: Matrix3<f64>, new_sequence: [usize; 3]) /*-> Vector3<f64>*/
-    {
-        // dcm2euler here, depends on sequence
-        // self.sequence = new_sequence;
+    pub fn dcm2euler(dcm: &Matrix3<f64>, sequence: [usize; 3]) -> Self {
+        // inverse of euler2dcm for any of the 6 asymmetric (e.g. 3-2-1) or 6 symmetric
+        // (e.g. 3-1-3) body sequences
+        // angle ranges: first and third angles in (-pi, pi], second angle in [-pi/2, pi/2] for
+        //     asymmetric sequences and [0, pi] for symmetric sequences
+        // gimbal lock (second angle at +-pi/2 for asymmetric, 0 or pi for symmetric) only
+        //     determines the sum/difference of the first and third angles, the third angle is
+        //     set to zero and the whole rotation is put in the first angle
+        assert!(
+            sequence.iter().all(|axis| (1..=3).contains(axis))
+                && sequence[0] != sequence[1]
+                && sequence[1] != sequence[2],
+            "invalid euler angle sequence {:?}",
+            sequence
+        );
+        let gimbal_tol = 1e-10;
+        let i = sequence[0] - 1;
+        let j = sequence[1] - 1;
+        let k = sequence[2] - 1;
+
+        let mut angle = Vector3::zeros();
+        let locked;
+        if i != k {
+            // asymmetric, sign of the permutation (i, j, k)
+            let eps = if (j + 3 - i) % 3 == 1 { 1. } else { -1. };
+            let s2 = (eps * dcm[(k, i)]).clamp(-1., 1.);
+            angle[1] = s2.asin();
+            locked = 1. - s2.abs() <= gimbal_tol;
+            if !locked {
+                angle[0] = (-eps * dcm[(k, j)]).atan2(dcm[(k, k)]);
+                angle[2] = (-eps * dcm[(j, i)]).atan2(dcm[(i, i)]);
+            }
+        } else {
+            // symmetric, l is the axis not used in the sequence
+            let l = 3 - i - j;
+            let eps = if (j + 3 - i) % 3 == 1 { 1. } else { -1. };
+            let c2 = dcm[(i, i)].clamp(-1., 1.);
+            angle[1] = c2.acos();
+            locked = 1. - c2.abs() <= gimbal_tol;
+            if !locked {
+                angle[0] = dcm[(i, j)].atan2(-eps * dcm[(i, l)]);
+                angle[2] = dcm[(j, i)].atan2(eps * dcm[(l, i)]);
+            }
+        }
+
+        if locked {
+            // gimbal lock, with the third angle at zero the remaining rotation
+            // about the first axis is [R_j(t2)]^T [C]
+            let rot = axis_rotation(sequence[1], angle[1]).transpose() * dcm;
+            let p = (i + 1) % 3;
+            let q = (i + 2) % 3;
+            angle[0] = rot[(p, q)].atan2(rot[(p, p)]);
+        }
+
+        Self::new(angle, sequence)
     }
 }
 
@@ -112,46 +166,1020 @@ impl Quaternions {
 
         Matrix3::from_rows(&[c1, c2, c3])
     }
-    #[allow(dead_code)]
-    #[allow(unused_variables)]
-    pub fn dcm2quat(&self, dcm: Matrix3<f64>) /*-> Vector4<f64>*/
-    {
-        // dcm2quat code here, probably use shepards methods
+    pub fn dcm2quat(dcm: &Matrix3<f64>) -> Self {
+        // Shepperd's method: find the largest squared component from the trace and diagonal
+        // and divide by it to get the others, avoids dividing by a small number near 180 deg
+        // rotations. Quaternion is scalar last [q1, q2, q3, q4] with q4 >= 0
+        let trace = dcm.trace();
+        let squares = [
+            (1. + 2. * dcm[(0, 0)] - trace) / 4.,
+            (1. + 2. * dcm[(1, 1)] - trace) / 4.,
+            (1. + 2. * dcm[(2, 2)] - trace) / 4.,
+            (1. + trace) / 4.,
+        ];
+        let mut largest = 0;
+        for i in 1..4 {
+            if squares[i] > squares[largest] {
+                largest = i;
+            }
+        }
+
+        let q = squares[largest].sqrt();
+        let mut quaternion = match largest {
+            0 => vector![
+                q,
+                (dcm[(0, 1)] + dcm[(1, 0)]) / (4. * q),
+                (dcm[(2, 0)] + dcm[(0, 2)]) / (4. * q),
+                (dcm[(1, 2)] - dcm[(2, 1)]) / (4. * q)
+            ],
+            1 => vector![
+                (dcm[(0, 1)] + dcm[(1, 0)]) / (4. * q),
+                q,
+                (dcm[(1, 2)] + dcm[(2, 1)]) / (4. * q),
+                (dcm[(2, 0)] - dcm[(0, 2)]) / (4. * q)
+            ],
+            2 => vector![
+                (dcm[(2, 0)] + dcm[(0, 2)]) / (4. * q),
+                (dcm[(1, 2)] + dcm[(2, 1)]) / (4. * q),
+                q,
+                (dcm[(0, 1)] - dcm[(1, 0)]) / (4. * q)
+            ],
+            _ => vector![
+                (dcm[(1, 2)] - dcm[(2, 1)]) / (4. * q),
+                (dcm[(2, 0)] - dcm[(0, 2)]) / (4. * q),
+                (dcm[(0, 1)] - dcm[(1, 0)]) / (4. * q),
+                q
+            ],
+        };
+
+        // pick the short rotation (positive scalar) and remove any drift from a non-orthogonal dcm
+        if quaternion[3] < 0. {
+            quaternion = -quaternion;
+        }
+        Self::new(quaternion.normalize())
+    }
+
+    pub fn kinematics(quaternion: &Vector4<f64>, omega: &Vector3<f64>) -> Vector4<f64> {
+        // quaternion rates from body angular velocity omega (rad/s, body frame)
+        // q_vec_dot = (q4 omega + q_vec x omega) / 2, q4_dot = -(q_vec . omega) / 2
+        let q_vec: Vector3<f64> = quaternion.fixed_rows::<3>(0).into();
+        let q_vec_dot = (quaternion[3] * omega + q_vec.cross(omega)) * 0.5;
+        vector![
+            q_vec_dot[0],
+            q_vec_dot[1],
+            q_vec_dot[2],
+            -0.5 * q_vec.dot(omega)
+        ]
+    }
+
+    // quaternion algebra, scalar last [q1, q2, q3, q4] describing [BN] like quat2dcm. Products
+    // are in dcm order: q_FN = q_FB.compose(q_BN) gives [FN] = [FB][BN]
+
+    pub fn compose(&self, other: &Self) -> Self {
+        // [v, s] x [v', s'] = [s v' + s' v - v x v', s s' - v . v']
+        let (v1, s1) = (self.vector(), self.quaternion[3]);
+        let (v2, s2) = (other.vector(), other.quaternion[3]);
+        let v = s1 * v2 + s2 * v1 - v1.cross(&v2);
+        Self::new(vector![v[0], v[1], v[2], s1 * s2 - v1.dot(&v2)])
+    }
+
+    pub fn vector(&self) -> Vector3<f64> {
+        self.quaternion.fixed_rows::<3>(0).into()
+    }
+
+    pub fn conjugate(&self) -> Self {
+        let q = self.quaternion;
+        Self::new(vector![-q[0], -q[1], -q[2], q[3]])
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self::new(self.conjugate().quaternion / self.quaternion.norm_squared())
+    }
+
+    pub fn normalize(&self) -> Self {
+        Self::new(self.quaternion.normalize())
+    }
+
+    pub fn rotate(&self, vec: &Vector3<f64>) -> Vector3<f64> {
+        // coordinates of an inertial vector in the body frame, same as [BN] v
+        let (q_vec, q4) = (self.vector(), self.quaternion[3]);
+        (q4.powi(2) - q_vec.norm_squared()) * vec + 2. * q_vec * q_vec.dot(vec)
+            - 2. * q4 * q_vec.cross(vec)
+    }
+
+    pub fn log(&self) -> Vector3<f64> {
+        // half angle rotation vector of a unit quaternion, q = [e sin(t), cos(t)] -> e t
+        let q_vec = self.vector();
+        let sin_half = q_vec.norm();
+        if sin_half < 1e-12 {
+            return q_vec;
+        }
+        q_vec * (sin_half.atan2(self.quaternion[3]) / sin_half)
+    }
+
+    pub fn exp(vec: &Vector3<f64>) -> Self {
+        // inverse of log, e t -> [e sin(t), cos(t)]
+        let angle = vec.norm();
+        let scale = if angle < 1e-12 {
+            1.
+        } else {
+            angle.sin() / angle
+        };
+        Self::new(vector![
+            vec[0] * scale,
+            vec[1] * scale,
+            vec[2] * scale,
+            angle.cos()
+        ])
+    }
+
+    pub fn slerp(&self, other: &Self, t: f64) -> Self {
+        // constant rate interpolation along the shorter arc, t in [0, 1]
+        let mut end = other.quaternion;
+        let mut cos_angle = self.quaternion.dot(&end);
+        if cos_angle < 0. {
+            end = -end;
+            cos_angle = -cos_angle;
+        }
+        if cos_angle > 1. - 1e-10 {
+            // nearly parallel, fall back to a normalized linear interpolation
+            return Self::new(((1. - t) * self.quaternion + t * end).normalize());
+        }
+        let angle = cos_angle.min(1.).acos();
+        let quaternion = ((1. - t) * angle).sin() / angle.sin() * self.quaternion
+            + (t * angle).sin() / angle.sin() * end;
+        Self::new(quaternion.normalize())
+    }
+
+    pub fn squad_control(previous: &Self, current: &Self, next: &Self) -> Self {
+        // inner control point for squad at current, neighbours are moved to the same hemisphere
+        let align = |q: &Self| -> Self {
+            if q.quaternion.dot(&current.quaternion) < 0. {
+                Self::new(-q.quaternion)
+            } else {
+                Self::new(q.quaternion)
+            }
+        };
+        let inverse = current.inverse();
+        let to_next = align(next).compose(&inverse).log();
+        let to_previous = align(previous).compose(&inverse).log();
+        Self::exp(&(-(to_next + to_previous) / 4.)).compose(current)
+    }
+
+    pub fn squad(&self, other: &Self, control: &Self, other_control: &Self, t: f64) -> Self {
+        // spherical cubic interpolation between self and other using the control points from
+        // squad_control, continuous in angular velocity across consecutive intervals
+        let outer = self.slerp(other, t);
+        let inner = control.slerp(other_control, t);
+        outer.slerp(&inner, 2. * t * (1. - t))
+    }
+
+    pub fn error(&self, reference: &Self) -> Self {
+        // error quaternion q_BR = q_BN x q_RN^-1 on the short rotation (q4 >= 0)
+        let error = self.compose(&reference.inverse());
+        if error.quaternion[3] < 0. {
+            Self::new(-error.quaternion)
+        } else {
+            error
+        }
+    }
+
+    pub fn small_angle_error(&self, reference: &Self) -> Vector3<f64> {
+        // rotation angles (rad) about the body axes taking the reference to self, 2 dq_vec to
+        // first order
+        2. * self.error(reference).vector()
     }
 }
+impl Mrps {
+    pub fn new(sigma: Vector3<f64>) -> Self {
+        Self { sigma }
+    }
+
+    pub fn mrp2dcm(&self) -> Matrix3<f64> {
+        let sigma_sq = self.sigma.norm_squared();
+        let tilde = self.sigma.cross_matrix();
+        Matrix3::identity()
+            + (8. * tilde * tilde - 4. * (1. - sigma_sq) * tilde) / (1. + sigma_sq).powi(2)
+    }
+
+    pub fn dcm2mrp(dcm: &Matrix3<f64>) -> Self {
+        // through the quaternion, which has a non-negative scalar part so |sigma| <= 1
+        let quat = Quaternions::dcm2quat(dcm).quaternion;
+        Self::new(quat.fixed_rows::<3>(0) / (1. + quat[3]))
+    }
+
+    pub fn shadow(&self) -> Self {
+        // shadow set describes the same attitude, sigma_s = -sigma / |sigma|^2
+        let sigma_sq = self.sigma.norm_squared();
+        if sigma_sq == 0. {
+            return Self::new(self.sigma);
+        }
+        Self::new(-self.sigma / sigma_sq)
+    }
+
+    pub fn kinematics(sigma: &Vector3<f64>, omega: &Vector3<f64>) -> Vector3<f64> {
+        // mrp rates from body angular velocity omega (rad/s, body frame)
+        // sigma_dot = [(1 - sigma^2) I + 2 [sigma~] + 2 sigma sigma^T] omega / 4
+        let sigma_sq = sigma.norm_squared();
+        ((1. - sigma_sq) * omega + 2. * sigma.cross(omega) + 2. * sigma * sigma.dot(omega)) * 0.25
+    }
+
+    pub fn switch_shadow(&mut self) -> bool {
+        // switch to the shadow set when outside the unit sphere, keeps |sigma| <= 1 so the
+        // parameters never approach the 360 deg singularity, returns if a switch happened
+        if self.sigma.norm_squared() > 1. {
+            self.sigma = self.shadow().sigma;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Crps {
+    pub fn new(q: Vector3<f64>) -> Self {
+        Self { q }
+    }
+
+    pub fn crp2dcm(&self) -> Matrix3<f64> {
+        let q_sq = self.q.norm_squared();
+        ((1. - q_sq) * Matrix3::identity() + 2. * self.q * self.q.transpose()
+            - 2. * self.q.cross_matrix())
+            / (1. + q_sq)
+    }
+
+    pub fn dcm2crp(dcm: &Matrix3<f64>) -> Self {
+        // through the quaternion, components go to infinity for a 180 deg rotation
+        let quat = Quaternions::dcm2quat(dcm).quaternion;
+        Self::new(quat.fixed_rows::<3>(0) / quat[3])
+    }
+}
+
+impl PrincipalRotation {
+    pub fn new(axis: Vector3<f64>, angle: f64) -> Self {
+        Self { axis, angle }
+    }
+
+    pub fn prv2dcm(&self) -> Matrix3<f64> {
+        let e = self.axis.normalize();
+        let (s, c) = self.angle.sin_cos();
+        c * Matrix3::identity() + (1. - c) * e * e.transpose() - s * e.cross_matrix()
+    }
+
+    pub fn dcm2prv(dcm: &Matrix3<f64>) -> Self {
+        // angle in [0, pi], axis is arbitrary (x) for a zero rotation
+        let quat = Quaternions::dcm2quat(dcm).quaternion;
+        let angle = 2. * quat[3].clamp(-1., 1.).acos();
+        let vec_mag = quat.fixed_rows::<3>(0).norm();
+        let axis = if vec_mag > 1e-12 {
+            quat.fixed_rows::<3>(0) / vec_mag
+        } else {
+            Vector3::x()
+        };
+        Self::new(axis, angle)
+    }
+}
+
 #[allow(dead_code)]
-#[allow(unused_variables)]
 impl Attitude {
+    pub fn new<T: AttitudeParam>(value: T) -> Self {
+        // attitude with value as the main (and only) representation
+        let mut attitude = Attitude {
+            euler: None,
+            quat: None,
+            mrp: None,
+            crp: None,
+            prv: None,
+            dcm: Matrix3::identity(),
+            main_rep: T::rep(),
+        };
+        attitude.set(value);
+        attitude
+    }
     pub fn gen_dcm(&mut self) {
-        if let Some(ref euler) = &mut self.euler {
-            self.dcm = euler.euler2dcm();
-        } else if let Some(ref quat) = &mut self.quat {
-            self.dcm = quat.quat2dcm();
+        // dcm from the main representation
+        match self.main_rep {
+            AttitudeRep::Euler => {
+                if let Some(euler) = &self.euler {
+                    self.dcm = euler.euler2dcm();
+                }
+            }
+            AttitudeRep::Quat => {
+                if let Some(quat) = &self.quat {
+                    self.dcm = quat.quat2dcm();
+                }
+            }
+            AttitudeRep::Mrp => {
+                if let Some(mrp) = &self.mrp {
+                    self.dcm = mrp.mrp2dcm();
+                }
+            }
+            AttitudeRep::Crp => {
+                if let Some(crp) = &self.crp {
+                    self.dcm = crp.crp2dcm();
+                }
+            }
+            AttitudeRep::Prv => {
+                if let Some(prv) = &self.prv {
+                    self.dcm = prv.prv2dcm();
+                }
+            }
         }
     }
     pub fn update_others(&mut self) {
-        if self.main_rep == "euler" {
-            if let Some(ref quat) = &mut self.quat {
-                // put dcm to quat here, self.quat.quaternion = self.quat.dcm2quat(self.dcm);
+        // regenerate the dcm from the main representation and bring every other held
+        // representation in line with it
+        self.gen_dcm();
+        self.sync_from_dcm(Some(self.main_rep));
+    }
+    pub fn switch_rep(&mut self, new_rep: AttitudeRep) {
+        // make another representation the main one, creating it from the dcm if it is not held
+        // yet (euler angles default to a 3-2-1 sequence)
+        if new_rep == self.main_rep {
+            return;
+        }
+        self.gen_dcm();
+        match new_rep {
+            AttitudeRep::Euler => {
+                if self.euler.is_none() {
+                    self.euler = Some(EulerAngles::dcm2euler(&self.dcm, [3, 2, 1]));
+                }
+            }
+            AttitudeRep::Quat => {
+                if self.quat.is_none() {
+                    self.quat = Some(Quaternions::dcm2quat(&self.dcm));
+                }
+            }
+            AttitudeRep::Mrp => {
+                if self.mrp.is_none() {
+                    self.mrp = Some(Mrps::dcm2mrp(&self.dcm));
+                }
+            }
+            AttitudeRep::Crp => {
+                if self.crp.is_none() {
+                    self.crp = Some(Crps::dcm2crp(&self.dcm));
+                }
+            }
+            AttitudeRep::Prv => {
+                if self.prv.is_none() {
+                    self.prv = Some(PrincipalRotation::dcm2prv(&self.dcm));
+                }
             }
         }
-        if self.main_rep == "quat" {
-            if let Some(ref euler) = &mut self.euler {
-                // put dcm to quat here, self.euler.angle =
-                // self.euler.dcm2euler(self.dcm,self.euler.sequence);
+        self.main_rep = new_rep;
+        self.update_others();
+    }
+    pub fn set<T: AttitudeParam>(&mut self, value: T) {
+        // replace one representation (main or not) and update the dcm and all others from it
+        let rep = T::rep();
+        self.dcm = value.to_dcm();
+        value.store(self);
+        self.sync_from_dcm(Some(rep));
+    }
+    pub fn set_dcm(&mut self, dcm: Matrix3<f64>) {
+        self.dcm = dcm;
+        self.sync_from_dcm(None);
+    }
+    fn sync_from_dcm(&mut self, skip: Option<AttitudeRep>) {
+        // recompute every held representation except skip from the dcm
+        if skip != Some(AttitudeRep::Euler) {
+            if let Some(euler) = &self.euler {
+                self.euler = Some(EulerAngles::dcm2euler(&self.dcm, euler.sequence));
             }
         }
+        if skip != Some(AttitudeRep::Quat) && self.quat.is_some() {
+            self.quat = Some(Quaternions::dcm2quat(&self.dcm));
+        }
+        if skip != Some(AttitudeRep::Mrp) && self.mrp.is_some() {
+            self.mrp = Some(Mrps::dcm2mrp(&self.dcm));
+        }
+        if skip != Some(AttitudeRep::Crp) && self.crp.is_some() {
+            self.crp = Some(Crps::dcm2crp(&self.dcm));
+        }
+        if skip != Some(AttitudeRep::Prv) && self.prv.is_some() {
+            self.prv = Some(PrincipalRotation::dcm2prv(&self.dcm));
+        }
     }
-    // TODO: add method to switch to another attitude representation
-    pub fn switch_rep(&mut self, new_rep: String) {
-        if new_rep == self.main_rep {
-            //println!("already the main attitude representation");
-            return;
-        } else {
-            self.main_rep = new_rep;
-            if self.euler.is_none() {}
+}
+
+// common interface of the attitude representations used by Attitude::set
+pub trait AttitudeParam {
+    fn rep() -> AttitudeRep;
+    fn to_dcm(&self) -> Matrix3<f64>;
+    fn store(self, attitude: &mut Attitude);
+}
+
+impl AttitudeParam for EulerAngles {
+    fn rep() -> AttitudeRep {
+        AttitudeRep::Euler
+    }
+    fn to_dcm(&self) -> Matrix3<f64> {
+        self.euler2dcm()
+    }
+    fn store(self, attitude: &mut Attitude) {
+        attitude.euler = Some(self);
+    }
+}
+
+impl AttitudeParam for Quaternions {
+    fn rep() -> AttitudeRep {
+        AttitudeRep::Quat
+    }
+    fn to_dcm(&self) -> Matrix3<f64> {
+        self.quat2dcm()
+    }
+    fn store(self, attitude: &mut Attitude) {
+        attitude.quat = Some(self);
+    }
+}
+
+impl AttitudeParam for Mrps {
+    fn rep() -> AttitudeRep {
+        AttitudeRep::Mrp
+    }
+    fn to_dcm(&self) -> Matrix3<f64> {
+        self.mrp2dcm()
+    }
+    fn store(self, attitude: &mut Attitude) {
+        attitude.mrp = Some(self);
+    }
+}
+
+impl AttitudeParam for Crps {
+    fn rep() -> AttitudeRep {
+        AttitudeRep::Crp
+    }
+    fn to_dcm(&self) -> Matrix3<f64> {
+        self.crp2dcm()
+    }
+    fn store(self, attitude: &mut Attitude) {
+        attitude.crp = Some(self);
+    }
+}
+
+impl AttitudeParam for PrincipalRotation {
+    fn rep() -> AttitudeRep {
+        AttitudeRep::Prv
+    }
+    fn to_dcm(&self) -> Matrix3<f64> {
+        self.prv2dcm()
+    }
+    fn store(self, attitude: &mut Attitude) {
+        attitude.prv = Some(self);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VectorObservation {
+    // unit vector measured in the body frame and the same direction known in the inertial frame
+    pub body: Vector3<f64>,
+    pub inertial: Vector3<f64>,
+    pub weight: f64,
+}
+
+impl VectorObservation {
+    pub fn new(body: Vector3<f64>, inertial: Vector3<f64>, weight: f64) -> Self {
+        Self {
+            body: body.normalize(),
+            inertial: inertial.normalize(),
+            weight,
+        }
+    }
+}
+
+pub fn triad(primary: &VectorObservation, secondary: &VectorObservation) -> Attitude {
+    // the primary observation is matched exactly, the secondary only fixes the rotation about it
+    let triad_frame = |v1: &Vector3<f64>, v2: &Vector3<f64>| -> Matrix3<f64> {
+        let t1 = v1.normalize();
+        let t2 = v1.cross(v2).normalize();
+        let t3 = t1.cross(&t2);
+        Matrix3::from_columns(&[t1, t2, t3])
+    };
+    let body = triad_frame(&primary.body, &secondary.body);
+    let inertial = triad_frame(&primary.inertial, &secondary.inertial);
+    Attitude::new(Quaternions::dcm2quat(&(body * inertial.transpose())))
+}
+
+fn attitude_profile(observations: &[VectorObservation]) -> (Matrix3<f64>, Vector3<f64>, f64) {
+    // wahba's problem terms: S = B + B^T, z = sum w (b x n), sigma = tr(B), B = sum w b n^T
+    let mut b = Matrix3::zeros();
+    let mut z = Vector3::zeros();
+    for obs in observations.iter() {
+        b += obs.weight * obs.body * obs.inertial.transpose();
+        z += obs.weight * obs.body.cross(&obs.inertial);
+    }
+    (b + b.transpose(), z, b.trace())
+}
+
+fn max_eigenvalue(observations: &[VectorObservation]) -> f64 {
+    // largest eigenvalue of davenport's matrix from newton-raphson on its characteristic
+    // equation, starting at the sum of the weights which is exact for noise free measurements
+    let (s, z, sigma) = attitude_profile(observations);
+    // trace of the adjugate of S, the sum of its principal 2x2 minors
+    let kappa = s[(0, 0)] * s[(1, 1)] - s[(0, 1)] * s[(1, 0)] + s[(0, 0)] * s[(2, 2)]
+        - s[(0, 2)] * s[(2, 0)]
+        + s[(1, 1)] * s[(2, 2)]
+        - s[(1, 2)] * s[(2, 1)];
+    let delta = s.determinant();
+    let a = sigma.powi(2) - kappa;
+    let b = sigma.powi(2) + z.dot(&z);
+    let c = delta + z.dot(&(s * z));
+    let d = z.dot(&(s * s * z));
+
+    let mut lambda: f64 = observations.iter().map(|obs| obs.weight).sum();
+    for _ in 0..50 {
+        let f = lambda.powi(4) - (a + b) * lambda.powi(2) - c * lambda + (a * b + c * sigma - d);
+        let df = 4. * lambda.powi(3) - 2. * (a + b) * lambda - c;
+        if df == 0. {
+            break;
+        }
+        let step = f / df;
+        lambda -= step;
+        if step.abs() < 1e-14 * lambda.abs().max(1.) {
+            break;
+        }
+    }
+    lambda
+}
+
+pub fn davenport_q(observations: &[VectorObservation]) -> Attitude {
+    // eigenvector of the largest eigenvalue of K = [S - sigma I, z; z^T, sigma] (scalar last)
+    let (s, z, sigma) = attitude_profile(observations);
+    let mut k = Matrix4::zeros();
+    k.fixed_view_mut::<3, 3>(0, 0)
+        .copy_from(&(s - sigma * Matrix3::identity()));
+    k.fixed_view_mut::<3, 1>(0, 3).copy_from(&z);
+    k.fixed_view_mut::<1, 3>(3, 0).copy_from(&z.transpose());
+    k[(3, 3)] = sigma;
+
+    let eigen = k.symmetric_eigen();
+    let largest = eigen.eigenvalues.imax();
+    let mut quaternion: Vector4<f64> = eigen.eigenvectors.column(largest).into();
+    if quaternion[3] < 0. {
+        quaternion = -quaternion;
+    }
+    Attitude::new(Quaternions::new(quaternion.normalize()))
+}
+
+pub fn quest(observations: &[VectorObservation]) -> Attitude {
+    // shuster's quest: crp from [(lambda + sigma) I - S] p = z, which is singular near 180 deg
+    // rotations, so the inertial vectors are also rotated 180 deg about each axis (method of
+    // sequential rotations) and the best conditioned solution is rotated back
+    let lambda = max_eigenvalue(observations);
+    let flips = [
+        Matrix3::identity(),
+        Matrix3::from_diagonal(&vector![1., -1., -1.]),
+        Matrix3::from_diagonal(&vector![-1., 1., -1.]),
+        Matrix3::from_diagonal(&vector![-1., -1., 1.]),
+    ];
+
+    let mut best = (0., Matrix3::identity());
+    for flip in flips.iter() {
+        let rotated: Vec<VectorObservation> = observations
+            .iter()
+            .map(|obs| VectorObservation {
+                inertial: flip * obs.inertial,
+                ..*obs
+            })
+            .collect();
+        let (s, z, sigma) = attitude_profile(&rotated);
+        let m = (lambda + sigma) * Matrix3::identity() - s;
+        let gamma = m.determinant().abs();
+        if gamma > best.0 {
+            let crp = m.lu().solve(&z).unwrap_or_else(Vector3::zeros);
+            let quaternion = vector![crp[0], crp[1], crp[2], 1.] / (1. + crp.norm_squared()).sqrt();
+            // [BN] = [BN'] [N'N]
+            best = (gamma, Quaternions::new(quaternion).quat2dcm() * flip);
+        }
+        // a well conditioned first try is kept as is
+        if gamma > 0.1 * lambda.powi(3) {
+            break;
+        }
+    }
+    Attitude::new(Quaternions::dcm2quat(&best.1))
+}
+
+pub fn esoq2(observations: &[VectorObservation]) -> Attitude {
+    // mortari's esoq2: the rotation axis e is the null vector of
+    // M = (lambda - sigma) [(lambda + sigma) I - S] - z z^T, found as the largest cross product
+    // of two of its rows, then q = [(lambda - sigma) e, z . e] normalized
+    let (s, z, sigma) = attitude_profile(observations);
+    let lambda = max_eigenvalue(observations);
+    let m = (lambda - sigma) * ((lambda + sigma) * Matrix3::identity() - s) - z * z.transpose();
+
+    let rows: [Vector3<f64>; 3] = [
+        m.row(0).transpose(),
+        m.row(1).transpose(),
+        m.row(2).transpose(),
+    ];
+    let candidates = [
+        rows[1].cross(&rows[2]),
+        rows[2].cross(&rows[0]),
+        rows[0].cross(&rows[1]),
+    ];
+    let mut axis = candidates[0];
+    for candidate in candidates.iter().skip(1) {
+        if candidate.norm_squared() > axis.norm_squared() {
+            axis = *candidate;
         }
     }
-    // TODO: add a method to update all attitude represenations if one is updated
-    // TODO: add a method to update a single representation
+    if axis.norm_squared() == 0. {
+        // M vanishes when the attitude is the identity
+        return Attitude::new(Quaternions::new(vector![0., 0., 0., 1.]));
+    }
+    let axis = axis.normalize();
+
+    let scaled = (lambda - sigma) * axis;
+    let mut quaternion = vector![scaled[0], scaled[1], scaled[2], z.dot(&axis)].normalize();
+    if quaternion[3] < 0. {
+        quaternion = -quaternion;
+    }
+    Attitude::new(Quaternions::new(quaternion))
+}
+
+pub fn rigid_body_dynamics(
+    inertia: &Matrix3<f64>,
+    omega: &Vector3<f64>,
+    torque: &Vector3<f64>,
+) -> Vector3<f64> {
+    // euler's rotational equations of motion for a rigid body, all in the body frame
+    // [I] omega_dot = -omega x [I] omega + L
+    let rhs = torque - omega.cross(&(inertia * omega));
+    inertia.lu().solve(&rhs).unwrap_or_else(Vector3::zeros)
+}
+
+pub fn r1(t: f64) -> Matrix3<f64> {
+    matrix![
+        1.0, 0.0, 0.0;
+        0.0, t.cos(), t.sin();
+        0.0, -t.sin(), t.cos();
+    ]
+}
+
+pub fn r2(t: f64) -> Matrix3<f64> {
+    matrix![
+        t.cos(), 0.0, -t.sin();
+        0.0, 1.0, 0.0;
+        t.sin(), 0.0, t.cos();
+    ]
+}
+
+pub fn r3(t: f64) -> Matrix3<f64> {
+    matrix![
+        t.cos(), t.sin(), 0.0;
+        -t.sin(), t.cos(), 0.0;
+        0.0, 0.0, 1.0;
+    ]
+}
+
+fn axis_rotation(axis: usize, t: f64) -> Matrix3<f64> {
+    // single axis rotation about axis 1, 2 or 3
+    let r_functions = [r1, r2, r3];
+    r_functions[axis - 1](t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn assert_same_rotation(q: &Vector4<f64>, expected: &Vector4<f64>) {
+        // q and -q describe the same rotation, the sign is only fixed when q4 != 0
+        let error = (q - expected).norm().min((q + expected).norm());
+        assert!(error < 1e-12, "{:?} != {:?}", q, expected);
+    }
+
+    #[test]
+    fn dcm2quat_inverts_quat2dcm_in_every_branch() {
+        // the largest component picks the shepperd branch: q1, q2, q3 and q4 in turn
+        let quaternions = [
+            vector![0.8, 0.3, -0.1, 0.5],
+            vector![-0.2, 0.9, 0.3, 0.25],
+            vector![0.1, -0.4, 0.85, 0.3],
+            vector![0.2, 0.1, -0.3, 0.9],
+        ];
+        for quaternion in quaternions.iter() {
+            let quaternion = quaternion.normalize();
+            let dcm = Quaternions::new(quaternion).quat2dcm();
+            assert!((dcm * dcm.transpose() - Matrix3::identity()).norm() < 1e-12);
+            let recovered = Quaternions::dcm2quat(&dcm).quaternion;
+            assert!(recovered[3] >= 0.);
+            assert_same_rotation(&recovered, &quaternion);
+        }
+    }
+
+    #[test]
+    fn dcm2quat_handles_180_deg_rotations() {
+        let oblique = vector![1., -2., 3.].normalize();
+        let cases = [
+            (r1(PI), vector![1., 0., 0., 0.]),
+            (r2(PI), vector![0., 1., 0., 0.]),
+            (r3(PI), vector![0., 0., 1., 0.]),
+            (
+                PrincipalRotation::new(oblique, PI).prv2dcm(),
+                vector![oblique[0], oblique[1], oblique[2], 0.],
+            ),
+        ];
+        for (dcm, expected) in cases.iter() {
+            let quaternion = Quaternions::dcm2quat(dcm);
+            assert_same_rotation(&quaternion.quaternion, expected);
+            assert!((quaternion.quat2dcm() - dcm).norm() < 1e-12);
+        }
+    }
+
+    const SEQUENCES: [[usize; 3]; 12] = [
+        [1, 2, 3],
+        [1, 3, 2],
+        [2, 1, 3],
+        [2, 3, 1],
+        [3, 1, 2],
+        [3, 2, 1],
+        [1, 2, 1],
+        [1, 3, 1],
+        [2, 1, 2],
+        [2, 3, 2],
+        [3, 1, 3],
+        [3, 2, 3],
+    ];
+
+    #[test]
+    fn euler2dcm_applies_the_body_sequence() {
+        let angles = vector![0.3, -0.7, 1.1];
+        let dcm = EulerAngles::new(angles, [3, 2, 1]).euler2dcm();
+        assert!((dcm - r1(1.1) * r2(-0.7) * r3(0.3)).norm() < 1e-14);
+        let dcm = EulerAngles::new(angles, [3, 1, 3]).euler2dcm();
+        assert!((dcm - r3(1.1) * r1(-0.7) * r3(0.3)).norm() < 1e-14);
+    }
+
+    #[test]
+    fn dcm2euler_inverts_euler2dcm_for_all_sequences() {
+        for sequence in SEQUENCES.iter() {
+            // second angle inside [-pi/2, pi/2] (asymmetric) or [0, pi] (symmetric)
+            let angles = if sequence[0] == sequence[2] {
+                vector![-2.5, 2.2, 0.4]
+            } else {
+                vector![-2.5, -1.2, 0.4]
+            };
+            let dcm = EulerAngles::new(angles, *sequence).euler2dcm();
+            let recovered = EulerAngles::dcm2euler(&dcm, *sequence);
+            assert!(
+                (recovered.angle - angles).norm() < 1e-12,
+                "{:?}: {:?}",
+                sequence,
+                recovered.angle
+            );
+        }
+    }
+
+    #[test]
+    fn dcm2euler_keeps_the_rotation_in_gimbal_lock() {
+        for sequence in SEQUENCES.iter() {
+            let locked_angles: &[f64] = if sequence[0] == sequence[2] {
+                &[0., PI]
+            } else {
+                &[PI / 2., -PI / 2.]
+            };
+            for second in locked_angles.iter() {
+                let dcm = EulerAngles::new(vector![0.6, *second, -0.25], *sequence).euler2dcm();
+                let recovered = EulerAngles::dcm2euler(&dcm, *sequence);
+                assert!((recovered.angle[1] - second).abs() < 1e-7);
+                assert_eq!(recovered.angle[2], 0.);
+                assert!(
+                    (recovered.euler2dcm() - dcm).norm() < 1e-12,
+                    "{:?}",
+                    sequence
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn mrp_crp_and_prv_match_a_known_rotation() {
+        // 90 deg about the third axis
+        let dcm = r3(PI / 2.);
+        let mrp = Mrps::dcm2mrp(&dcm);
+        assert!((mrp.sigma - vector![0., 0., (PI / 8.).tan()]).norm() < 1e-14);
+        let crp = Crps::dcm2crp(&dcm);
+        assert!((crp.q - vector![0., 0., 1.]).norm() < 1e-14);
+        let prv = PrincipalRotation::dcm2prv(&dcm);
+        assert!((prv.axis - Vector3::z()).norm() < 1e-14);
+        assert!((prv.angle - PI / 2.).abs() < 1e-14);
+    }
+
+    #[test]
+    fn mrp_crp_and_prv_round_trip_through_the_dcm() {
+        let axis = vector![0.3, -0.5, 0.8].normalize();
+        for angle in [1e-3, 0.4, 2., 3.1] {
+            let dcm = PrincipalRotation::new(axis, angle).prv2dcm();
+            let prv = PrincipalRotation::dcm2prv(&dcm);
+            assert!((prv.axis - axis).norm() < 1e-10);
+            assert!((prv.angle - angle).abs() < 1e-12);
+
+            let mrp = Mrps::dcm2mrp(&dcm);
+            assert!((mrp.sigma - axis * (angle / 4.).tan()).norm() < 1e-12);
+            assert!((mrp.mrp2dcm() - dcm).norm() < 1e-12);
+            assert!((mrp.shadow().mrp2dcm() - dcm).norm() < 1e-12);
+
+            let crp = Crps::dcm2crp(&dcm);
+            assert!((crp.q - axis * (angle / 2.).tan()).norm() < 1e-9);
+            assert!((crp.crp2dcm() - dcm).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn mrp_switches_to_the_shadow_set_outside_the_unit_sphere() {
+        let axis = vector![1., 1., 0.].normalize();
+        let mut mrp = Mrps::new(axis * (1.5 * PI / 4.).tan());
+        let dcm = mrp.mrp2dcm();
+        assert!(mrp.switch_shadow());
+        assert!(mrp.sigma.norm() <= 1.);
+        assert!((mrp.mrp2dcm() - dcm).norm() < 1e-12);
+        assert!(!mrp.switch_shadow());
+    }
+
+    fn assert_in_sync(attitude: &Attitude) {
+        // every held representation has to describe the attitude dcm
+        let mut dcms = vec![];
+        if let Some(quat) = &attitude.quat {
+            dcms.push(quat.quat2dcm());
+        }
+        if let Some(mrp) = &attitude.mrp {
+            dcms.push(mrp.mrp2dcm());
+        }
+        if let Some(crp) = &attitude.crp {
+            dcms.push(crp.crp2dcm());
+        }
+        if let Some(prv) = &attitude.prv {
+            dcms.push(prv.prv2dcm());
+        }
+        for dcm in dcms.iter() {
+            assert!(
+                (dcm - attitude.dcm).norm() < 1e-12,
+                "{} != {}",
+                dcm,
+                attitude.dcm
+            );
+        }
+    }
+
+    #[test]
+    fn attitude_keeps_every_representation_in_sync() {
+        let quaternion = vector![0.3, -0.2, 0.5, 0.78].normalize();
+        let mut attitude = Attitude::new(Quaternions::new(quaternion));
+        let dcm = Quaternions::new(quaternion).quat2dcm();
+
+        // switching creates the missing representations without moving the attitude
+        attitude.switch_rep(AttitudeRep::Mrp);
+        attitude.switch_rep(AttitudeRep::Crp);
+        attitude.switch_rep(AttitudeRep::Quat);
+        assert_eq!(attitude.main_rep, AttitudeRep::Quat);
+        assert!(attitude.mrp.is_some() && attitude.crp.is_some());
+        assert!((attitude.dcm - dcm).norm() < 1e-12);
+        assert_in_sync(&attitude);
+
+        // a shadow mrp set is kept as given and the others follow the same rotation
+        let axis = vector![1., -2., 0.5].normalize();
+        let shadow = axis * (1.6 * PI / 4.).tan();
+        attitude.set(Mrps::new(shadow));
+        assert_eq!(attitude.mrp.as_ref().unwrap().sigma, shadow);
+        assert!((attitude.dcm - Mrps::new(shadow).mrp2dcm()).norm() < 1e-12);
+        assert_in_sync(&attitude);
+        assert_same_rotation(
+            &attitude.quat.as_ref().unwrap().quaternion,
+            &Quaternions::dcm2quat(&attitude.dcm).quaternion,
+        );
+
+        // setting the dcm directly also resets the mrp to the short rotation set
+        attitude.set_dcm(dcm);
+        assert!(attitude.mrp.as_ref().unwrap().sigma.norm() <= 1.);
+        assert_in_sync(&attitude);
+
+        // editing the main representation in place takes effect with update_others
+        attitude.switch_rep(AttitudeRep::Crp);
+        let crp = vector![0.1, 0.4, -0.3];
+        attitude.crp = Some(Crps { q: crp });
+        attitude.update_others();
+        assert!((attitude.dcm - Crps { q: crp }.crp2dcm()).norm() < 1e-12);
+        assert_in_sync(&attitude);
+    }
+
+    fn observations(dcm: &Matrix3<f64>, noise: f64) -> Vec<VectorObservation> {
+        // body vectors from the true attitude, optionally tilted by a fixed small error
+        let inertial = [
+            (vector![1., 0.2, -0.3], 1.),
+            (vector![-0.4, 1., 0.5], 0.5),
+            (vector![0.3, -0.6, 1.], 0.25),
+        ];
+        inertial
+            .iter()
+            .enumerate()
+            .map(|(i, (vec, weight))| {
+                let tilt = r3(noise * (i as f64 + 1.)) * r1(-noise);
+                VectorObservation::new(tilt * dcm * vec, *vec, *weight)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn attitude_determination_recovers_the_true_attitude() {
+        let rotations = [
+            EulerAngles::new(vector![0.4, -0.3, 1.2], [3, 2, 1]).euler2dcm(),
+            Matrix3::identity(),
+            // 180 deg rotations make quest's crp singular without the sequential rotations
+            r1(PI),
+            PrincipalRotation::new(vector![1., 1., 1.], PI).prv2dcm(),
+        ];
+        for dcm in rotations.iter() {
+            let observations = observations(dcm, 0.);
+            let estimates = [
+                triad(&observations[0], &observations[1]),
+                davenport_q(&observations),
+                quest(&observations),
+                esoq2(&observations),
+            ];
+            for estimate in estimates.iter() {
+                assert!((estimate.dcm - dcm).norm() < 1e-11, "{}", estimate.dcm);
+            }
+        }
+    }
+
+    #[test]
+    fn wahba_solutions_agree_with_noisy_observations() {
+        let dcm = EulerAngles::new(vector![-2., 0.7, 0.1], [3, 1, 3]).euler2dcm();
+        let observations = observations(&dcm, 1e-3);
+        let optimal = davenport_q(&observations).dcm;
+        assert!((optimal - dcm).norm() > 1e-4);
+        assert!((optimal - dcm).norm() < 1e-2);
+        assert!((quest(&observations).dcm - optimal).norm() < 1e-9);
+        assert!((esoq2(&observations).dcm - optimal).norm() < 1e-9);
+
+        // triad matches the primary observation exactly
+        let triad = triad(&observations[0], &observations[1]);
+        assert!((triad.dcm * observations[0].inertial - observations[0].body).norm() < 1e-12);
+    }
+
+    #[test]
+    fn quaternion_composition_matches_the_dcm_product() {
+        let q_bn = Quaternions::dcm2quat(
+            &EulerAngles::new(vector![0.3, 0.2, -0.5], [3, 2, 1]).euler2dcm(),
+        );
+        let q_fb = Quaternions::dcm2quat(&r1(1.1));
+        let q_fn = q_fb.compose(&q_bn);
+        assert!((q_fn.quat2dcm() - q_fb.quat2dcm() * q_bn.quat2dcm()).norm() < 1e-14);
+
+        let identity = q_bn.compose(&q_bn.inverse());
+        assert_same_rotation(&identity.quaternion, &vector![0., 0., 0., 1.]);
+        let v = vector![1., -2., 0.5];
+        assert!((q_bn.rotate(&v) - q_bn.quat2dcm() * v).norm() < 1e-14);
+        assert!((q_bn.conjugate().rotate(&q_bn.rotate(&v)) - v).norm() < 1e-14);
+    }
+
+    #[test]
+    fn quaternion_log_and_exp_are_inverses() {
+        let q = Quaternions::new(vector![0.3, -0.1, 0.2, 0.9].normalize());
+        assert_same_rotation(&Quaternions::exp(&q.log()).quaternion, &q.quaternion);
+        // half of a rotation of pi/2 about the third axis
+        let log = Quaternions::dcm2quat(&r3(PI / 2.)).log();
+        assert!((log - vector![0., 0., PI / 4.]).norm() < 1e-14);
+    }
+
+    #[test]
+    fn slerp_rotates_at_a_constant_rate() {
+        let start = Quaternions::dcm2quat(&Matrix3::identity());
+        let end = Quaternions::dcm2quat(&r2(1.2));
+        for t in [0., 0.25, 0.5, 1.] {
+            let q = start.slerp(&end, t).quat2dcm();
+            assert!((q - r2(1.2 * t)).norm() < 1e-12);
+        }
+        // the shorter arc is used when the end is given with the opposite sign
+        let flipped = Quaternions::new(-end.quaternion);
+        assert!((start.slerp(&flipped, 0.5).quat2dcm() - r2(0.6)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn squad_passes_through_its_end_points() {
+        let keys = [
+            Quaternions::dcm2quat(&Matrix3::identity()),
+            Quaternions::dcm2quat(&r3(0.5)),
+            Quaternions::dcm2quat(&(r1(0.3) * r3(1.))),
+            Quaternions::dcm2quat(&(r1(0.8) * r3(1.2))),
+        ];
+        let control_1 = Quaternions::squad_control(&keys[0], &keys[1], &keys[2]);
+        let control_2 = Quaternions::squad_control(&keys[1], &keys[2], &keys[3]);
+        let start = keys[1].squad(&keys[2], &control_1, &control_2, 0.);
+        let end = keys[1].squad(&keys[2], &control_1, &control_2, 1.);
+        assert_same_rotation(&start.quaternion, &keys[1].quaternion);
+        assert_same_rotation(&end.quaternion, &keys[2].quaternion);
+    }
+
+    #[test]
+    fn error_quaternion_gives_the_small_rotation_to_the_reference() {
+        let reference = Quaternions::dcm2quat(&r3(0.7));
+        let attitude = Quaternions::dcm2quat(&(r2(1e-3) * r3(0.7)));
+        let error = attitude.error(&reference);
+        assert!(error.quaternion[3] > 0.);
+        assert!((error.quat2dcm() - r2(1e-3)).norm() < 1e-14);
+        let angles = attitude.small_angle_error(&reference);
+        assert!((angles - vector![0., 1e-3, 0.]).norm() < 1e-9);
+    }
 }
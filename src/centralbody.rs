@@ -1,9 +1,23 @@
+use crate::ephemeris::time2jd;
+use crate::epoch::*;
+use crate::frames::*;
 #[allow(unused_imports)]
 use crate::math::*;
 use nalgebra::*;
 use std::f64::consts::PI;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
+// unnormalized earth J_n (egm2008) by degree up to J6
+pub const EARTH_ZONALS: [f64; 7] = [
+    0.,
+    0.,
+    1.08262668355e-3,
+    -2.53265648533e-6,
+    -1.61962159137e-6,
+    -2.27296082869e-7,
+    5.40681239107e-7,
+];
+
 #[derive(Clone)]
 #[allow(non_snake_case)]
 pub struct CentralBody {
@@ -11,21 +25,105 @@ pub struct CentralBody {
     pub mass: f64,
     pub mu: f64,
     pub equatorial_radius: f64,
+    pub flattening: f64, // reference ellipsoid, 0 for a sphere
     pub max_deg: usize,
     pub max_order: usize,
     pub c: Vec<Vec<f64>>,
     pub s: Vec<Vec<f64>>,
-    pub eci2ecef: Matrix3<f64>, // simple transformation from eci2ecef
+    pub zonals: Vec<f64>, // unnormalized J_n by degree, used when no coefficients are loaded
     pub omega: f64,
+    pub prime_meridian: f64, // rad, rotation angle of the body fixed frame at J2000 (ut1)
+    pub radiation: RadiationGrid,
+    pub time_data: TimeData, // leap seconds and ut1 - utc for the body's rotation
+    pub orientation: EarthOrientation, // frame model and earth orientation parameters
+}
+
+#[derive(Clone)]
+pub struct RadiationGrid {
+    // surface reflectivity (albedo) and emissivity on a regular latitude/longitude grid of cell
+    // centers (deg), the mean values are used where the grid is empty
+    pub lat_min: f64,
+    pub lat_step: f64,
+    pub lon_min: f64,
+    pub lon_step: f64,
+    pub reflectivity: Vec<Vec<f64>>, // [lat][lon]
+    pub emissivity: Vec<Vec<f64>>,   // [lat][lon]
+    pub mean_reflectivity: f64,
+    pub mean_emissivity: f64,
+}
+
+#[allow(dead_code)]
+impl RadiationGrid {
+    pub fn new() -> Self {
+        RadiationGrid {
+            lat_min: 0.,
+            lat_step: 0.,
+            lon_min: 0.,
+            lon_step: 0.,
+            reflectivity: vec![],
+            emissivity: vec![],
+            mean_reflectivity: 0.3, // earth average
+            mean_emissivity: 0.7,
+        }
+    }
+
+    pub fn lookup(&self, lat: f64, lon: f64) -> (f64, f64) {
+        // (reflectivity, emissivity) of the cell containing geocentric lat/lon (rad)
+        if self.reflectivity.is_empty() || self.lat_step <= 0. || self.lon_step <= 0. {
+            return (self.mean_reflectivity, self.mean_emissivity);
+        }
+        let nlat = self.reflectivity.len();
+        let nlon = self.reflectivity[0].len();
+        let lat_deg = lat * 180. / PI;
+        let lon_deg = (lon * 180. / PI - self.lon_min).rem_euclid(360.) + self.lon_min;
+        let i = ((lat_deg - self.lat_min) / self.lat_step)
+            .round()
+            .clamp(0., (nlat - 1) as f64) as usize;
+        let j = (((lon_deg - self.lon_min) / self.lon_step).round() as usize) % nlon;
+        (self.reflectivity[i][j], self.emissivity[i][j])
+    }
 }
 
 #[allow(dead_code)]
 impl CentralBody {
+    pub fn rotation_angle(&self, time: &f64) -> f64 {
+        // angle from the inertial x axis to the body fixed x axis at simulation time, the
+        // rotation runs on ut1 rather than the uniform simulation time
+        let elapsed =
+            Epoch::from_j2000_seconds(*time).elapsed_j2000(TimeScale::Ut1, &self.time_data);
+        self.prime_meridian + self.omega * elapsed
+    }
+
     pub fn gen_eci2ecef(&self, time: &f64) -> Matrix3<f64> {
-        matrix![(self.omega * time).cos(),  (self.omega * time).sin(), 0.;
-                -(self.omega * time).sin(), (self.omega * time).cos(), 0.;
+        // [ECEF <- ECI] from the orientation model, a uniform rotation unless a
+        // precession-nutation model is selected
+        let epoch = Epoch::from_j2000_seconds(*time);
+        let model = self.orientation.gcrf2itrf(
+            time2jd(time),
+            epoch.jd(TimeScale::Ut1, &self.time_data),
+            epoch.mjd(TimeScale::Utc, &self.time_data),
+        );
+        if let Some(gcrf2itrf) = model {
+            return gcrf2itrf;
+        }
+        let angle = self.rotation_angle(time);
+        matrix![angle.cos(),  angle.sin(), 0.;
+                -angle.sin(), angle.cos(), 0.;
                 0., 0., 1.]
     }
+
+    pub fn read_eop(&mut self, file_path: &str, iau2000a: bool) -> io::Result<()> {
+        // iers finals file for the orientation model, ut1 - utc also feeds the time data so
+        // every ut1 conversion uses it
+        self.orientation.read_finals(file_path, iau2000a)?;
+        self.time_data.dut1 = self
+            .orientation
+            .eop
+            .iter()
+            .map(|record| (record.mjd, record.ut1_utc))
+            .collect();
+        Ok(())
+    }
     #[allow(non_snake_case)]
     #[allow(dead_code)]
     pub fn norm_legendre(
@@ -40,13 +138,12 @@ impl CentralBody {
 
         let mut cphi = (PI / 2. - phi).cos();
         let mut sphi = (PI / 2. - phi).sin();
-        
+
         let eps = 2.220446049250313e-16;
-        
+
         cphi = if cphi.abs() <= eps { 0.0 } else { cphi };
-        sphi = if sphi.abs() <= eps { 0.0 } else { sphi }; 
-    
-    
+        sphi = if sphi.abs() <= eps { 0.0 } else { sphi };
+
         // seeds for normalized recursive formula
         P[0][0] = 1.; // n = 0, m = 0;
         P[1][0] = 3.0_f64.sqrt() * cphi;
@@ -88,6 +185,15 @@ impl CentralBody {
         (P, scale_factor)
     }
 
+    pub fn zonal(&self, deg: usize) -> f64 {
+        // unnormalized zonal coefficient J_n = -C_n0, from the fully normalized coefficients
+        // when they have been read in, otherwise from zonals (zero when neither has it)
+        match self.c.get(deg).and_then(|row| row.first()) {
+            Some(c) if *c != 0. => -c * (2. * deg as f64 + 1.).sqrt(),
+            _ => self.zonals.get(deg).copied().unwrap_or(0.),
+        }
+    }
+
     pub fn read_sph_coefs(
         &mut self,
         file_path: &str,
@@ -117,41 +223,167 @@ impl CentralBody {
         let mut current_n = 0;
         self.c = vec![vec![0.; max_m + 1]; max_n + 1];
         self.s = vec![vec![0.; max_m + 1]; max_n + 1];
-        for line in reader.lines() {
-            if let Ok(valid_line) = line {
-                let (n, m, c, s) = parse_egm_line(&valid_line).unwrap_or((0, 0, 0.0, 0.0));
+        for valid_line in reader.lines().map_while(Result::ok) {
+            let (n, m, c, s) = parse_egm_line(&valid_line).unwrap_or((0, 0, 0.0, 0.0));
 
-                if n != current_n && n <= max_n {
-                    current_n = n;
-                }
+            if n != current_n && n <= max_n {
+                current_n = n;
+            }
 
-                if n <= max_n && m <= max_m {
-                    self.c[n][m] = c;
-                    self.s[n][m] = s;
-                }
+            if n <= max_n && m <= max_m {
+                self.c[n][m] = c;
+                self.s[n][m] = s;
+            }
 
-                // Stop reading if we have met the max n and m
-                if n == max_n && m == max_m {
-                    break;
-                }
+            // Stop reading if we have met the max n and m
+            if n == max_n && m == max_m {
+                break;
             }
         }
 
         Ok(())
     }
 
+    pub fn read_radiation_grid(&mut self, file_path: &str) -> io::Result<()> {
+        // each line holds "lat lon reflectivity emissivity" (deg) for one cell center of a
+        // regular grid, lines may come in any order
+        let file = File::open(file_path)?;
+        let reader = BufReader::new(file);
+
+        let mut cells: Vec<[f64; 4]> = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            let parts: Vec<f64> = line
+                .split_whitespace()
+                .filter_map(|part| part.parse::<f64>().ok())
+                .collect();
+            if parts.len() >= 4 {
+                cells.push([parts[0], parts[1], parts[2], parts[3]]);
+            }
+        }
+        if cells.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no grid cells found in radiation file",
+            ));
+        }
+
+        let distinct = |k: usize| -> Vec<f64> {
+            let mut values: Vec<f64> = cells.iter().map(|cell| cell[k]).collect();
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            values.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+            values
+        };
+        let lats = distinct(0);
+        let lons = distinct(1);
+        let step = |values: &Vec<f64>| -> f64 {
+            if values.len() > 1 {
+                (values[values.len() - 1] - values[0]) / (values.len() - 1) as f64
+            } else {
+                360.
+            }
+        };
+
+        let grid = &mut self.radiation;
+        grid.lat_min = lats[0];
+        grid.lat_step = step(&lats);
+        grid.lon_min = lons[0];
+        grid.lon_step = step(&lons);
+        grid.reflectivity = vec![vec![grid.mean_reflectivity; lons.len()]; lats.len()];
+        grid.emissivity = vec![vec![grid.mean_emissivity; lons.len()]; lats.len()];
+        for cell in cells.iter() {
+            let i = ((cell[0] - grid.lat_min) / grid.lat_step).round() as usize;
+            let j = ((cell[1] - grid.lon_min) / grid.lon_step).round() as usize;
+            grid.reflectivity[i.min(lats.len() - 1)][j.min(lons.len() - 1)] = cell[2];
+            grid.emissivity[i.min(lats.len() - 1)][j.min(lons.len() - 1)] = cell[3];
+        }
+
+        // keep the averages consistent with the loaded grid for any cell lookups that fall back
+        let ncells = (lats.len() * lons.len()) as f64;
+        grid.mean_reflectivity = grid.reflectivity.iter().flatten().sum::<f64>() / ncells;
+        grid.mean_emissivity = grid.emissivity.iter().flatten().sum::<f64>() / ncells;
+        Ok(())
+    }
+
     pub fn new() -> Self {
         CentralBody {
             name: String::from(""),
             mass: 0.,              // kg
             mu: 0.,                // kg.m^3/s^2
             equatorial_radius: 0., // m
-            omega: 0.,             // rad/s
+            flattening: 0.,
+            omega: 0.,          // rad/s
+            prime_meridian: 0., // rad
             max_order: 0, // [0,0] for spherical, [2,0] for J2, [2+,1+] for spherical harmonics
             max_deg: 0,   // order >= degree
             c: vec![vec![]],
             s: vec![vec![]],
-            eci2ecef: Matrix3::zeros(),
+            zonals: vec![],
+            radiation: RadiationGrid::new(),
+            time_data: TimeData::new(),
+            orientation: EarthOrientation::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radiation_grid_looks_up_the_nearest_cell() {
+        // 3 x 4 grid of cell centers at lat -45, 0, 45 and lon 0, 90, 180, 270, reflectivity
+        // 10 i + j and emissivity 0.5 for cell [i][j], written out of order
+        let mut lines = vec![];
+        for j in (0..4).rev() {
+            for i in 0..3 {
+                lines.push(format!(
+                    "{} {} {} 0.5",
+                    -45. + 45. * i as f64,
+                    90. * j as f64,
+                    10 * i + j
+                ));
+            }
+        }
+        let path = std::env::temp_dir().join("rbody_radiation_grid.txt");
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let mut central_body = CentralBody::new();
+        central_body
+            .read_radiation_grid(path.to_str().unwrap())
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let grid = &central_body.radiation;
+        assert_eq!((grid.lat_step, grid.lon_step), (45., 90.));
+        assert!((grid.mean_reflectivity - 11.5).abs() < 1e-12);
+        let lookup = |lat: f64, lon: f64| grid.lookup(lat.to_radians(), lon.to_radians()).0;
+        // inside a cell and on either side of the boundary between two
+        assert_eq!(lookup(10., 100.), 11.);
+        assert_eq!(lookup(-22., 44.), 10.);
+        assert_eq!(lookup(-23., 46.), 1.);
+        // longitudes wrap around, west longitudes and the last cell before 360 included
+        assert_eq!(lookup(0., -80.), 13.);
+        assert_eq!(lookup(0., 315.5), 10.);
+        assert_eq!(lookup(0., -44.), 10.);
+        assert_eq!(lookup(0., 404.), 10.);
+        // latitudes past the outer rows use those rows
+        assert_eq!(lookup(89., 180.), 22.);
+        assert_eq!(lookup(-89., 270.), 3.);
+        assert_eq!(grid.lookup(0., 0.).1, 0.5);
+    }
+
+    #[test]
+    fn empty_radiation_grid_uses_the_means() {
+        let grid = RadiationGrid::new();
+        assert_eq!(
+            grid.lookup(0.3, -2.),
+            (grid.mean_reflectivity, grid.mean_emissivity)
+        );
+        let path = std::env::temp_dir().join("rbody_radiation_empty.txt");
+        std::fs::write(&path, "# no cells\n").unwrap();
+        let mut central_body = CentralBody::new();
+        let result = central_body.read_radiation_grid(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
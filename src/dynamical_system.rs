@@ -1,9 +1,15 @@
-// use crate::math::*;
 use crate::eoms::*;
+use crate::ephemeris::time2jd;
+use crate::geodetic::{ground_track, GroundStation};
+use crate::kepler::kepler_state;
+use crate::maneuver::{ManeuverEvent, ManeuverRecord};
+use crate::math::state2coes;
+use crate::mean_elements::mean_element_state;
 // use crate::otherbody::*;
 // use crate::satbody::*;
 // use matfile::{MatFile, NumericData};
 use bincode::serialize_into;
+use nalgebra::allocator::Allocator;
 use nalgebra::*;
 
 // use ndarray::Array2;
@@ -12,6 +18,14 @@ use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::time::Instant;
 // use nalgebra::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Propagator {
+    Numerical,    // rk4 on the full equations of motion
+    Keplerian,    // analytic two-body motion about the central body, position and velocity only
+    MeanElements, // semi-analytic zonal (j2 to j5) mean elements for satellites, rk4 otherwise
+}
+
 pub struct DynamicalSystem<'a> {
     pub eoms: &'a mut Eoms<'a>,
     pub time: f64,
@@ -21,6 +35,7 @@ pub struct DynamicalSystem<'a> {
     pub timeflag: bool,
     pub storeflag: bool,
     pub time_history: Vec<f64>,
+    pub propagator: Propagator,
 }
 
 impl<'a> DynamicalSystem<'a> {
@@ -32,7 +47,7 @@ impl<'a> DynamicalSystem<'a> {
     ) -> Vector6<f64> {
         let halfstep = self.step_width / 2.;
 
-        let k1 = dxdt(&state, &self.time);
+        let k1 = dxdt(state, &self.time);
 
         let k2 = dxdt(&(state + k1 * halfstep), &(self.time + halfstep));
 
@@ -46,48 +61,97 @@ impl<'a> DynamicalSystem<'a> {
         state + self.step_width / 6. * (k1 + 2. * k2 + 2. * k3 + k4)
     }
 
-    pub fn rk4_step<F>(&self, dxdt: F, state: &Vector6<f64>) -> Vector6<f64>
+    pub fn rk4_step<F, D: Dim>(&self, dxdt: F, state: &OVector<f64, D>) -> OVector<f64, D>
     where
-        F: Fn(&Vector6<f64>, &f64) -> Vector6<f64>,
+        F: Fn(&OVector<f64, D>, &f64) -> OVector<f64, D>,
+        DefaultAllocator: Allocator<f64, D>,
     {
-        let halfstep = self.step_width * 0.5;
-        let sixth_step_width = self.step_width / 6.0;
+        self.rk4_substep(dxdt, state, self.time, self.step_width)
+    }
 
-        let k1 = dxdt(&state, &self.time);
+    pub fn rk4_substep<F, D: Dim>(
+        &self,
+        dxdt: F,
+        state: &OVector<f64, D>,
+        time: f64,
+        step_width: f64,
+    ) -> OVector<f64, D>
+    where
+        F: Fn(&OVector<f64, D>, &f64) -> OVector<f64, D>,
+        DefaultAllocator: Allocator<f64, D>,
+    {
+        // rk4 step of arbitrary width starting at an arbitrary time, used to split steps, works
+        // for fixed (SVector) and dynamically (DVector) sized states
+        let halfstep = step_width * 0.5;
+        let sixth_step_width = step_width / 6.0;
 
-        let k2 = dxdt(&(state + k1 * halfstep), &(self.time + halfstep));
+        let k1 = dxdt(state, &time);
 
-        let k3 = dxdt(&(state + k2 * halfstep), &(self.time + halfstep));
+        let k2 = dxdt(&(state + &k1 * halfstep), &(time + halfstep));
 
-        let k4 = dxdt(
-            &(state + self.step_width * k3),
-            &(self.time + self.step_width),
-        );
+        let k3 = dxdt(&(state + &k2 * halfstep), &(time + halfstep));
+
+        let k4 = dxdt(&(state + &k3 * step_width), &(time + step_width));
 
-        state + sixth_step_width * (k1 + 2.0 * (k2 + k3) + k4)
+        state + (k1 + (k2 + k3) * 2.0 + k4) * sixth_step_width
+    }
+
+    pub fn kepler_substep(&self, state: &DVector<f64>, step_width: f64, mu: f64) -> DVector<f64> {
+        // advance position and velocity on a two-body orbit, any other states are held constant
+        let translational: Vector6<f64> = state.fixed_rows::<6>(0).into();
+        let mut state_new = state.clone();
+        state_new
+            .fixed_rows_mut::<6>(0)
+            .copy_from(&kepler_state(&translational, step_width, mu));
+        state_new
+    }
+
+    pub fn mean_element_substep(&self, state: &DVector<f64>, step_width: f64) -> DVector<f64> {
+        // advance position and velocity through the averaged zonal equations, any other states
+        // are held constant
+        let translational: Vector6<f64> = state.fixed_rows::<6>(0).into();
+        let mut state_new = state.clone();
+        state_new
+            .fixed_rows_mut::<6>(0)
+            .copy_from(&mean_element_state(
+                &translational,
+                step_width,
+                self.eoms.central_body,
+            ));
+        state_new
     }
 
     pub fn propagate(&mut self) {
         let start_time = Instant::now();
+        if self.storeflag {
+            self.time_history.push(self.time);
+        }
 
         // storing and/or writing initial states for satellites
         for sat_num in 0..self.eoms.satellite.len() {
+            let satellite = &mut self.eoms.satellite[sat_num];
+            let translational: Vector6<f64> = satellite.state.fixed_rows::<6>(0).into();
+            satellite.maneuvers.remove_missed(self.time, &translational);
+            // make sure every attitude representation matches the main one before packing
+            if let Some(attitude) = &mut self.eoms.satellite[sat_num].attitude {
+                attitude.update_others();
+            }
             if self.storeflag {
                 // store first state
-                let init_state = self.eoms.satellite[sat_num].state.data.0[0];
-                self.eoms.satellite[sat_num]
-                    .state_history
-                    .push(init_state.to_vec());
+                let init_state = self.eoms.satellite[sat_num].state.as_slice().to_vec();
+                self.eoms.satellite[sat_num].state_history.push(init_state);
+                self.eoms.satellite[sat_num].time_history.push(self.time);
+                self.store_attitude(sat_num);
             }
         }
         // storing initial states for other bodies
         for other_num in 0..self.eoms.other_body.len() {
             if self.storeflag {
                 // store first state
-                let init_state = self.eoms.other_body[other_num].state.data.0[0];
+                let init_state = self.eoms.other_body[other_num].state.as_slice().to_vec();
                 self.eoms.other_body[other_num]
                     .state_history
-                    .push(init_state.to_vec());
+                    .push(init_state);
             }
 
             let mut init_pos = Vector3::zeros();
@@ -102,27 +166,103 @@ impl<'a> DynamicalSystem<'a> {
                 .copy_from(&init_pos);
         }
 
+        self.update_coes();
+
         // propagation ----------------------------------------------------------------------
         for _k in 0..self.maxsteps {
+            // one nutation evaluation serves every rk4 stage of the step
+            self.eoms
+                .central_body
+                .orientation
+                .hold_nutation(time2jd(&self.time), time2jd(&(self.time + self.step_width)));
             // integrate for each satellite
             for sat_num in 0..self.eoms.satellite.len() {
-                let current_state = self.eoms.satellite[sat_num].state.clone();
+                // integrated satellite state is position, velocity, auxiliary states and mass
+                let time_end = self.time + self.step_width;
+                let central_body = self.eoms.central_body;
+                self.eoms.satellite[sat_num].update_control(central_body, &self.time);
 
-                let dxdt_fun = |state: &Vector6<f64>, time: &f64| -> Vector6<f64> {
-                    self.eoms.dxdt(state, time, 9999)
+                let dxdt_fun = |state: &DVector<f64>, time: &f64| -> DVector<f64> {
+                    self.eoms.dxdt_sat(state, time, sat_num)
+                };
+                let advance = |state: &DVector<f64>, time: f64, step_width: f64| match self
+                    .propagator
+                {
+                    Propagator::Numerical => self.rk4_substep(dxdt_fun, state, time, step_width),
+                    Propagator::Keplerian => {
+                        self.kepler_substep(state, step_width, central_body.mu)
+                    }
+                    Propagator::MeanElements => self.mean_element_substep(state, step_width),
                 };
 
-                let state_new = self.rk4_step(&dxdt_fun, &current_state);
+                // split the step at every maneuver epoch, burn ignition/cutoff and propellant
+                // depletion so impulsive delta v's are applied exactly at their epoch and thrust
+                // is never switched on or off inside an rk4 step
+                let plan = &self.eoms.satellite[sat_num].maneuvers;
+                let mut state_new = self.eoms.satellite[sat_num].pack_state();
+                let mass = state_new[self.eoms.satellite[sat_num].mass_index()];
+                let depletion = plan.depletion_time(self.time, time_end, mass);
+                let mut breakpoints = plan.breakpoints(self.time, time_end);
+                if let Some(depletion) = depletion {
+                    breakpoints.push(depletion);
+                    breakpoints.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                    breakpoints.dedup();
+                }
+                let maneuvers = plan.pending(self.time, time_end);
+                let mut records = Vec::with_capacity(maneuvers.len());
+                let mut depleted_state = None;
+                let mut time_sub = self.time;
+                for breakpoint in breakpoints.iter() {
+                    if *breakpoint > time_sub {
+                        state_new = advance(&state_new, time_sub, breakpoint - time_sub);
+                        time_sub = *breakpoint;
+                    }
+                    if depletion == Some(*breakpoint) {
+                        // the propellant is used up here, the last rk4 stage already sees the
+                        // burn as off so the mass is set to the dry mass exactly
+                        let mass_index = self.eoms.satellite[sat_num].mass_index();
+                        state_new[mass_index] = plan.dry_mass;
+                        let translational: Vector6<f64> = state_new.fixed_rows::<6>(0).into();
+                        depleted_state = Some((*breakpoint, translational));
+                    }
+                    for (index, maneuver) in
+                        maneuvers.iter().filter(|(_, m)| m.epoch == *breakpoint)
+                    {
+                        let translational: Vector6<f64> = state_new.fixed_rows::<6>(0).into();
+                        let delta_v = maneuver.inertial_delta_v(&translational);
+                        let translational = maneuver.apply(&translational);
+                        state_new.fixed_rows_mut::<6>(0).copy_from(&translational);
+                        records.push((
+                            *index,
+                            ManeuverRecord {
+                                epoch: maneuver.epoch,
+                                delta_v,
+                                frame: maneuver.frame,
+                                state: translational,
+                                event: ManeuverEvent::Impulsive,
+                            },
+                        ));
+                    }
+                }
+                state_new = advance(&state_new, time_sub, time_end - time_sub);
 
-                self.eoms.satellite[sat_num].state = state_new;
+                for (index, record) in records {
+                    self.eoms.satellite[sat_num]
+                        .maneuvers
+                        .mark_applied(index, record);
+                }
+                if let Some((time, state)) = depleted_state {
+                    self.eoms.satellite[sat_num]
+                        .maneuvers
+                        .log_depletion(time, &state);
+                }
+                self.eoms.satellite[sat_num].unpack_state(&state_new);
                 // store state and time histories
                 if self.storeflag {
-                    let state_new = self.eoms.satellite[sat_num].state;
-                    self.eoms.satellite[sat_num]
-                        .state_history
-                        .push(state_new.data.0[0].to_vec());
-                    let time_new = self.time + self.step_width;
-                    self.eoms.satellite[sat_num].time_history.push(time_new);
+                    let state_new = self.eoms.satellite[sat_num].state.as_slice().to_vec();
+                    self.eoms.satellite[sat_num].state_history.push(state_new);
+                    self.eoms.satellite[sat_num].time_history.push(time_end);
+                    self.store_attitude(sat_num);
                 }
             }
             for other_num in 0..self.eoms.other_body.len() {
@@ -139,33 +279,71 @@ impl<'a> DynamicalSystem<'a> {
                     .copy_from(&old_pos);
 
                 let current_state = self.eoms.other_body[other_num].state.clone();
-                let dxdt_fun = |state: &Vector6<f64>, time: &f64| -> Vector6<f64> {
+                let dxdt_fun = |state: &DVector<f64>, time: &f64| -> DVector<f64> {
                     self.eoms
                         .dxdt2(state, time, self.eoms.other_body[other_num].id)
                 };
 
-                let state_new = self.rk4_step(&dxdt_fun, &current_state);
+                let state_new = match self.propagator {
+                    Propagator::Numerical | Propagator::MeanElements => {
+                        self.rk4_step(dxdt_fun, &current_state)
+                    }
+                    Propagator::Keplerian => self.kepler_substep(
+                        &current_state,
+                        self.step_width,
+                        self.eoms.central_body.mu + self.eoms.other_body[other_num].mu,
+                    ),
+                };
 
                 self.eoms.other_body[other_num].state = state_new;
 
                 // store state and time histories
                 if self.storeflag {
-                    let state_new = self.eoms.other_body[other_num].state;
+                    let state_new = self.eoms.other_body[other_num].state.as_slice().to_vec();
                     self.eoms.other_body[other_num]
                         .state_history
-                        .push(state_new.data.0[0].to_vec());
-                    self.time_history.push(self.time + self.step_width);
+                        .push(state_new);
                 }
             }
             self.time += self.step_width;
+            if self.storeflag {
+                self.time_history.push(self.time);
+            }
+            self.update_coes();
         }
 
-        if self.timeflag == true {
+        if self.timeflag {
             let end_time = Instant::now() - start_time;
             println!("Elapsed propagation time: {:?}", end_time);
         }
     }
 
+    fn update_coes(&mut self) {
+        // osculating elements of every body about the central body
+        let mu = self.eoms.central_body.mu;
+        for satellite in self.eoms.satellite.iter_mut() {
+            let translational: Vector6<f64> = satellite.state.fixed_rows::<6>(0).into();
+            satellite.coes = state2coes(&translational, mu);
+        }
+        for body in self.eoms.other_body.iter_mut() {
+            let translational: Vector6<f64> = body.state.fixed_rows::<6>(0).into();
+            body.coes = state2coes(&translational, mu + body.mu);
+        }
+    }
+
+    fn store_attitude(&mut self, sat_num: usize) {
+        // attitude history holds the integrated attitude parameters followed by omega
+        let satellite = &mut self.eoms.satellite[sat_num];
+        if satellite.attitude.is_some() {
+            let packed = satellite.pack_state();
+            let att_index = satellite.attitude_index();
+            let entry = packed.rows(att_index, packed.len() - att_index);
+            satellite
+                .attitude_history
+                .push(entry.iter().copied().collect());
+        }
+    }
+
     #[allow(dead_code)]
     pub fn writefiles(&self) -> Result<(), Box<dyn Error>> {
         // Ensure the directory exists
@@ -266,12 +444,10 @@ impl<'a> DynamicalSystem<'a> {
 
         for writer in &mut files {
             writer.flush()?;
-            drop(writer);
         }
 
         for writer in &mut other_files {
             writer.flush()?;
-            drop(writer);
         }
 
         let end_time = Instant::now() - start_time;
@@ -279,6 +455,130 @@ impl<'a> DynamicalSystem<'a> {
         Ok(())
     }
 
+    pub fn writemaneuvers(&self) -> Result<(), Box<dyn Error>> {
+        // one line per maneuver event: epoch, inertial delta v, frame, post-burn state, event
+        // (impulsive burns, finite burn cutoff at the dry mass and maneuvers skipped because
+        // they were before the start)
+        std::fs::create_dir_all("outputs/txt")?;
+
+        for satellite in self.eoms.satellite.iter() {
+            if satellite.maneuvers.log.is_empty() {
+                continue;
+            }
+            let filename = format!("outputs/txt/maneuvers_{}.txt", satellite.name);
+            let mut file = BufWriter::new(File::create(&filename)?);
+            for record in satellite.maneuvers.log.iter() {
+                writeln!(
+                    file,
+                    "{:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}",
+                    record.epoch,
+                    record.delta_v[0],
+                    record.delta_v[1],
+                    record.delta_v[2],
+                    record.frame,
+                    record.state[0],
+                    record.state[1],
+                    record.state[2],
+                    record.state[3],
+                    record.state[4],
+                    record.state[5],
+                    record.event
+                )?;
+            }
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn writegroundtracks(&self) -> Result<(), Box<dyn Error>> {
+        // one line per stored state: time, geodetic latitude, longitude (deg) and altitude above
+        // the ellipsoid (km)
+        std::fs::create_dir_all("outputs/txt")?;
+
+        for satellite in self.eoms.satellite.iter() {
+            let filename = format!("outputs/txt/groundtrack_{}.txt", satellite.name);
+            let mut file = BufWriter::new(File::create(&filename)?);
+            for (time, point) in ground_track(satellite, self.eoms.central_body) {
+                writeln!(
+                    file,
+                    "{:?}, {:?}, {:?}, {:?}",
+                    time,
+                    point.lat.to_degrees(),
+                    point.lon.to_degrees(),
+                    point.alt
+                )?;
+            }
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn writelookangles(&self, stations: &[GroundStation]) -> Result<(), Box<dyn Error>> {
+        // one file per satellite and ground station: time, azimuth and elevation (deg), range
+        // (km) and whether the satellite is above the station's elevation mask
+        std::fs::create_dir_all("outputs/txt")?;
+
+        let central_body = self.eoms.central_body;
+        for satellite in self.eoms.satellite.iter() {
+            for station in stations.iter() {
+                let filename = format!(
+                    "outputs/txt/lookangles_{}_{}.txt",
+                    satellite.name, station.name
+                );
+                let mut file = BufWriter::new(File::create(&filename)?);
+                for (time, state) in satellite
+                    .time_history
+                    .iter()
+                    .zip(satellite.state_history.iter())
+                {
+                    let r_eci = Vector3::new(state[0], state[1], state[2]);
+                    let (azimuth, elevation, range) =
+                        station.look_angles(&r_eci, time, central_body);
+                    writeln!(
+                        file,
+                        "{:?}, {:?}, {:?}, {:?}, {:?}",
+                        time,
+                        azimuth.to_degrees(),
+                        elevation.to_degrees(),
+                        range,
+                        elevation >= station.min_elevation
+                    )?;
+                }
+                file.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn writeresiduals(&self) -> Result<(), Box<dyn Error>> {
+        // satellites created from an element set: time and numerical minus sgp4 position (km)
+        // and velocity (km/s) in the inertial frame
+        std::fs::create_dir_all("outputs/txt")?;
+
+        for satellite in self.eoms.satellite.iter() {
+            let Some(sgp4) = &satellite.sgp4 else {
+                continue;
+            };
+            let filename = format!("outputs/txt/residuals_{}.txt", satellite.name);
+            let mut file = BufWriter::new(File::create(&filename)?);
+            for (time, residual) in sgp4.residuals(satellite, &self.eoms.central_body.time_data) {
+                writeln!(
+                    file,
+                    "{:?}, {:?}, {:?}, {:?}, {:?}, {:?}, {:?}",
+                    time,
+                    residual[0],
+                    residual[1],
+                    residual[2],
+                    residual[3],
+                    residual[4],
+                    residual[5]
+                )?;
+            }
+            file.flush()?;
+        }
+        Ok(())
+    }
+
     // pub fn writemat(&self) -> Result<(), Box<dyn Error>> {
     //     let start_time = Instant::now();
     //
@@ -327,3 +627,97 @@ impl<'a> DynamicalSystem<'a> {
     //     Ok(())
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::centralbody::CentralBody;
+    use crate::maneuver::{FiniteBurn, SteeringLaw};
+    use crate::otherbody::OtherBody;
+    use crate::satbody::SatBody;
+
+    #[test]
+    fn finite_burn_cuts_off_at_the_dry_mass() {
+        let mut earth = CentralBody::new();
+        earth.mu = 3.986004418e5;
+        earth.equatorial_radius = 6378.137;
+        let mut satellite = SatBody::new();
+        satellite.name = String::from("sat");
+        satellite.mass = 100.;
+        satellite.state = dvector![7000., 0., 0., 0., 7.546, 0.];
+        satellite.maneuvers.dry_mass = 60.;
+        satellite.maneuvers.add_finite(FiniteBurn::new(
+            0.,
+            1e6,
+            10.,
+            300.,
+            SteeringLaw::VelocityAligned,
+        ));
+        let flow = satellite.maneuvers.finite[0].mass_flow();
+
+        let mut satellites: Vec<&mut SatBody> = vec![&mut satellite];
+        let mut others: Vec<&mut OtherBody> = vec![];
+        let mut eoms = Eoms::spherical(&earth, &mut satellites, &mut others);
+        let mut system = DynamicalSystem {
+            maxsteps: 300,
+            step_width: 60.,
+            time: 0.,
+            eoms: &mut eoms,
+            writeflag: false,
+            timeflag: false,
+            storeflag: true,
+            time_history: vec![],
+            propagator: Propagator::Numerical,
+        };
+        system.propagate();
+
+        let satellite = &system.eoms.satellite[0];
+        assert!((satellite.mass - 60.).abs() < 1e-9);
+        let cutoffs: Vec<_> = satellite
+            .maneuvers
+            .log
+            .iter()
+            .filter(|record| record.event == ManeuverEvent::PropellantDepleted)
+            .collect();
+        assert_eq!(cutoffs.len(), 1);
+        assert!((cutoffs[0].epoch - 40. / flow).abs() < 1e-6);
+    }
+
+    #[test]
+    fn histories_hold_one_time_per_stored_state() {
+        let mut earth = CentralBody::new();
+        earth.mu = 3.986004418e5;
+        let mut satellite = SatBody::new();
+        satellite.state = dvector![7000., 0., 0., 0., 7.546, 0.];
+        let mut moon = OtherBody::new();
+        moon.state = dvector![384400., 0., 0., 0., 1.018, 0.];
+
+        let mut satellites: Vec<&mut SatBody> = vec![&mut satellite];
+        let mut others: Vec<&mut OtherBody> = vec![&mut moon];
+        let mut eoms = Eoms::spherical(&earth, &mut satellites, &mut others);
+        let mut system = DynamicalSystem {
+            maxsteps: 5,
+            step_width: 60.,
+            time: 1000.,
+            eoms: &mut eoms,
+            writeflag: false,
+            timeflag: false,
+            storeflag: true,
+            time_history: vec![],
+            propagator: Propagator::Numerical,
+        };
+        system.propagate();
+
+        let satellite = &system.eoms.satellite[0];
+        assert_eq!(satellite.time_history.len(), 6);
+        assert_eq!(satellite.state_history.len(), 6);
+        assert_eq!(satellite.time_history[0], 1000.);
+        assert_eq!(
+            satellite.state_history[0],
+            vec![7000., 0., 0., 0., 7.546, 0.]
+        );
+        assert_eq!(satellite.time_history[5], 1300.);
+        assert_eq!(system.time_history, satellite.time_history);
+        assert_eq!(system.eoms.other_body[0].state_history.len(), 6);
+    }
+}
@@ -1,8 +1,14 @@
+use crate::attitude::*;
 use crate::centralbody::*;
+use crate::ephemeris::*;
+use crate::geodetic::ecef2geodetic;
+use crate::math::*;
 use crate::otherbody::*;
 use crate::satbody::*;
 
+use nalgebra::allocator::Allocator;
 use nalgebra::*;
+use std::f64::consts::PI;
 use std::ops::AddAssign;
 use std::ops::SubAssign;
 
@@ -15,45 +21,144 @@ pub struct Eoms<'a> {
 
 #[allow(dead_code)]
 impl<'a> Eoms<'a> {
-    pub fn dxdt(&self, state: &Vector6<f64>, time: &f64, other_body_id: usize) -> Vector6<f64> {
+    pub fn dxdt<D: Dim>(
+        &self,
+        state: &OVector<f64, D>,
+        time: &f64,
+        other_body_id: usize,
+    ) -> OVector<f64, D>
+    where
+        DefaultAllocator: Allocator<f64, D>,
+    {
         match &self.model {
             GravityModel::Spherical(model) => model.calculate(
-                &self.central_body,
-                &self.other_body,
+                self.central_body,
+                self.other_body,
                 other_body_id,
-                &state,
-                &time,
+                state,
+                time,
             ),
             GravityModel::J(model) => model.calculate(
-                &self.central_body,
-                &self.other_body,
+                self.central_body,
+                self.other_body,
                 other_body_id,
                 state,
                 time,
             ),
             GravityModel::SphHarmonic(model) => model.calculate(
-                &self.central_body,
-                &self.other_body,
+                self.central_body,
+                self.other_body,
                 other_body_id,
-                &state,
-                &time,
+                state,
+                time,
             ), // ... other models
             GravityModel::OtherModel(model) => model.calculate(
-                &self.central_body,
-                &self.other_body,
+                self.central_body,
+                self.other_body,
                 other_body_id,
-                &state,
-                &time,
+                state,
+                time,
             ),
         }
     }
-    pub fn dxdt2(&self, state: &Vector6<f64>, time: &f64, other_body_id: usize) -> Vector6<f64> {
+    pub fn dxdt_sat(&self, state: &DVector<f64>, time: &f64, sat_num: usize) -> DVector<f64> {
+        // satellite dynamics on the packed state from SatBody::pack_state, gravity acts on the
+        // translational states, finite burns add thrust and deplete mass and any auxiliary
+        // states are handled by the satellite's aux_dxdt
+        let satellite = &self.satellite[sat_num];
+        let mass_index = satellite.mass_index();
+        let mut state_dot = self.dxdt(state, time, 9999);
+
+        let translational: Vector6<f64> = state.fixed_rows::<6>(0).into();
+        let (thrust_accel, mass_dot) =
+            satellite
+                .maneuvers
+                .thrust(&translational, state[mass_index], *time);
+        state_dot.fixed_rows_mut::<3>(3).add_assign(&thrust_accel);
+        state_dot[mass_index] = mass_dot;
+
+        let body = satellite.attitude_dcm(state).map(|dcm| BodyAttitude {
+            dcm,
+            inertia: &satellite.inertia,
+            panels: &satellite.panels,
+        });
+        let mut torque = Vector3::zeros();
+        for perturbation in satellite.perturbations.iter() {
+            let accel = perturbation.acceleration(
+                self.central_body,
+                &translational,
+                state[mass_index],
+                body.as_ref(),
+                time,
+            );
+            state_dot.fixed_rows_mut::<3>(3).add_assign(&accel);
+            if let Some(body) = &body {
+                torque += perturbation.torque(self.central_body, &translational, body, time);
+            }
+        }
+
+        if let Some(aux_dxdt) = &satellite.aux_dxdt {
+            let aux_dot = aux_dxdt(&state.rows(0, mass_index).into_owned(), *time);
+            state_dot.rows_mut(6, mass_index - 6).copy_from(&aux_dot);
+        }
+
+        if satellite.attitude.is_some() {
+            // attitude kinematics and euler's rotational equations
+            let att_index = satellite.attitude_index();
+            let n_att = satellite.attitude_len();
+            let omega: Vector3<f64> = state.fixed_rows::<3>(att_index + n_att).into();
+            if n_att == 3 {
+                let sigma: Vector3<f64> = state.fixed_rows::<3>(att_index).into();
+                state_dot
+                    .fixed_rows_mut::<3>(att_index)
+                    .copy_from(&Mrps::kinematics(&sigma, &omega));
+            } else {
+                let quaternion: Vector4<f64> = state.fixed_rows::<4>(att_index).into();
+                state_dot
+                    .fixed_rows_mut::<4>(att_index)
+                    .copy_from(&Quaternions::kinematics(&quaternion, &omega));
+            }
+            if let (Some(adcs), Some(body)) = (&satellite.adcs, &body) {
+                // actuator torques and reaction wheel spin up
+                let wheel_index = satellite.wheel_index();
+                let speeds: Vec<f64> = state
+                    .rows(wheel_index, adcs.wheels.len())
+                    .iter()
+                    .copied()
+                    .collect();
+                torque += adcs.torque(
+                    self.central_body,
+                    &translational,
+                    &body.dcm,
+                    &omega,
+                    &speeds,
+                    time,
+                );
+                for (i, accel) in adcs.wheel_accelerations(&speeds).iter().enumerate() {
+                    state_dot[wheel_index + i] = *accel;
+                }
+            }
+            state_dot
+                .fixed_rows_mut::<3>(att_index + n_att)
+                .copy_from(&rigid_body_dynamics(&satellite.inertia, &omega, &torque));
+        }
+        state_dot
+    }
+    pub fn dxdt2<D: Dim>(
+        &self,
+        state: &OVector<f64, D>,
+        time: &f64,
+        other_body_id: usize,
+    ) -> OVector<f64, D>
+    where
+        DefaultAllocator: Allocator<f64, D>,
+    {
         OtherGrav.calculate(
-            &self.central_body,
-            &self.other_body,
+            self.central_body,
+            self.other_body,
             other_body_id,
-            &state,
-            &time,
+            state,
+            time,
         )
     }
     // constructor functions
@@ -118,36 +223,43 @@ pub enum GravityModel {
     OtherModel(OtherGrav),
 }
 
+// gravity models act on the first six (position and velocity) states of a state vector of any
+// size, derivatives of the remaining auxiliary states are left at zero
 pub trait GravityCalculation {
-    fn calculate<'a>(
+    fn calculate<D: Dim>(
         &self,
         central_body: &CentralBody,
-        other_body: &Vec<&mut OtherBody>,
+        other_body: &[&mut OtherBody],
         other_body_id: usize,
-        state: &Vector6<f64>,
+        state: &OVector<f64, D>,
         time: &f64,
-    ) -> Vector6<f64>;
+    ) -> OVector<f64, D>
+    where
+        DefaultAllocator: Allocator<f64, D>;
 }
 
 #[derive(PartialEq)]
 pub struct OtherGrav;
 impl GravityCalculation for OtherGrav {
-    fn calculate<'a>(
+    fn calculate<D: Dim>(
         &self,
         central_body: &CentralBody,
-        other_body: &Vec<&mut OtherBody>,
+        other_body: &[&mut OtherBody],
         other_body_id: usize, // set to 0 if satellite
-        state: &Vector6<f64>,
+        state: &OVector<f64, D>,
         _time: &f64,
-    ) -> Vector6<f64> {
-        let mut state_dot = Vector6::zeros();
+    ) -> OVector<f64, D>
+    where
+        DefaultAllocator: Allocator<f64, D>,
+    {
+        let mut state_dot = OVector::zeros_generic(state.shape_generic().0, Const::<1>);
 
         // Initialize the state_dot with the current state's velocity
         state_dot
             .fixed_rows_mut::<3usize>(0)
             .copy_from(&state.fixed_rows::<3usize>(3));
 
-        let mut x = Vector3::zeros();
+        let mut x = Vector3::new(0., 0., 0.);
         x.fixed_rows_mut::<3usize>(0)
             .copy_from(&state.fixed_rows::<3usize>(0));
 
@@ -159,7 +271,7 @@ impl GravityCalculation for OtherGrav {
                 // Skip if the body is the same as the one being evaluated
                 continue;
             }
-            let mut y = Vector3::zeros();
+            let mut y = Vector3::new(0., 0., 0.);
             y.fixed_rows_mut::<3usize>(0)
                 .copy_from(&body.pos_old.fixed_rows::<3usize>(0));
             let delta_x = x - y; // Assuming `position` is a field in OtherBody
@@ -184,15 +296,18 @@ impl GravityCalculation for OtherGrav {
 #[derive(PartialEq)]
 pub struct SphericalGrav;
 impl GravityCalculation for SphericalGrav {
-    fn calculate<'a>(
+    fn calculate<D: Dim>(
         &self,
         central_body: &CentralBody,
-        other_body: &Vec<&mut OtherBody>,
+        other_body: &[&mut OtherBody],
         other_body_id: usize,
-        state: &Vector6<f64>,
+        state: &OVector<f64, D>,
         _time: &f64,
-    ) -> Vector6<f64> {
-        let mut state_dot = Vector6::zeros();
+    ) -> OVector<f64, D>
+    where
+        DefaultAllocator: Allocator<f64, D>,
+    {
+        let mut state_dot = OVector::zeros_generic(state.shape_generic().0, Const::<1>);
 
         // Initialize the state_dot with the current state's velocity
         state_dot
@@ -233,25 +348,31 @@ impl GravityCalculation for SphericalGrav {
     }
 }
 
+// highest zonal the j model applies, orders above it are rejected before a run
+pub const MAX_ZONAL_ORDER: usize = 6;
+
 #[derive(PartialEq)]
 pub struct JGrav;
 impl GravityCalculation for JGrav {
-    fn calculate<'a>(
+    fn calculate<D: Dim>(
         &self,
         central_body: &CentralBody,
-        other_body: &Vec<&mut OtherBody>,
+        other_body: &[&mut OtherBody],
         other_body_id: usize,
-        state: &Vector6<f64>,
+        state: &OVector<f64, D>,
         _time: &f64,
-    ) -> Vector6<f64> {
-        let mut state_dot = Vector6::zeros();
+    ) -> OVector<f64, D>
+    where
+        DefaultAllocator: Allocator<f64, D>,
+    {
+        let mut state_dot = OVector::zeros_generic(state.shape_generic().0, Const::<1>);
 
         // copy velocity into first three elements of dxdt
         state_dot
             .fixed_rows_mut::<3usize>(0)
             .copy_from(&state.fixed_rows::<3usize>(3));
 
-        let mut x = Vector3::zeros();
+        let mut x = Vector3::new(0., 0., 0.);
         x.fixed_rows_mut::<3usize>(0)
             .copy_from(&state.fixed_rows::<3usize>(0));
         let r = x.norm();
@@ -261,42 +382,25 @@ impl GravityCalculation for JGrav {
             .fixed_rows_mut::<3usize>(3)
             .copy_from(&(state * -muor3).fixed_rows::<3usize>(0));
 
-        let j_vals = [
-            1.08262668355e-3,  // j2
-            -2.53265648533e-6, // j3
-            -1.61962159137e-6, // j4
-            -2.27296082869e-7, // j5
-            5.40681239107e-7,  // j6
-        ];
-
-        if central_body.max_order == 2 {
-            // j2
-            let a_j2 = -3. / 2.
-                * j_vals[0]
-                * muor3
-                * (central_body.equatorial_radius / r).powi(2)
-                * vector![
-                    (1. - 5. * (state[2] / r).powi(2)) * state[0],
-                    (1. - 5. * (state[2] / r).powi(2)) * state[1],
-                    (3. - 5. * (state[2] / r).powi(2)) * state[2]
-                ];
-            state_dot.fixed_rows_mut::<3>(3).add_assign(&a_j2);
-        }
-        if central_body.max_order == 3 {
-            // j3
-            todo!()
-        }
-        if central_body.max_order == 4 {
-            // j4
-            todo!()
-        }
-        if central_body.max_order == 5 {
-            // j5
-            todo!()
-        }
-        if central_body.max_order == 6 {
-            // j6
-            todo!()
+        // zonal harmonics j2 up to the max order, a = mu J_n R^n / r^(n+2) ((n+1) P_n(u) r_hat
+        // - P_n'(u) (z_hat - u r_hat)) with u = z / r
+        let u = state[2] / r;
+        let r_hat = x / r;
+        let z_hat = vector![0., 0., 1.];
+        // legendre polynomials and their derivatives by recursion
+        let (mut p_prev, mut p) = (1., u);
+        let (mut dp_prev, mut dp) = (0., 1.);
+        for n in 2..=central_body.max_order.min(MAX_ZONAL_ORDER) {
+            let k = (n - 1) as f64;
+            let p_next = ((2. * k + 1.) * u * p - k * p_prev) / (k + 1.);
+            let dp_next = dp_prev + (2. * k + 1.) * p;
+            (p_prev, p) = (p, p_next);
+            (dp_prev, dp) = (dp, dp_next);
+            let nf = n as f64;
+            let a_jn = central_body.zonal(n) * central_body.mu / r.powi(2)
+                * (central_body.equatorial_radius / r).powi(n as i32)
+                * ((nf + 1.) * p * r_hat - dp * (z_hat - u * r_hat));
+            state_dot.fixed_rows_mut::<3>(3).add_assign(&a_jn);
         }
 
         // Compute gravitational effects from each body in other_body
@@ -305,7 +409,7 @@ impl GravityCalculation for JGrav {
                 // Skip if the body is the same as the one being evaluated
                 continue;
             }
-            let mut y = Vector3::zeros();
+            let mut y = Vector3::new(0., 0., 0.);
             y.fixed_rows_mut::<3usize>(0)
                 .copy_from(&body.pos_old.fixed_rows::<3usize>(0)); // position of other body
             let delta_x = x - y; // Assuming `position` is a field in OtherBody
@@ -323,31 +427,33 @@ impl GravityCalculation for JGrav {
 
 #[derive(PartialEq)]
 pub struct SphHarmonicGrav;
-#[allow(non_snake_case)]
+#[allow(non_snake_case, clippy::assign_op_pattern)]
 impl GravityCalculation for SphHarmonicGrav {
-    fn calculate<'a>(
+    fn calculate<D: Dim>(
         &self,
         central_body: &CentralBody,
-        other_body: &Vec<&mut OtherBody>,
+        other_body: &[&mut OtherBody],
         other_body_id: usize,
-        state: &Vector6<f64>,
+        state: &OVector<f64, D>,
         time: &f64,
-    ) -> Vector6<f64> {
-        let ct = (central_body.omega * time).cos();
-        let st = (central_body.omega * time).sin();
+    ) -> OVector<f64, D>
+    where
+        DefaultAllocator: Allocator<f64, D>,
+    {
+        let eci2ecef = central_body.gen_eci2ecef(time);
 
-        let mut state_dot = Vector6::zeros();
+        let mut state_dot = OVector::zeros_generic(state.shape_generic().0, Const::<1>);
 
         // copy velocity into first three elements of dxdt
         state_dot
             .fixed_rows_mut::<3usize>(0)
             .copy_from(&state.fixed_rows::<3usize>(3));
 
-        let mut x = Vector3::zeros();
+        let mut x = Vector3::new(0., 0., 0.);
         x.fixed_rows_mut::<3usize>(0)
             .copy_from(&state.fixed_rows::<3usize>(0));
 
-        let x = Vector3::new(x[0] * ct + x[1] * st, x[1] * ct - x[0] * st, x[2]);
+        let x = eci2ecef * x;
 
         let maxord = central_body.max_order;
         let maxdeg = central_body.max_deg;
@@ -433,15 +539,10 @@ impl GravityCalculation for SphHarmonicGrav {
         // let mut grav_sph = self.sphharmon_grav(&x_ecef); // acceleration due to gravity wrt to
         // ECI in ECEF coordinates (no need to
         // counteract rotational effects on acceleration)
-        grav_sph = Vector3::new(
-            grav_sph[0] * ct - grav_sph[1] * st,
-            grav_sph[1] * ct + grav_sph[0] * st,
-            grav_sph[2],
-        );
+        grav_sph = eci2ecef.transpose() * grav_sph; // transformation from ecef to eci
 
-        let x = Vector3::new(x[0] * ct - x[1] * st, x[1] * ct + x[0] * st, x[2]);
+        let x = eci2ecef.transpose() * x;
 
-        // grav_sph = eci2ecef.transpose() * grav_sph; // transformation from ecef to eci
         // https://spsweb.fltops.jpl.nasa.gov/portaldataops/mpg/MPG_Docs/Source%20Docs/gravity-SphericalHarmonics.pdf
         // page 5 to 7,
         // spherical harmonics calculations from sphharmon_grav takes in ecef, gets spherical
@@ -455,7 +556,7 @@ impl GravityCalculation for SphHarmonicGrav {
                 // Skip if the body is the same as the one being evaluated
                 continue;
             }
-            let mut y = Vector3::zeros();
+            let mut y = Vector3::new(0., 0., 0.);
             y.fixed_rows_mut::<3usize>(0)
                 .copy_from(&body.pos_old.fixed_rows::<3usize>(0)); // position of other body
             let delta_x = x - y; // Assuming `position` is a field in OtherBody
@@ -474,6 +575,767 @@ impl GravityCalculation for SphHarmonicGrav {
 #[allow(dead_code)]
 pub enum Perturbations {
     Aerodynamic(AeroAccel),
+    SolarRadiation(SolarRadiationAccel),
+    EarthRadiation(EarthRadiationAccel),
+    Empirical(EmpiricalAccel),
+    GravityGradient(GravityGradientTorque),
+}
+
+pub struct BodyAttitude<'a> {
+    // attitude dependent inputs, dcm is [BN] from the integrated attitude
+    pub dcm: Matrix3<f64>,
+    pub inertia: &'a Matrix3<f64>, // kg.m^2
+    pub panels: &'a [Panel],
+}
+
+// non-gravitational accelerations acting on a single satellite, returned in km/s^2, and
+// disturbance torques about the center of mass, returned in N.m in the body frame
+pub trait PerturbationCalculation {
+    fn acceleration(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        mass: f64,
+        body: Option<&BodyAttitude>,
+        time: &f64,
+    ) -> Vector3<f64>;
+
+    fn torque(
+        &self,
+        _central_body: &CentralBody,
+        _state: &Vector6<f64>,
+        _body: &BodyAttitude,
+        _time: &f64,
+    ) -> Vector3<f64> {
+        Vector3::zeros()
+    }
+}
+
+impl Perturbations {
+    pub fn acceleration(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        mass: f64,
+        body: Option<&BodyAttitude>,
+        time: &f64,
+    ) -> Vector3<f64> {
+        match self {
+            Perturbations::Aerodynamic(model) => {
+                model.acceleration(central_body, state, mass, body, time)
+            }
+            Perturbations::SolarRadiation(model) => {
+                model.acceleration(central_body, state, mass, body, time)
+            }
+            Perturbations::EarthRadiation(model) => {
+                model.acceleration(central_body, state, mass, body, time)
+            }
+            Perturbations::Empirical(model) => {
+                model.acceleration(central_body, state, mass, body, time)
+            }
+            Perturbations::GravityGradient(model) => {
+                model.acceleration(central_body, state, mass, body, time)
+            }
+        }
+    }
+
+    pub fn torque(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        body: &BodyAttitude,
+        time: &f64,
+    ) -> Vector3<f64> {
+        match self {
+            Perturbations::Aerodynamic(model) => model.torque(central_body, state, body, time),
+            Perturbations::SolarRadiation(model) => model.torque(central_body, state, body, time),
+            Perturbations::EarthRadiation(model) => model.torque(central_body, state, body, time),
+            Perturbations::Empirical(model) => model.torque(central_body, state, body, time),
+            Perturbations::GravityGradient(model) => model.torque(central_body, state, body, time),
+        }
+    }
+}
+
+pub struct GravityGradientTorque;
+
+impl PerturbationCalculation for GravityGradientTorque {
+    fn acceleration(
+        &self,
+        _central_body: &CentralBody,
+        _state: &Vector6<f64>,
+        _mass: f64,
+        _body: Option<&BodyAttitude>,
+        _time: &f64,
+    ) -> Vector3<f64> {
+        Vector3::zeros()
+    }
+
+    fn torque(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        body: &BodyAttitude,
+        _time: &f64,
+    ) -> Vector3<f64> {
+        // L = 3 mu / r^3 (r_hat x [I] r_hat) with r_hat in the body frame, mu / r^3 is in 1/s^2
+        // so the torque comes out in N.m with the inertia in kg.m^2
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        let r = r_vec.norm();
+        let r_hat = body.dcm * r_vec / r;
+        3. * central_body.mu / r.powi(3) * r_hat.cross(&(body.inertia * r_hat))
+    }
+}
+
+pub struct AeroAccel {
+    // drag on an exponential atmosphere that rotates with the central body, flat plates are
+    // used when the satellite has panels and attitude, otherwise a cannonball of the given area
+    pub cd: f64,
+    pub area: f64, // m^2
+}
+
+// base altitude (km), base density (kg/m^3) and scale height (km) (Vallado, table 8-4)
+const EXPONENTIAL_ATMOSPHERE: [(f64, f64, f64); 28] = [
+    (0., 1.225, 7.249),
+    (25., 3.899e-2, 6.349),
+    (30., 1.774e-2, 6.682),
+    (40., 3.972e-3, 7.554),
+    (50., 1.057e-3, 8.382),
+    (60., 3.206e-4, 7.714),
+    (70., 8.770e-5, 6.549),
+    (80., 1.905e-5, 5.799),
+    (90., 3.396e-6, 5.382),
+    (100., 5.297e-7, 5.877),
+    (110., 9.661e-8, 7.263),
+    (120., 2.438e-8, 9.473),
+    (130., 8.484e-9, 12.636),
+    (140., 3.845e-9, 16.149),
+    (150., 2.070e-9, 22.523),
+    (180., 5.464e-10, 29.740),
+    (200., 2.789e-10, 37.105),
+    (250., 7.248e-11, 45.546),
+    (300., 2.418e-11, 53.628),
+    (350., 9.518e-12, 53.298),
+    (400., 3.725e-12, 58.515),
+    (450., 1.585e-12, 60.828),
+    (500., 6.967e-13, 63.822),
+    (600., 1.454e-13, 71.835),
+    (700., 3.614e-14, 88.667),
+    (800., 1.170e-14, 124.64),
+    (900., 5.245e-15, 181.05),
+    (1000., 3.019e-15, 268.00),
+];
+
+impl AeroAccel {
+    pub fn new(cd: f64, area: f64) -> Self {
+        Self { cd, area }
+    }
+
+    pub fn density(altitude: f64) -> f64 {
+        // kg/m^3 at altitude (km)
+        if altitude < 0. {
+            return EXPONENTIAL_ATMOSPHERE[0].1;
+        }
+        let index = EXPONENTIAL_ATMOSPHERE.partition_point(|layer| layer.0 <= altitude) - 1;
+        let (base, rho0, scale) = EXPONENTIAL_ATMOSPHERE[index];
+        rho0 * (-(altitude - base) / scale).exp()
+    }
+
+    fn panel_forces(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        body: Option<&BodyAttitude>,
+    ) -> (Vector3<f64>, Vector3<f64>) {
+        // inertial force (N) and body frame torque (N.m)
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        let v_vec: Vector3<f64> = state.fixed_rows::<3>(3).into();
+        // altitude above the ellipsoid only depends on the distance from the polar axis and
+        // the height along it, so the inertial position serves
+        let rho = Self::density(ecef2geodetic(&r_vec, central_body).alt);
+        let v_rel = (v_vec - Vector3::new(0., 0., central_body.omega).cross(&r_vec)) * 1000.;
+        let v_mag = v_rel.norm();
+        let mut force = Vector3::zeros();
+        let mut torque = Vector3::zeros();
+        if v_mag == 0. {
+            return (force, torque);
+        }
+        let v_hat = v_rel / v_mag;
+        let dynamic_pressure = 0.5 * rho * v_mag.powi(2);
+
+        match body {
+            Some(body) if !body.panels.is_empty() => {
+                // only panels facing the flow see it, self shadowing is ignored
+                let v_hat_body = body.dcm * v_hat;
+                for panel in body.panels.iter() {
+                    let cos_incidence = panel.normal.dot(&v_hat_body);
+                    if cos_incidence <= 0. {
+                        continue;
+                    }
+                    let panel_force =
+                        -dynamic_pressure * self.cd * panel.area * cos_incidence * v_hat_body;
+                    force += body.dcm.transpose() * panel_force;
+                    torque += panel.center.cross(&panel_force);
+                }
+            }
+            _ => force = -dynamic_pressure * self.cd * self.area * v_hat,
+        }
+        (force, torque)
+    }
+}
+
+impl PerturbationCalculation for AeroAccel {
+    fn acceleration(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        mass: f64,
+        body: Option<&BodyAttitude>,
+        _time: &f64,
+    ) -> Vector3<f64> {
+        if mass <= 0. {
+            return Vector3::zeros();
+        }
+        let (force, _) = self.panel_forces(central_body, state, body);
+        force / mass / 1000.
+    }
+
+    fn torque(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        body: &BodyAttitude,
+        _time: &f64,
+    ) -> Vector3<f64> {
+        self.panel_forces(central_body, state, Some(body)).1
+    }
+}
+
+pub struct SolarRadiationAccel {
+    // direct solar radiation pressure with a cylindrical shadow, flat plates are used when the
+    // satellite has panels and attitude, otherwise a cannonball of the given area
+    pub area: f64, // m^2
+    pub cr: f64,   // radiation pressure coefficient for the cannonball
+}
+
+impl SolarRadiationAccel {
+    pub fn new(area: f64, cr: f64) -> Self {
+        Self { area, cr }
+    }
+
+    fn panel_forces(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        body: Option<&BodyAttitude>,
+        time: &f64,
+    ) -> (Vector3<f64>, Vector3<f64>) {
+        // inertial force (N) and body frame torque (N.m)
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        let sun_vec = sun_position(time2jd(time)) - r_vec;
+        let sun_dist = sun_vec.norm();
+        let sun_hat = sun_vec / sun_dist;
+        let mut force = Vector3::zeros();
+        let mut torque = Vector3::zeros();
+
+        // cylindrical shadow behind the central body
+        let along = r_vec.dot(&sun_hat);
+        if along < 0. && (r_vec - along * sun_hat).norm() < central_body.equatorial_radius {
+            return (force, torque);
+        }
+        let pressure = SOLAR_FLUX / SPEED_OF_LIGHT * (AU / sun_dist).powi(2);
+
+        match body {
+            Some(body) if !body.panels.is_empty() => {
+                let sun_hat_body = body.dcm * sun_hat;
+                for panel in body.panels.iter() {
+                    let cos_incidence = panel.normal.dot(&sun_hat_body);
+                    if cos_incidence <= 0. {
+                        continue;
+                    }
+                    // absorbed and diffuse light push along the incoming light, specular and
+                    // diffuse reflection push along the normal
+                    let panel_force = -pressure
+                        * panel.area
+                        * cos_incidence
+                        * ((1. - panel.specular) * sun_hat_body
+                            + 2. * (panel.specular * cos_incidence + panel.diffuse / 3.)
+                                * panel.normal);
+                    force += body.dcm.transpose() * panel_force;
+                    torque += panel.center.cross(&panel_force);
+                }
+            }
+            _ => force = -pressure * self.cr * self.area * sun_hat,
+        }
+        (force, torque)
+    }
+}
+
+impl PerturbationCalculation for SolarRadiationAccel {
+    fn acceleration(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        mass: f64,
+        body: Option<&BodyAttitude>,
+        time: &f64,
+    ) -> Vector3<f64> {
+        if mass <= 0. {
+            return Vector3::zeros();
+        }
+        let (force, _) = self.panel_forces(central_body, state, body, time);
+        force / mass / 1000.
+    }
+
+    fn torque(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        body: &BodyAttitude,
+        time: &f64,
+    ) -> Vector3<f64> {
+        self.panel_forces(central_body, state, Some(body), time).1
+    }
+}
+
+pub struct EarthRadiationAccel {
+    // radiation pressure from sunlight reflected by the central body (albedo) and from its
+    // thermal emission (infrared), cannonball model summed over the visible spherical cap
+    pub area: f64, // m^2
+    pub cr: f64,   // radiation pressure coefficient
+    pub albedo: bool,
+    pub infrared: bool,
+    pub rings: usize,   // number of rings the visible cap is split into
+    pub sectors: usize, // number of azimuth sectors per ring
+}
+
+const SOLAR_FLUX: f64 = 1361.; // W/m^2 at 1 AU
+const SPEED_OF_LIGHT: f64 = 299792458.; // m/s
+
+#[allow(dead_code)]
+impl EarthRadiationAccel {
+    pub fn new(area: f64, cr: f64) -> Self {
+        Self {
+            area,
+            cr,
+            albedo: true,
+            infrared: true,
+            rings: 10,
+            sectors: 24,
+        }
+    }
+}
+
+impl PerturbationCalculation for EarthRadiationAccel {
+    fn acceleration(
+        &self,
+        central_body: &CentralBody,
+        state: &Vector6<f64>,
+        mass: f64,
+        _body: Option<&BodyAttitude>,
+        time: &f64,
+    ) -> Vector3<f64> {
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        let r = r_vec.norm();
+        let radius = central_body.equatorial_radius;
+        let mut accel = Vector3::zeros();
+        if r <= radius || mass <= 0. || self.rings == 0 || self.sectors == 0 {
+            return accel;
+        }
+
+        let sun_hat = sun_position(time2jd(time)).normalize();
+        let eci2ecef = central_body.gen_eci2ecef(time);
+
+        // local basis around the sub-satellite point
+        let u_hat = r_vec / r;
+        let e1 = if u_hat[2].abs() < 0.9 {
+            u_hat.cross(&Vector3::z()).normalize()
+        } else {
+            u_hat.cross(&Vector3::x()).normalize()
+        };
+        let e2 = u_hat.cross(&e1);
+
+        let cap_angle = (radius / r).acos();
+        let dtheta = cap_angle / self.rings as f64;
+        let dphi = 2. * PI / self.sectors as f64;
+
+        // irradiance (W/m^2) at the satellite summed over the cap, weighted by direction
+        let mut flux = Vector3::zeros();
+        for i in 0..self.rings {
+            let theta = (i as f64 + 0.5) * dtheta;
+            let area_element = radius.powi(2) * theta.sin() * dtheta * dphi;
+            for j in 0..self.sectors {
+                let phi = (j as f64 + 0.5) * dphi;
+                let n_hat = theta.cos() * u_hat + theta.sin() * (phi.cos() * e1 + phi.sin() * e2);
+                let rho = r_vec - radius * n_hat;
+                let rho_mag = rho.norm();
+                let rho_hat = rho / rho_mag;
+                let cos_emit = n_hat.dot(&rho_hat);
+                if cos_emit <= 0. {
+                    continue;
+                }
+
+                let n_ecef = eci2ecef * n_hat;
+                let (reflectivity, emissivity) = central_body
+                    .radiation
+                    .lookup(n_ecef[2].asin(), n_ecef[1].atan2(n_ecef[0]));
+
+                // lambertian surface element seen from the satellite
+                let view = cos_emit * area_element / (PI * rho_mag.powi(2));
+                let mut exitance = 0.;
+                if self.albedo {
+                    let cos_sun = n_hat.dot(&sun_hat);
+                    if cos_sun > 0. {
+                        exitance += reflectivity * SOLAR_FLUX * cos_sun;
+                    }
+                }
+                if self.infrared {
+                    exitance += emissivity * SOLAR_FLUX / 4.;
+                }
+                flux += rho_hat * (exitance * view);
+            }
+        }
+
+        // pressure (N/m^2) times area over mass gives m/s^2, states are in km
+        accel += flux * (self.cr * self.area / mass / SPEED_OF_LIGHT / 1000.);
+        accel
+    }
+}
+
+pub struct EmpiricalAccel {
+    // constant and once-per-rev accelerations in the radial, transverse and normal directions,
+    // a = constant + sine * sin(u) + cosine * cos(u) with u the argument of latitude (km/s^2)
+    pub constant: Vector3<f64>,
+    pub sine: Vector3<f64>,
+    pub cosine: Vector3<f64>,
 }
 
-pub struct AeroAccel {}
+#[allow(dead_code)]
+impl EmpiricalAccel {
+    pub fn new(constant: Vector3<f64>, sine: Vector3<f64>, cosine: Vector3<f64>) -> Self {
+        Self {
+            constant,
+            sine,
+            cosine,
+        }
+    }
+
+    pub fn params(&self) -> SVector<f64, 9> {
+        // estimation parameter vector [constant; sine; cosine], each in RTN order
+        let mut params = SVector::<f64, 9>::zeros();
+        params.fixed_rows_mut::<3>(0).copy_from(&self.constant);
+        params.fixed_rows_mut::<3>(3).copy_from(&self.sine);
+        params.fixed_rows_mut::<3>(6).copy_from(&self.cosine);
+        params
+    }
+
+    pub fn set_params(&mut self, params: &SVector<f64, 9>) {
+        self.constant = params.fixed_rows::<3>(0).into();
+        self.sine = params.fixed_rows::<3>(3).into();
+        self.cosine = params.fixed_rows::<3>(6).into();
+    }
+
+    pub fn partials(&self, state: &Vector6<f64>) -> SMatrix<f64, 3, 9> {
+        // inertial acceleration partials with respect to the parameter vector
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        let v_vec: Vector3<f64> = state.fixed_rows::<3>(3).into();
+        let rtn = rtn2eci(&r_vec, &v_vec);
+        let u = arg_of_latitude(&r_vec, &v_vec);
+
+        let mut partials = SMatrix::<f64, 3, 9>::zeros();
+        partials.fixed_columns_mut::<3>(0).copy_from(&rtn);
+        partials
+            .fixed_columns_mut::<3>(3)
+            .copy_from(&(rtn * u.sin()));
+        partials
+            .fixed_columns_mut::<3>(6)
+            .copy_from(&(rtn * u.cos()));
+        partials
+    }
+}
+
+impl PerturbationCalculation for EmpiricalAccel {
+    fn acceleration(
+        &self,
+        _central_body: &CentralBody,
+        state: &Vector6<f64>,
+        _mass: f64,
+        _body: Option<&BodyAttitude>,
+        _time: &f64,
+    ) -> Vector3<f64> {
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        let v_vec: Vector3<f64> = state.fixed_rows::<3>(3).into();
+        let u = arg_of_latitude(&r_vec, &v_vec);
+        let accel_rtn = self.constant + self.sine * u.sin() + self.cosine * u.cos();
+        rtn2eci(&r_vec, &v_vec) * accel_rtn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn earth() -> CentralBody {
+        let mut central_body = CentralBody::new();
+        central_body.mu = 398600.4418;
+        central_body.equatorial_radius = 6378.137;
+        central_body.zonals = EARTH_ZONALS.to_vec();
+        central_body
+    }
+
+    fn zonal_potential(central_body: &CentralBody, r_vec: &Vector3<f64>, order: usize) -> f64 {
+        // U = mu / r (1 - sum J_n (R / r)^n P_n(z / r)) with the legendre polynomials written out
+        let r = r_vec.norm();
+        let u = r_vec[2] / r;
+        let legendre = [
+            1.,
+            u,
+            (3. * u.powi(2) - 1.) / 2.,
+            (5. * u.powi(3) - 3. * u) / 2.,
+            (35. * u.powi(4) - 30. * u.powi(2) + 3.) / 8.,
+            (63. * u.powi(5) - 70. * u.powi(3) + 15. * u) / 8.,
+            (231. * u.powi(6) - 315. * u.powi(4) + 105. * u.powi(2) - 5.) / 16.,
+        ];
+        let sum = (2..=order)
+            .map(|n| {
+                central_body.zonal(n)
+                    * (central_body.equatorial_radius / r).powi(n as i32)
+                    * legendre[n]
+            })
+            .sum::<f64>();
+        central_body.mu / r * (1. - sum)
+    }
+
+    #[test]
+    fn j_gravity_is_the_gradient_of_the_zonal_potential() {
+        let mut central_body = earth();
+        let state = vector![4500., -2300., 5100., 1., 2., 3.];
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        for order in 2..=MAX_ZONAL_ORDER {
+            central_body.max_order = order;
+            let state_dot = JGrav.calculate(&central_body, &[], 0, &state, &0.);
+            // central differences of the potential
+            let h = 1e-2;
+            let gradient = Vector3::from_fn(|i, _| {
+                let mut step = Vector3::zeros();
+                step[i] = h;
+                (zonal_potential(&central_body, &(r_vec + step), order)
+                    - zonal_potential(&central_body, &(r_vec - step), order))
+                    / (2. * h)
+            });
+            let accel: Vector3<f64> = state_dot.fixed_rows::<3>(3).into();
+            assert!(
+                (accel - gradient).norm() < 1e-12,
+                "J{}: {}",
+                order,
+                (accel - gradient).norm()
+            );
+            // each added zonal changes the acceleration
+            if order > 2 {
+                central_body.max_order = order - 1;
+                let lower = JGrav.calculate(&central_body, &[], 0, &state, &0.);
+                assert!((state_dot - lower).norm() > 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn j2_matches_the_closed_form() {
+        let mut central_body = earth();
+        central_body.max_order = 2;
+        let state = vector![7000., 0., 0., 0., 7.5, 0.];
+        let state_dot = JGrav.calculate(&central_body, &[], 0, &state, &0.);
+        // equatorial j2 adds -3/2 J2 mu R^2 / r^4 radially
+        let j2 = central_body.zonal(2);
+        let expected = -central_body.mu / 7000_f64.powi(2)
+            * (1. + 1.5 * j2 * (central_body.equatorial_radius / 7000.).powi(2));
+        assert!((state_dot[3] - expected).abs() < 1e-15);
+        assert!(state_dot[4].abs() < 1e-18 && state_dot[5].abs() < 1e-18);
+    }
+
+    #[test]
+    fn albedo_under_the_sun_matches_the_cap_integral() {
+        // uniform lambertian albedo seen from straight above the sub-solar point, the cap
+        // integral 2 a S R^2 int x (r x - R)(r - R x) / rho^4 dx over cos(theta) from R / r to 1
+        // gives 2 a S I(h) with h = R / r and
+        // I(h) = ((1 - h^2)^2 ln((1 - h) / (1 + h)) + 4 h^4 + 2 h^3 + 2 h) / (16 h)
+        let central_body = earth();
+        let time = 1e8;
+        let r = 7000.;
+        let r_vec = sun_position(time2jd(&time)).normalize() * r;
+        let state = Vector6::new(r_vec[0], r_vec[1], r_vec[2], 0., 0., 0.);
+        let mut radiation = EarthRadiationAccel::new(2., 1.5);
+        radiation.infrared = false;
+        radiation.rings = 1000;
+        radiation.sectors = 4;
+        let mass = 100.;
+        let accel = radiation.acceleration(&central_body, &state, mass, None, &time);
+
+        let h = central_body.equatorial_radius / r;
+        let cap = ((1. - h * h).powi(2) * ((1. - h) / (1. + h)).ln()
+            + 4. * h.powi(4)
+            + 2. * h.powi(3)
+            + 2. * h)
+            / (16. * h);
+        let flux = 2. * central_body.radiation.mean_reflectivity * SOLAR_FLUX * cap;
+        let expected = r_vec / r * flux * 1.5 * 2. / mass / SPEED_OF_LIGHT / 1000.;
+        assert!(
+            (accel - expected).norm() < 5e-6 * expected.norm(),
+            "{} {}",
+            accel.norm(),
+            expected.norm()
+        );
+        // the midpoint rule converges with the square of the rings, the default resolution is
+        // within a few percent
+        radiation.rings = 10;
+        radiation.sectors = 24;
+        let coarse = radiation.acceleration(&central_body, &state, mass, None, &time);
+        assert!((coarse - expected).norm() < 3e-2 * expected.norm());
+    }
+
+    #[test]
+    fn along_track_empirical_accel_raises_the_orbit_at_the_gauss_rate() {
+        // gauss' equation da/dt = 2 a^2 / h (e sin(f) R + p / r T) is 2 T / n on a circular
+        // orbit, so one revolution raises it by 4 pi T / n^2
+        let central_body = earth();
+        let mu = central_body.mu;
+        let along_track = 1e-9; // km/s^2
+        let empirical = EmpiricalAccel::new(
+            vector![0., along_track, 0.],
+            Vector3::zeros(),
+            Vector3::zeros(),
+        );
+        let a = 7000.;
+        let v = (mu / a).sqrt();
+        let mut state = Vector6::new(a, 0., 0., 0., v * 0.8_f64.cos(), v * 0.8_f64.sin());
+        let dxdt = |x: &Vector6<f64>| -> Vector6<f64> {
+            let r_vec: Vector3<f64> = x.fixed_rows::<3>(0).into();
+            let accel = -mu / r_vec.norm().powi(3) * r_vec
+                + empirical.acceleration(&central_body, x, 100., None, &0.);
+            Vector6::new(x[3], x[4], x[5], accel[0], accel[1], accel[2])
+        };
+        let n = (mu / a.powi(3)).sqrt();
+        let steps = 1000;
+        let dt = 2. * PI / n / steps as f64;
+        for _ in 0..steps {
+            let k1 = dxdt(&state);
+            let k2 = dxdt(&(state + k1 * dt / 2.));
+            let k3 = dxdt(&(state + k2 * dt / 2.));
+            let k4 = dxdt(&(state + k3 * dt));
+            state += (k1 + 2. * k2 + 2. * k3 + k4) * dt / 6.;
+        }
+        let raised = state2coes(&state, mu).sma - a;
+        let expected = 4. * PI * along_track / n.powi(2);
+        assert!(
+            (raised - expected).abs() < 1e-3 * expected,
+            "{} {}",
+            raised,
+            expected
+        );
+        // and stays in the orbit plane
+        let h_hat = Vector3::new(0., -0.8_f64.sin(), 0.8_f64.cos());
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        assert!(r_vec.dot(&h_hat).abs() < 1e-9);
+    }
+
+    fn panel(normal: Vector3<f64>, center: Vector3<f64>) -> Panel {
+        Panel {
+            area: 2.,
+            normal,
+            center,
+            specular: 0.3,
+            diffuse: 0.2,
+        }
+    }
+
+    #[test]
+    fn gravity_gradient_vanishes_along_principal_axes() {
+        let central_body = earth();
+        let inertia = Matrix3::from_diagonal(&vector![10., 12., 8.]);
+        let state = vector![0., 0., 7000., 0., 7.5, 0.];
+        // body z, then body x and body y pointing at nadir
+        for dcm in [Matrix3::identity(), r2(PI / 2.), r1(-PI / 2.)] {
+            let body = BodyAttitude {
+                dcm,
+                inertia: &inertia,
+                panels: &[],
+            };
+            let torque = GravityGradientTorque.torque(&central_body, &state, &body, &0.);
+            assert!(torque.norm() < 1e-18, "{}", torque);
+        }
+    }
+
+    #[test]
+    fn gravity_gradient_matches_the_offset_formula() {
+        // nadir tilted by theta about body x puts r_hat at (0, sin, cos) in the body frame and
+        // gives L_x = 3 mu / r^3 (I_z - I_y) sin(theta) cos(theta)
+        let central_body = earth();
+        let inertia = Matrix3::from_diagonal(&vector![10., 12., 8.]);
+        let state = vector![0., 0., 7000., 0., 7.5, 0.];
+        let n2 = central_body.mu / 7000_f64.powi(3);
+        for theta in [0.01, -0.05, 0.3] {
+            let body = BodyAttitude {
+                dcm: r1(theta),
+                inertia: &inertia,
+                panels: &[],
+            };
+            let torque = GravityGradientTorque.torque(&central_body, &state, &body, &0.);
+            let expected = 3. * n2 * (8. - 12.) * theta.sin() * theta.cos();
+            assert!((torque[0] - expected).abs() < 1e-12 * expected.abs());
+            assert!(torque[1].abs() < 1e-18 && torque[2].abs() < 1e-18);
+        }
+    }
+
+    #[test]
+    fn panel_drag_torque_is_the_lever_arm_times_the_force() {
+        // body x along the velocity, a front panel off center by 0.5 m along body y and a back
+        // panel that the flow does not reach
+        let central_body = earth();
+        let state = vector![6778.137, 0., 0., 0., 7.67, 0.];
+        let inertia = Matrix3::identity();
+        let panels = [
+            panel(Vector3::x(), vector![0., 0.5, 0.]),
+            panel(-Vector3::x(), vector![0., -2., 0.]),
+        ];
+        let body = BodyAttitude {
+            dcm: r3(PI / 2.),
+            inertia: &inertia,
+            panels: &panels,
+        };
+        let aero = AeroAccel::new(2.2, 4.);
+        let force = 0.5 * AeroAccel::density(400.) * 7670_f64.powi(2) * 2.2 * 2.;
+        let torque = aero.torque(&central_body, &state, &body, &0.);
+        assert!((torque - vector![0., 0., 0.5 * force]).norm() < 1e-12 * force);
+        let accel = aero.acceleration(&central_body, &state, 100., Some(&body), &0.);
+        assert!((accel - vector![0., -force / 100. / 1000., 0.]).norm() < 1e-12 * force);
+    }
+
+    #[test]
+    fn panel_srp_torque_is_the_lever_arm_times_the_force() {
+        // body x to the sun, the sunlit panel pushes back along its normal with
+        // P A (1 + specular + 2 diffuse / 3)
+        let central_body = earth();
+        let time = 1e8;
+        let sun = sun_position(time2jd(&time));
+        let r_vec = sun.normalize() * 7000.;
+        let state = Vector6::new(r_vec[0], r_vec[1], r_vec[2], 0., 0., 0.);
+        let x_hat = (sun - r_vec).normalize();
+        let y_hat = x_hat.cross(&Vector3::z()).normalize();
+        let z_hat = x_hat.cross(&y_hat);
+        let dcm = Matrix3::from_rows(&[x_hat.transpose(), y_hat.transpose(), z_hat.transpose()]);
+        let inertia = Matrix3::identity();
+        let panels = [panel(Vector3::x(), vector![0., 0., 0.4])];
+        let body = BodyAttitude {
+            dcm,
+            inertia: &inertia,
+            panels: &panels,
+        };
+        let srp = SolarRadiationAccel::new(1., 1.5);
+        let pressure = SOLAR_FLUX / SPEED_OF_LIGHT * (AU / (sun - r_vec).norm()).powi(2);
+        let force = pressure * 2. * (1. + 0.3 + 2. * 0.2 / 3.);
+        let torque = srp.torque(&central_body, &state, &body, &time);
+        // (0, 0, 0.4) x (-force, 0, 0)
+        assert!((torque - vector![0., -0.4 * force, 0.]).norm() < 1e-12 * force);
+        let accel = srp.acceleration(&central_body, &state, 100., Some(&body), &time);
+        assert!((accel + x_hat * force / 100. / 1000.).norm() < 1e-12 * force);
+    }
+}
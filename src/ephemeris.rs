@@ -0,0 +1,33 @@
+use nalgebra::*;
+use std::f64::consts::PI;
+
+pub const J2000: f64 = 2451545.0; // julian date of the J2000 epoch
+pub const AU: f64 = 149597870.7; // km
+
+#[allow(dead_code)]
+pub fn time2jd(time: &f64) -> f64 {
+    // simulation time is tt seconds past the J2000 epoch, so this is a tt julian date
+    J2000 + time / 86400.
+}
+
+#[allow(dead_code)]
+pub fn sun_position(jd: f64) -> Vector3<f64> {
+    // low precision solar position in the mean equator/equinox inertial frame (km), good to
+    // about 0.01 deg between 1950 and 2050 (Vallado, Fundamentals of Astrodynamics, algorithm 29)
+    let deg = PI / 180.;
+    let t = (jd - J2000) / 36525.;
+
+    let mean_lon = 280.460 + 36000.771 * t;
+    let mean_anom = (357.5291092 + 35999.05034 * t) * deg;
+    let ecl_lon =
+        (mean_lon + 1.914666471 * mean_anom.sin() + 0.019994643 * (2. * mean_anom).sin()) * deg;
+    let obliquity = (23.439291 - 0.0130042 * t) * deg;
+    let r_mag =
+        (1.000140612 - 0.016708617 * mean_anom.cos() - 0.000139589 * (2. * mean_anom).cos()) * AU;
+
+    vector![
+        r_mag * ecl_lon.cos(),
+        r_mag * obliquity.cos() * ecl_lon.sin(),
+        r_mag * obliquity.sin() * ecl_lon.sin()
+    ]
+}
@@ -0,0 +1,616 @@
+use crate::ephemeris::J2000;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::ops::{Add, Sub};
+
+pub const LEAP_SECOND_FILE: &str = "Leap_Second.dat"; // iers format, read when present
+pub const MJD_OFFSET: f64 = 2400000.5; // julian date of mjd 0
+const SECONDS_PER_DAY: f64 = 86400.;
+const TT_TAI: f64 = 32.184; // s
+const TAI_GPS: f64 = 19.; // s
+const J2000_MJD: i64 = 51544; // the J2000 epoch is mjd 51544.5 tt
+
+// tai - utc (s) from the first of the month (utc), every leap second since integer offsets
+// began in 1972, used until a leap second file is read
+const LEAP_SECONDS: [(i32, u32, f64); 28] = [
+    (1972, 1, 10.),
+    (1972, 7, 11.),
+    (1973, 1, 12.),
+    (1974, 1, 13.),
+    (1975, 1, 14.),
+    (1976, 1, 15.),
+    (1977, 1, 16.),
+    (1978, 1, 17.),
+    (1979, 1, 18.),
+    (1980, 1, 19.),
+    (1981, 7, 20.),
+    (1982, 7, 21.),
+    (1983, 7, 22.),
+    (1985, 7, 23.),
+    (1988, 1, 24.),
+    (1990, 1, 25.),
+    (1991, 1, 26.),
+    (1992, 7, 27.),
+    (1993, 7, 28.),
+    (1994, 7, 29.),
+    (1996, 1, 30.),
+    (1997, 7, 31.),
+    (1999, 1, 32.),
+    (2006, 1, 33.),
+    (2009, 1, 34.),
+    (2012, 7, 35.),
+    (2015, 7, 36.),
+    (2017, 1, 37.),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeScale {
+    Utc, // coordinated universal time, tai less the leap seconds
+    Tai, // international atomic time
+    Tt,  // terrestrial time, tai + 32.184 s
+    Tdb, // barycentric dynamical time, tt plus periodic terms below 2 ms
+    Gps, // gps time, tai - 19 s
+    Ut1, // earth rotation time, utc + (ut1 - utc) from earth orientation data
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LeapSecond {
+    // tai - utc = offset + (mjd - ref_mjd) rate from this utc date on, the rate is only nonzero
+    // for the drifting offsets before 1972
+    pub mjd: f64,
+    pub offset: f64,
+    pub ref_mjd: f64,
+    pub rate: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct TimeData {
+    pub leap_seconds: Vec<LeapSecond>, // sorted by date
+    pub dut1: Vec<(f64, f64)>,         // (utc mjd, ut1 - utc in s), empty when ut1 = utc
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Epoch {
+    // instant kept on the tai scale as a whole modified julian day and the seconds into it,
+    // which holds microseconds for centuries where a single julian date would not
+    day: i64,
+    seconds: f64,
+}
+
+impl TimeScale {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimeScale::Utc => "UTC",
+            TimeScale::Tai => "TAI",
+            TimeScale::Tt => "TT",
+            TimeScale::Tdb => "TDB",
+            TimeScale::Gps => "GPS",
+            TimeScale::Ut1 => "UT1",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        [
+            TimeScale::Utc,
+            TimeScale::Tai,
+            TimeScale::Tt,
+            TimeScale::Tdb,
+            TimeScale::Gps,
+            TimeScale::Ut1,
+        ]
+        .into_iter()
+        .find(|scale| scale.name().eq_ignore_ascii_case(text.trim()))
+    }
+}
+
+impl TimeData {
+    pub fn new() -> Self {
+        TimeData {
+            leap_seconds: LEAP_SECONDS
+                .iter()
+                .map(|(year, month, offset)| LeapSecond {
+                    mjd: calendar2mjd(*year, *month, 1) as f64,
+                    offset: *offset,
+                    ref_mjd: 0.,
+                    rate: 0.,
+                })
+                .collect(),
+            dut1: vec![],
+        }
+    }
+
+    pub fn read_leap_seconds(&mut self, file_path: &str) -> io::Result<()> {
+        // replaces the leap second table from either the iers Leap_Second.dat file
+        // ("mjd day month year tai-utc" rows, # comments) or the usno tai-utc.dat file
+        // (" 1972 JAN  1 =JD 2441317.5  TAI-UTC=  10.0       S + (MJD - 41317.) X 0.0      S")
+        let file = File::open(file_path)?;
+        let reader = BufReader::new(file);
+
+        let mut leap_seconds = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.contains("TAI-UTC") {
+                let after = |key: &str| -> Option<f64> {
+                    let start = line.find(key)? + key.len();
+                    line[start..]
+                        .split_whitespace()
+                        .next()?
+                        .trim_end_matches(['.', ')'])
+                        .parse::<f64>()
+                        .ok()
+                };
+                if let (Some(jd), Some(offset)) = (after("=JD"), after("TAI-UTC=")) {
+                    leap_seconds.push(LeapSecond {
+                        mjd: jd - MJD_OFFSET,
+                        offset,
+                        ref_mjd: after("MJD -").unwrap_or(0.),
+                        rate: after(" X").unwrap_or(0.),
+                    });
+                }
+            } else {
+                let parts: Vec<f64> = line
+                    .split_whitespace()
+                    .filter_map(|part| part.parse::<f64>().ok())
+                    .collect();
+                if parts.len() >= 5 {
+                    leap_seconds.push(LeapSecond {
+                        mjd: parts[0],
+                        offset: parts[4],
+                        ref_mjd: 0.,
+                        rate: 0.,
+                    });
+                }
+            }
+        }
+        if leap_seconds.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no leap seconds found in file",
+            ));
+        }
+        leap_seconds.sort_by(|a, b| a.mjd.partial_cmp(&b.mjd).unwrap());
+        self.leap_seconds = leap_seconds;
+        Ok(())
+    }
+
+    pub fn tai_utc(&self, mjd_utc: f64) -> f64 {
+        // tai - utc (s) at a utc date, the first entry's offset applies before the table
+        let leap = self
+            .leap_seconds
+            .iter()
+            .rev()
+            .find(|leap| leap.mjd <= mjd_utc)
+            .or(self.leap_seconds.first());
+        match leap {
+            Some(leap) => leap.offset + (mjd_utc - leap.ref_mjd) * leap.rate,
+            None => 0.,
+        }
+    }
+
+    pub fn ut1_utc(&self, mjd_utc: f64) -> f64 {
+        // ut1 - utc (s) interpolated in the dut1 table, held constant past its ends
+        let (first, last) = match (self.dut1.first(), self.dut1.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return 0.,
+        };
+        if mjd_utc <= first.0 {
+            return first.1;
+        }
+        if mjd_utc >= last.0 {
+            return last.1;
+        }
+        let k = self.dut1.partition_point(|entry| entry.0 <= mjd_utc);
+        let (t0, v0) = self.dut1[k - 1];
+        let (t1, mut v1) = self.dut1[k];
+        // ut1 - utc jumps by a whole second at leap seconds, interpolate across it on the
+        // earlier side
+        v1 -= (v1 - v0).round();
+        v0 + (v1 - v0) * (mjd_utc - t0) / (t1 - t0)
+    }
+}
+
+impl Epoch {
+    fn from_tai(day: i64, seconds: f64) -> Self {
+        // keeps the seconds within the day
+        let carry = (seconds / SECONDS_PER_DAY).floor();
+        Epoch {
+            day: day + carry as i64,
+            seconds: seconds - carry * SECONDS_PER_DAY,
+        }
+    }
+
+    pub fn from_scale(day: i64, seconds: f64, scale: TimeScale, time_data: &TimeData) -> Self {
+        // epoch from a modified julian day and seconds of that day on any scale, utc seconds
+        // may run to 86401 on a day that ends in a leap second
+        match scale {
+            TimeScale::Tai => Epoch::from_tai(day, seconds),
+            TimeScale::Tt => Epoch::from_tai(day, seconds - TT_TAI),
+            TimeScale::Gps => Epoch::from_tai(day, seconds + TAI_GPS),
+            TimeScale::Tdb => {
+                let jd_tt = day as f64 + seconds / SECONDS_PER_DAY + MJD_OFFSET;
+                Epoch::from_tai(day, seconds - TT_TAI - tdb_tt(jd_tt))
+            }
+            TimeScale::Utc => {
+                let mjd = day as f64 + seconds.min(SECONDS_PER_DAY) / SECONDS_PER_DAY;
+                // the offset of the day itself, a leap second belongs to the day it ends
+                let offset = time_data.tai_utc(mjd.min(day as f64 + 0.999_999_999));
+                Epoch::from_tai(day, seconds + offset)
+            }
+            TimeScale::Ut1 => {
+                let mjd = day as f64 + seconds / SECONDS_PER_DAY;
+                let utc = Epoch::from_tai(day, seconds - time_data.ut1_utc(mjd));
+                Epoch::from_scale(utc.day, utc.seconds, TimeScale::Utc, time_data)
+            }
+        }
+    }
+
+    pub fn from_jd(jd: f64, scale: TimeScale, time_data: &TimeData) -> Self {
+        // the whole and fractional parts are split before the offset to keep the precision
+        let day = (jd - MJD_OFFSET).floor();
+        let fraction = (jd - day) - MJD_OFFSET;
+        Epoch::from_scale(day as i64, fraction * SECONDS_PER_DAY, scale, time_data)
+    }
+
+    pub fn from_j2000_seconds(time: f64) -> Self {
+        // simulation time, tt seconds past the J2000 epoch (2000-01-01 12:00:00 tt)
+        Epoch::from_tai(J2000_MJD, SECONDS_PER_DAY / 2. + time - TT_TAI)
+    }
+
+    pub fn j2000_seconds(&self) -> f64 {
+        // simulation time of the epoch, tt seconds past J2000
+        (self.day - J2000_MJD) as f64 * SECONDS_PER_DAY + self.seconds + TT_TAI
+            - SECONDS_PER_DAY / 2.
+    }
+
+    pub fn elapsed_j2000(&self, scale: TimeScale, time_data: &TimeData) -> f64 {
+        // seconds of a scale since 2000-01-01 12:00:00 on that scale, a leap second in between
+        // is not counted in utc or ut1
+        let (day, seconds) = self.split(scale, time_data);
+        (day - J2000_MJD) as f64 * SECONDS_PER_DAY + seconds - SECONDS_PER_DAY / 2.
+    }
+
+    pub fn split(&self, scale: TimeScale, time_data: &TimeData) -> (i64, f64) {
+        // modified julian day and seconds into it on a scale, during a leap second the utc
+        // seconds run past 86400 on the day that it ends
+        match scale {
+            TimeScale::Tai => (self.day, self.seconds),
+            TimeScale::Tt => Epoch::from_tai(self.day, self.seconds + TT_TAI).tai_parts(),
+            TimeScale::Gps => Epoch::from_tai(self.day, self.seconds - TAI_GPS).tai_parts(),
+            TimeScale::Tdb => {
+                let (day, seconds) = self.split(TimeScale::Tt, time_data);
+                let jd_tt = day as f64 + seconds / SECONDS_PER_DAY + MJD_OFFSET;
+                Epoch::from_tai(day, seconds + tdb_tt(jd_tt)).tai_parts()
+            }
+            TimeScale::Utc => {
+                // utc runs behind tai so the utc day is the tai day or the one before, a day
+                // ending in a leap second is a second longer and holds 23:59:60
+                for day in [self.day, self.day - 1] {
+                    let elapsed = self.seconds + (self.day - day) as f64 * SECONDS_PER_DAY;
+                    let mut seconds = elapsed - time_data.tai_utc(day as f64);
+                    let mjd =
+                        day as f64 + seconds.clamp(0., SECONDS_PER_DAY - 1e-4) / SECONDS_PER_DAY;
+                    seconds = elapsed - time_data.tai_utc(mjd);
+                    let leap = time_data.tai_utc(day as f64 + 1.)
+                        - time_data.tai_utc(day as f64 + 1. - 1e-9);
+                    if seconds >= 0. && seconds < SECONDS_PER_DAY + leap.max(0.) {
+                        return (day, seconds);
+                    }
+                }
+                Epoch::from_tai(self.day, self.seconds - time_data.tai_utc(self.day as f64))
+                    .tai_parts()
+            }
+            TimeScale::Ut1 => {
+                let (day, seconds) = self.split(TimeScale::Utc, time_data);
+                let mjd = day as f64 + seconds / SECONDS_PER_DAY;
+                Epoch::from_tai(day, seconds + time_data.ut1_utc(mjd)).tai_parts()
+            }
+        }
+    }
+
+    fn tai_parts(&self) -> (i64, f64) {
+        (self.day, self.seconds)
+    }
+
+    pub fn mjd(&self, scale: TimeScale, time_data: &TimeData) -> f64 {
+        let (day, seconds) = self.split(scale, time_data);
+        day as f64 + seconds / SECONDS_PER_DAY
+    }
+
+    pub fn jd(&self, scale: TimeScale, time_data: &TimeData) -> f64 {
+        self.mjd(scale, time_data) + MJD_OFFSET
+    }
+
+    pub fn parse(text: &str, time_data: &TimeData) -> io::Result<Self> {
+        // iso 8601 calendar (2024-03-01T12:30:00.5) or ordinal (2024-061T12:30:00.5) dates,
+        // 'T' or a space between date and time, the time may be cut after hours or minutes.
+        // a trailing Z or +hh:mm/-hh:mm offset is utc, a trailing scale name (TT, TAI, TDB,
+        // GPS, UT1, UTC) picks the scale, utc otherwise
+        let invalid = |msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: '{}'", msg, text.trim()),
+            )
+        };
+        let mut text = text.trim();
+        let mut scale = TimeScale::Utc;
+        if let Some((rest, name)) = text.rsplit_once(' ') {
+            if let Some(named) = TimeScale::parse(name) {
+                scale = named;
+                text = rest.trim();
+            }
+        }
+        let (date, time) = match text.split_once(['T', 't', ' ']) {
+            Some((date, time)) => (date, time.trim()),
+            None => (text, ""),
+        };
+
+        // zone designator, offsets are only meaningful for utc
+        let mut zone_seconds = 0.;
+        let time = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+            time
+        } else if let Some(index) = time.rfind(['+', '-']) {
+            let (clock, zone) = time.split_at(index);
+            let sign = if zone.starts_with('-') { -1. } else { 1. };
+            let zone: Vec<f64> = zone[1..]
+                .split(':')
+                .map(|part| part.parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid("bad utc offset"))?;
+            let (hours, minutes) = match zone.as_slice() {
+                [hhmm] if zone_len(&time[index + 1..]) == 4 => ((hhmm / 100.).floor(), hhmm % 100.),
+                [hours] => (*hours, 0.),
+                [hours, minutes] => (*hours, *minutes),
+                _ => return Err(invalid("bad utc offset")),
+            };
+            if hours >= 24. || minutes >= 60. {
+                return Err(invalid("bad utc offset"));
+            }
+            zone_seconds = sign * (hours * 3600. + minutes * 60.);
+            clock
+        } else {
+            time
+        };
+
+        let date_parts: Vec<&str> = date.split('-').collect();
+        let number = |part: &str| part.parse::<u32>().map_err(|_| invalid("bad date"));
+        let year = date_parts
+            .first()
+            .and_then(|year| year.parse::<i32>().ok())
+            .ok_or_else(|| invalid("bad year"))?;
+        let day = match date_parts.as_slice() {
+            [_, ordinal] if ordinal.len() == 3 => {
+                let ordinal = number(ordinal)?;
+                let year_len = calendar2mjd(year + 1, 1, 1) - calendar2mjd(year, 1, 1);
+                if ordinal == 0 || ordinal as i64 > year_len {
+                    return Err(invalid("bad day of year"));
+                }
+                calendar2mjd(year, 1, 1) + ordinal as i64 - 1
+            }
+            [_, month, day] => {
+                let (month, day) = (number(month)?, number(day)?);
+                if !(1..=12).contains(&month) || day == 0 || day > month_len(year, month) {
+                    return Err(invalid("bad date"));
+                }
+                calendar2mjd(year, month, day)
+            }
+            _ => return Err(invalid("bad date")),
+        };
+
+        let clock: Vec<f64> = if time.is_empty() {
+            vec![]
+        } else {
+            time.split(':')
+                .map(|part| part.parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid("bad time"))?
+        };
+        if clock.len() > 3 || clock.iter().any(|value| *value < 0.) {
+            return Err(invalid("bad time"));
+        }
+        // 23:59:60 only on a utc day that ends in a leap second
+        let leap = scale == TimeScale::Utc
+            && clock.len() == 3
+            && clock[0] == 23.
+            && clock[1] == 59.
+            && time_data.tai_utc(day as f64 + 1.) > time_data.tai_utc(day as f64);
+        let limits = [24., 60., if leap { 61. } else { 60. }];
+        if clock
+            .iter()
+            .zip(limits)
+            .any(|(value, limit)| *value >= limit)
+        {
+            return Err(invalid("bad time"));
+        }
+        let seconds = clock
+            .iter()
+            .zip([3600., 60., 1.])
+            .map(|(value, unit)| value * unit)
+            .sum::<f64>();
+        if zone_seconds != 0. && scale != TimeScale::Utc {
+            return Err(invalid("utc offsets need a utc time"));
+        }
+        Ok(Epoch::from_scale(
+            day,
+            seconds - zone_seconds,
+            scale,
+            time_data,
+        ))
+    }
+
+    pub fn to_iso(self, scale: TimeScale, time_data: &TimeData) -> String {
+        // iso 8601 to the millisecond, utc gets a Z, other scales their name
+        let (mut day, seconds) = self.split(scale, time_data);
+        let mut millis = (seconds * 1000.).round() as i64;
+        let day_millis = if scale == TimeScale::Utc {
+            let leap = time_data.tai_utc(day as f64 + 1.) - time_data.tai_utc(day as f64);
+            86_400_000 + (leap.max(0.) * 1000.).round() as i64
+        } else {
+            86_400_000
+        };
+        if millis >= day_millis {
+            millis -= day_millis;
+            day += 1;
+        }
+        let (year, month, date) = mjd2calendar(day);
+        // a leap second shows as 23:59:60
+        let (hour, minute, second) = if millis >= 86_400_000 {
+            (23, 59, 60_000 + millis - 86_400_000)
+        } else {
+            (millis / 3_600_000, millis / 60_000 % 60, millis % 60_000)
+        };
+        let suffix = match scale {
+            TimeScale::Utc => String::from("Z"),
+            _ => format!(" {}", scale.name()),
+        };
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}{}",
+            year,
+            month,
+            date,
+            hour,
+            minute,
+            second / 1000,
+            second % 1000,
+            suffix
+        )
+    }
+}
+
+impl Add<f64> for Epoch {
+    // elapsed si seconds
+    type Output = Epoch;
+    fn add(self, seconds: f64) -> Epoch {
+        Epoch::from_tai(self.day, self.seconds + seconds)
+    }
+}
+
+impl Sub for Epoch {
+    // elapsed si seconds between two epochs
+    type Output = f64;
+    fn sub(self, other: Epoch) -> f64 {
+        (self.day - other.day) as f64 * SECONDS_PER_DAY + (self.seconds - other.seconds)
+    }
+}
+
+fn month_len(year: i32, month: u32) -> u32 {
+    // days in a month of the proleptic gregorian calendar
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    (calendar2mjd(next_year, next_month, 1) - calendar2mjd(year, month, 1)) as u32
+}
+
+fn zone_len(zone: &str) -> usize {
+    zone.chars().filter(|c| c.is_ascii_digit()).count()
+}
+
+pub fn tdb_tt(jd_tt: f64) -> f64 {
+    // tdb - tt (s), the two largest periodic terms (good to about 30 microseconds)
+    let g = (357.53 + 0.98560028 * (jd_tt - J2000)) * PI / 180.;
+    0.001657 * g.sin() + 0.000014 * (2. * g).sin()
+}
+
+pub fn calendar2mjd(year: i32, month: u32, day: u32) -> i64 {
+    // modified julian day of a proleptic gregorian date
+    let year = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    // days from 1970-01-01 (mjd 40587)
+    era * 146097 + day_of_era - 719468 + 40587
+}
+
+pub fn mjd2calendar(mjd: i64) -> (i32, u32, u32) {
+    // proleptic gregorian (year, month, day) of a modified julian day
+    let days = mjd - 40587 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as i32, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_impossible_dates_and_times() {
+        let time_data = TimeData::new();
+        for text in [
+            "2023-02-31T00:00:00Z",
+            "2023-02-29T00:00:00Z",
+            "2023-04-31",
+            "2023-13-01",
+            "2023-366T00:00:00Z",
+            "2024-03-01T12:75:99Z",
+            "2024-03-01T24:00:00Z",
+            "2024-03-01T12:00:60Z",
+            "2015-12-31T23:59:60Z",
+            "2016-12-31T23:59:60 TT",
+            "2024-03-01T12:00:00+25:00",
+        ] {
+            assert!(Epoch::parse(text, &time_data).is_err(), "{}", text);
+        }
+        for text in [
+            "2024-02-29T12:00:00Z",
+            "2024-366",
+            "1900-02-28",
+            "2000-02-29",
+        ] {
+            assert!(Epoch::parse(text, &time_data).is_ok(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn parse_accepts_leap_seconds() {
+        let time_data = TimeData::new();
+        let leap = Epoch::parse("2016-12-31T23:59:60Z", &time_data).unwrap();
+        let before = Epoch::parse("2016-12-31T23:59:59Z", &time_data).unwrap();
+        let after = Epoch::parse("2017-01-01T00:00:00Z", &time_data).unwrap();
+        assert!((leap - before - 1.).abs() < 1e-9);
+        assert!((after - leap - 1.).abs() < 1e-9);
+        assert_eq!(
+            leap.to_iso(TimeScale::Utc, &time_data),
+            "2016-12-31T23:59:60.000Z"
+        );
+    }
+
+    #[test]
+    fn parse_reads_every_form_of_the_same_instant() {
+        let time_data = TimeData::new();
+        // J2000 is 2000-01-01 11:58:55.816 utc
+        let j2000 = Epoch::from_j2000_seconds(0.);
+        for text in [
+            "2000-01-01T12:00:00 TT",
+            "2000-001T12:00:00 TT",
+            "2000-01-01 11:59:27.816 TAI",
+            "2000-01-01T11:58:55.816Z",
+            "2000-01-01T13:28:55.816+01:30",
+            "2000-01-01T11:58:55.816",
+        ] {
+            let epoch = Epoch::parse(text, &time_data).unwrap();
+            assert!((epoch - j2000).abs() < 1e-6, "{}: {}", text, epoch - j2000);
+        }
+        let jd = Epoch::from_jd(2451545., TimeScale::Tt, &time_data);
+        assert!((jd - j2000).abs() < 1e-6);
+    }
+}
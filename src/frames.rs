@@ -0,0 +1,811 @@
+use crate::attitude::{r1, r2, r3};
+use crate::ephemeris::*;
+use nalgebra::*;
+use std::cell::Cell;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+const ARCSEC: f64 = PI / (180. * 3600.);
+const MICROARCSEC: f64 = ARCSEC * 1e-6;
+pub const FINALS_FILE: &str = "finals.all"; // iers eop with iau 1980 nutation offsets
+pub const FINALS_2000A_FILE: &str = "finals2000A.all"; // iers eop with iau 2000a pole offsets
+
+// multipliers of l, l', F, D, Omega and the coefficients of a nutation series term
+type NutationTerm = ([f64; 5], [f64; 4]);
+
+// iau 1980 nutation series (106 terms, Seidelmann 1982): multipliers of l, l', F, D, Omega then
+// dpsi = (a + b T) sin(arg), deps = (c + d T) cos(arg) in 0.0001 arcsec
+const NUTATION_1980_TERMS: [NutationTerm; 106] = [
+    ([0., 0., 0., 0., 1.], [-171996., -174.2, 92025., 8.9]),
+    ([0., 0., 0., 0., 2.], [2062., 0.2, -895., 0.5]),
+    ([-2., 0., 2., 0., 1.], [46., 0., -24., 0.]),
+    ([2., 0., -2., 0., 0.], [11., 0., 0., 0.]),
+    ([-2., 0., 2., 0., 2.], [-3., 0., 1., 0.]),
+    ([1., -1., 0., -1., 0.], [-3., 0., 0., 0.]),
+    ([0., -2., 2., -2., 1.], [-2., 0., 1., 0.]),
+    ([2., 0., -2., 0., 1.], [1., 0., 0., 0.]),
+    ([0., 0., 2., -2., 2.], [-13187., -1.6, 5736., -3.1]),
+    ([0., 1., 0., 0., 0.], [1426., -3.4, 54., -0.1]),
+    ([0., 1., 2., -2., 2.], [-517., 1.2, 224., -0.6]),
+    ([0., -1., 2., -2., 2.], [217., -0.5, -95., 0.3]),
+    ([0., 0., 2., -2., 1.], [129., 0.1, -70., 0.]),
+    ([2., 0., 0., -2., 0.], [48., 0., 1., 0.]),
+    ([0., 0., 2., -2., 0.], [-22., 0., 0., 0.]),
+    ([0., 2., 0., 0., 0.], [17., -0.1, 0., 0.]),
+    ([0., 1., 0., 0., 1.], [-15., 0., 9., 0.]),
+    ([0., 2., 2., -2., 2.], [-16., 0.1, 7., 0.]),
+    ([0., -1., 0., 0., 1.], [-12., 0., 6., 0.]),
+    ([-2., 0., 0., 2., 1.], [-6., 0., 3., 0.]),
+    ([0., -1., 2., -2., 1.], [-5., 0., 3., 0.]),
+    ([2., 0., 0., -2., 1.], [4., 0., -2., 0.]),
+    ([0., 1., 2., -2., 1.], [4., 0., -2., 0.]),
+    ([1., 0., 0., -1., 0.], [-4., 0., 0., 0.]),
+    ([2., 1., 0., -2., 0.], [1., 0., 0., 0.]),
+    ([0., 0., -2., 2., 1.], [1., 0., 0., 0.]),
+    ([0., 1., -2., 2., 0.], [-1., 0., 0., 0.]),
+    ([0., 1., 0., 0., 2.], [1., 0., 0., 0.]),
+    ([-1., 0., 0., 1., 1.], [1., 0., 0., 0.]),
+    ([0., 1., 2., -2., 0.], [-1., 0., 0., 0.]),
+    ([0., 0., 2., 0., 2.], [-2274., -0.2, 977., -0.5]),
+    ([1., 0., 0., 0., 0.], [712., 0.1, -7., 0.]),
+    ([0., 0., 2., 0., 1.], [-386., -0.4, 200., 0.]),
+    ([1., 0., 2., 0., 2.], [-301., 0., 129., -0.1]),
+    ([1., 0., 0., -2., 0.], [-158., 0., -1., 0.]),
+    ([-1., 0., 2., 0., 2.], [123., 0., -53., 0.]),
+    ([0., 0., 0., 2., 0.], [63., 0., -2., 0.]),
+    ([1., 0., 0., 0., 1.], [63., 0.1, -33., 0.]),
+    ([-1., 0., 0., 0., 1.], [-58., -0.1, 32., 0.]),
+    ([-1., 0., 2., 2., 2.], [-59., 0., 26., 0.]),
+    ([1., 0., 2., 0., 1.], [-51., 0., 27., 0.]),
+    ([0., 0., 2., 2., 2.], [-38., 0., 16., 0.]),
+    ([2., 0., 0., 0., 0.], [29., 0., -1., 0.]),
+    ([1., 0., 2., -2., 2.], [29., 0., -12., 0.]),
+    ([2., 0., 2., 0., 2.], [-31., 0., 13., 0.]),
+    ([0., 0., 2., 0., 0.], [26., 0., -1., 0.]),
+    ([-1., 0., 2., 0., 1.], [21., 0., -10., 0.]),
+    ([-1., 0., 0., 2., 1.], [16., 0., -8., 0.]),
+    ([1., 0., 0., -2., 1.], [-13., 0., 7., 0.]),
+    ([-1., 0., 2., 2., 1.], [-10., 0., 5., 0.]),
+    ([1., 1., 0., -2., 0.], [-7., 0., 0., 0.]),
+    ([0., 1., 2., 0., 2.], [7., 0., -3., 0.]),
+    ([0., -1., 2., 0., 2.], [-7., 0., 3., 0.]),
+    ([1., 0., 2., 2., 2.], [-8., 0., 3., 0.]),
+    ([1., 0., 0., 2., 0.], [6., 0., 0., 0.]),
+    ([2., 0., 2., -2., 2.], [6., 0., -3., 0.]),
+    ([0., 0., 0., 2., 1.], [-6., 0., 3., 0.]),
+    ([0., 0., 2., 2., 1.], [-7., 0., 3., 0.]),
+    ([1., 0., 2., -2., 1.], [6., 0., -3., 0.]),
+    ([0., 0., 0., -2., 1.], [-5., 0., 3., 0.]),
+    ([1., -1., 0., 0., 0.], [5., 0., 0., 0.]),
+    ([2., 0., 2., 0., 1.], [-5., 0., 3., 0.]),
+    ([0., 1., 0., -2., 0.], [-4., 0., 0., 0.]),
+    ([1., 0., -2., 0., 0.], [4., 0., 0., 0.]),
+    ([0., 0., 0., 1., 0.], [-4., 0., 0., 0.]),
+    ([1., 1., 0., 0., 0.], [-3., 0., 0., 0.]),
+    ([1., 0., 2., 0., 0.], [3., 0., 0., 0.]),
+    ([1., -1., 2., 0., 2.], [-3., 0., 1., 0.]),
+    ([-1., -1., 2., 2., 2.], [-3., 0., 1., 0.]),
+    ([-2., 0., 0., 0., 1.], [-2., 0., 1., 0.]),
+    ([3., 0., 2., 0., 2.], [-3., 0., 1., 0.]),
+    ([0., -1., 2., 2., 2.], [-3., 0., 1., 0.]),
+    ([1., 1., 2., 0., 2.], [2., 0., -1., 0.]),
+    ([-1., 0., 2., -2., 1.], [-2., 0., 1., 0.]),
+    ([2., 0., 0., 0., 1.], [2., 0., -1., 0.]),
+    ([1., 0., 0., 0., 2.], [-2., 0., 1., 0.]),
+    ([3., 0., 0., 0., 0.], [2., 0., 0., 0.]),
+    ([0., 0., 2., 1., 2.], [2., 0., -1., 0.]),
+    ([-1., 0., 0., 0., 2.], [1., 0., -1., 0.]),
+    ([1., 0., 0., -4., 0.], [-1., 0., 0., 0.]),
+    ([-2., 0., 2., 2., 2.], [1., 0., -1., 0.]),
+    ([-1., 0., 2., 4., 2.], [-2., 0., 1., 0.]),
+    ([2., 0., 0., -4., 0.], [-1., 0., 0., 0.]),
+    ([1., 1., 2., -2., 2.], [1., 0., -1., 0.]),
+    ([1., 0., 2., 2., 1.], [-1., 0., 1., 0.]),
+    ([-2., 0., 2., 4., 2.], [-1., 0., 1., 0.]),
+    ([-1., 0., 4., 0., 2.], [1., 0., 0., 0.]),
+    ([1., -1., 0., -2., 0.], [1., 0., 0., 0.]),
+    ([2., 0., 2., -2., 1.], [1., 0., -1., 0.]),
+    ([2., 0., 2., 2., 2.], [-1., 0., 0., 0.]),
+    ([1., 0., 0., 2., 1.], [-1., 0., 0., 0.]),
+    ([0., 0., 4., -2., 2.], [1., 0., 0., 0.]),
+    ([3., 0., 2., -2., 2.], [1., 0., 0., 0.]),
+    ([1., 0., 2., -2., 0.], [-1., 0., 0., 0.]),
+    ([0., 1., 2., 0., 1.], [1., 0., 0., 0.]),
+    ([-1., -1., 0., 2., 1.], [1., 0., 0., 0.]),
+    ([0., 0., -2., 0., 1.], [-1., 0., 0., 0.]),
+    ([0., 0., 2., -1., 2.], [-1., 0., 0., 0.]),
+    ([0., 1., 0., 2., 0.], [-1., 0., 0., 0.]),
+    ([1., 0., -2., -2., 0.], [-1., 0., 0., 0.]),
+    ([0., -1., 2., 0., 1.], [-1., 0., 0., 0.]),
+    ([1., 1., 0., -2., 1.], [-1., 0., 0., 0.]),
+    ([1., 0., -2., 2., 0.], [-1., 0., 0., 0.]),
+    ([2., 0., 0., 2., 0.], [1., 0., 0., 0.]),
+    ([0., 0., 2., 4., 2.], [-1., 0., 0., 0.]),
+    ([0., 1., 0., 1., 0.], [1., 0., 0., 0.]),
+];
+
+// iau 2000b lunisolar nutation series (McCarthy and Luzum, 2003): multipliers of l, l', F, D,
+// Omega then dpsi = (a + b T) sin(arg) + c cos(arg), deps = (d + e T) cos(arg) + f sin(arg) in
+// 0.1 microarcseconds
+const NUTATION_2000B_TERMS: [([f64; 5], [f64; 6]); 77] = [
+    (
+        [0., 0., 0., 0., 1.],
+        [-172064161., -174666., 33386., 92052331., 9086., 15377.],
+    ),
+    (
+        [0., 0., 2., -2., 2.],
+        [-13170906., -1675., -13696., 5730336., -3015., -4587.],
+    ),
+    (
+        [0., 0., 2., 0., 2.],
+        [-2276413., -234., 2796., 978459., -485., 1374.],
+    ),
+    (
+        [0., 0., 0., 0., 2.],
+        [2074554., 207., -698., -897492., 470., -291.],
+    ),
+    (
+        [0., 1., 0., 0., 0.],
+        [1475877., -3633., 11817., 73871., -184., -1924.],
+    ),
+    (
+        [0., 1., 2., -2., 2.],
+        [-516821., 1226., -524., 224386., -677., -174.],
+    ),
+    (
+        [1., 0., 0., 0., 0.],
+        [711159., 73., -872., -6750., 0., 358.],
+    ),
+    (
+        [0., 0., 2., 0., 1.],
+        [-387298., -367., 380., 200728., 18., 318.],
+    ),
+    (
+        [1., 0., 2., 0., 2.],
+        [-301461., -36., 816., 129025., -63., 367.],
+    ),
+    (
+        [0., -1., 2., -2., 2.],
+        [215829., -494., 111., -95929., 299., 132.],
+    ),
+    (
+        [0., 0., 2., -2., 1.],
+        [128227., 137., 181., -68982., -9., 39.],
+    ),
+    (
+        [-1., 0., 2., 0., 2.],
+        [123457., 11., 19., -53311., 32., -4.],
+    ),
+    (
+        [-1., 0., 0., 2., 0.],
+        [156994., 10., -168., -1235., 0., 82.],
+    ),
+    ([1., 0., 0., 0., 1.], [63110., 63., 27., -33228., 0., -9.]),
+    (
+        [-1., 0., 0., 0., 1.],
+        [-57976., -63., -189., 31429., 0., -75.],
+    ),
+    (
+        [-1., 0., 2., 2., 2.],
+        [-59641., -11., 149., 25543., -11., 66.],
+    ),
+    ([1., 0., 2., 0., 1.], [-51613., -42., 129., 26366., 0., 78.]),
+    (
+        [-2., 0., 2., 0., 1.],
+        [45893., 50., 31., -24236., -10., 20.],
+    ),
+    ([0., 0., 0., 2., 0.], [63384., 11., -150., -1220., 0., 29.]),
+    (
+        [0., 0., 2., 2., 2.],
+        [-38571., -1., 158., 16452., -11., 68.],
+    ),
+    ([0., -2., 2., -2., 2.], [32481., 0., 0., -13870., 0., 0.]),
+    ([-2., 0., 0., 2., 0.], [-47722., 0., -18., 477., 0., -25.]),
+    (
+        [2., 0., 2., 0., 2.],
+        [-31046., -1., 131., 13238., -11., 59.],
+    ),
+    ([1., 0., 2., -2., 2.], [28593., 0., -1., -12338., 10., -3.]),
+    ([-1., 0., 2., 0., 1.], [20441., 21., 10., -10758., 0., -3.]),
+    ([2., 0., 0., 0., 0.], [29243., 0., -74., -609., 0., 13.]),
+    ([0., 0., 2., 0., 0.], [25887., 0., -66., -550., 0., 11.]),
+    ([0., 1., 0., 0., 1.], [-14053., -25., 79., 8551., -2., -45.]),
+    ([-1., 0., 0., 2., 1.], [15164., 10., 11., -8001., 0., -1.]),
+    (
+        [0., 2., 2., -2., 2.],
+        [-15794., 72., -16., 6850., -42., -5.],
+    ),
+    ([0., 0., -2., 2., 0.], [21783., 0., 13., -167., 0., 13.]),
+    (
+        [1., 0., 0., -2., 1.],
+        [-12873., -10., -37., 6953., 0., -14.],
+    ),
+    ([0., -1., 0., 0., 1.], [-12654., 11., 63., 6415., 0., 26.]),
+    ([-1., 0., 2., 2., 1.], [-10204., 0., 25., 5222., 0., 15.]),
+    ([0., 2., 0., 0., 0.], [16707., -85., -10., 168., -1., 10.]),
+    ([1., 0., 2., 2., 2.], [-7691., 0., 44., 3268., 0., 19.]),
+    ([-2., 0., 2., 0., 0.], [-11024., 0., -14., 104., 0., 2.]),
+    ([0., 1., 2., 0., 2.], [7566., -21., -11., -3250., 0., -5.]),
+    ([0., 0., 2., 2., 1.], [-6637., -11., 25., 3353., 0., 14.]),
+    ([0., -1., 2., 0., 2.], [-7141., 21., 8., 3070., 0., 4.]),
+    ([0., 0., 0., 2., 1.], [-6302., -11., 2., 3272., 0., 4.]),
+    ([1., 0., 2., -2., 1.], [5800., 10., 2., -3045., 0., -1.]),
+    ([2., 0., 2., -2., 2.], [6443., 0., -7., -2768., 0., -4.]),
+    ([-2., 0., 0., 2., 1.], [-5774., -11., -15., 3041., 0., -5.]),
+    ([2., 0., 2., 0., 1.], [-5350., 0., 21., 2695., 0., 12.]),
+    ([0., -1., 2., -2., 1.], [-4752., -11., -3., 2719., 0., -3.]),
+    ([0., 0., 0., -2., 1.], [-4940., -11., -21., 2720., 0., -9.]),
+    ([-1., -1., 0., 2., 0.], [7350., 0., -8., -51., 0., 4.]),
+    ([2., 0., 0., -2., 1.], [4065., 0., 6., -2206., 0., 1.]),
+    ([1., 0., 0., 2., 0.], [6579., 0., -24., -199., 0., 2.]),
+    ([0., 1., 2., -2., 1.], [3579., 0., 5., -1900., 0., 1.]),
+    ([1., -1., 0., 0., 0.], [4725., 0., -6., -41., 0., 3.]),
+    ([-2., 0., 2., 0., 2.], [-3075., 0., -2., 1313., 0., -1.]),
+    ([3., 0., 2., 0., 2.], [-2904., 0., 15., 1233., 0., 7.]),
+    ([0., -1., 0., 2., 0.], [4348., 0., -10., -81., 0., 2.]),
+    ([1., -1., 2., 0., 2.], [-2878., 0., 8., 1232., 0., 4.]),
+    ([0., 0., 0., 1., 0.], [-4230., 0., 5., -20., 0., -2.]),
+    ([-1., -1., 2., 2., 2.], [-2819., 0., 7., 1207., 0., 3.]),
+    ([-1., 0., 2., 0., 0.], [-4056., 0., 5., 40., 0., -2.]),
+    ([0., -1., 2., 2., 2.], [-2647., 0., 11., 1129., 0., 5.]),
+    ([-2., 0., 0., 0., 1.], [-2294., 0., -10., 1266., 0., -4.]),
+    ([1., 1., 2., 0., 2.], [2481., 0., -7., -1062., 0., -3.]),
+    ([2., 0., 0., 0., 1.], [2179., 0., -2., -1129., 0., -2.]),
+    ([-1., 1., 0., 1., 0.], [3276., 0., 1., -9., 0., 0.]),
+    ([1., 1., 0., 0., 0.], [-3389., 0., 5., 35., 0., -2.]),
+    ([1., 0., 2., 0., 0.], [3339., 0., -13., -107., 0., 1.]),
+    ([-1., 0., 2., -2., 1.], [-1987., 0., -6., 1073., 0., -2.]),
+    ([1., 0., 0., 0., 2.], [-1981., 0., 0., 854., 0., 0.]),
+    ([-1., 0., 0., 1., 0.], [4026., 0., -353., -553., 0., -139.]),
+    ([0., 0., 2., 1., 2.], [1660., 0., -5., -710., 0., -2.]),
+    ([-1., 0., 2., 4., 2.], [-1521., 0., 9., 647., 0., 4.]),
+    ([-1., 1., 0., 1., 1.], [1314., 0., 0., -700., 0., 0.]),
+    ([0., -2., 2., -2., 1.], [-1283., 0., 0., 672., 0., 0.]),
+    ([1., 0., 2., 2., 1.], [-1331., 0., 8., 663., 0., 4.]),
+    ([-2., 0., 2., 2., 2.], [1383., 0., -2., -594., 0., -2.]),
+    ([-1., 0., 0., 0., 2.], [1405., 0., 4., -610., 0., 2.]),
+    ([1., 1., 2., -2., 2.], [1290., 0., 0., -556., 0., 0.]),
+];
+
+// largest periodic terms of the iau 2006 cio locator series s + XY/2: multipliers of l, l', F,
+// D, Omega then the power of T and the sine and cosine coefficients in microarcseconds
+const CIO_LOCATOR_TERMS: [([f64; 5], [f64; 3]); 19] = [
+    ([0., 0., 0., 0., 1.], [0., -2640.73, 0.39]),
+    ([0., 0., 0., 0., 2.], [0., -63.53, 0.02]),
+    ([0., 0., 2., -2., 3.], [0., -11.75, -0.01]),
+    ([0., 0., 2., -2., 1.], [0., -11.21, -0.01]),
+    ([0., 0., 2., -2., 2.], [0., 4.57, 0.]),
+    ([0., 0., 2., 0., 3.], [0., -2.02, 0.]),
+    ([0., 0., 2., 0., 1.], [0., -1.98, 0.]),
+    ([0., 0., 0., 0., 3.], [0., 1.72, 0.]),
+    ([0., 1., 0., 0., 1.], [0., 1.41, 0.01]),
+    ([0., 1., 0., 0., -1.], [0., 1.26, 0.01]),
+    ([1., 0., 0., 0., -1.], [0., 0.63, 0.]),
+    ([1., 0., 0., 0., 1.], [0., 0.63, 0.]),
+    ([0., 0., 0., 0., 1.], [1., -0.07, 3.57]),
+    ([0., 0., 0., 0., 2.], [1., 1.73, -0.03]),
+    ([0., 0., 2., -2., 3.], [1., 0., 0.48]),
+    ([0., 0., 0., 0., 1.], [2., 743.52, -0.17]),
+    ([0., 0., 2., -2., 2.], [2., 56.91, 0.06]),
+    ([0., 0., 2., 0., 2.], [2., 9.84, -0.01]),
+    ([0., 0., 0., 0., 2.], [2., -8.85, 0.01]),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameModel {
+    Rotation,   // uniform rotation about the inertial z axis
+    Iau76Fk5,   // iau 1976 precession, 1980 nutation, gmst and polar motion (equinox based)
+    Iau2006Cio, // iau 2006 precession, 2000b nutation, cio locator and earth rotation angle
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EopRecord {
+    // earth orientation parameters for one utc day, angles in rad. the celestial pole offsets
+    // are either iau 1980 nutation corrections (dpsi, deps) or iau 2006/2000a cip corrections
+    // (dx, dy) depending on the file, the other pair stays zero since each only corrects its
+    // own model
+    pub mjd: f64,
+    pub xp: f64,
+    pub yp: f64,
+    pub ut1_utc: f64, // s
+    pub dpsi: f64,
+    pub deps: f64,
+    pub dx: f64,
+    pub dy: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct HeldNutation {
+    // nutation series of the model evaluated once for an integration step (tt julian dates)
+    model: FrameModel,
+    jd_start: f64,
+    jd_end: f64,
+    dpsi: f64,
+    deps: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct EarthOrientation {
+    pub model: FrameModel,
+    pub eop: Vec<EopRecord>, // sorted by date, zero parameters when empty
+    held: Cell<Option<HeldNutation>>,
+}
+
+pub fn precession_iau76(jd_tt: f64) -> Matrix3<f64> {
+    // [MOD <- J2000] mean of date from the J2000 mean equator and equinox
+    let t = (jd_tt - J2000) / 36525.;
+    let zeta = (2306.2181 * t + 0.30188 * t.powi(2) + 0.017998 * t.powi(3)) * ARCSEC;
+    let theta = (2004.3109 * t - 0.42665 * t.powi(2) - 0.041833 * t.powi(3)) * ARCSEC;
+    let z = (2306.2181 * t + 1.09468 * t.powi(2) + 0.018203 * t.powi(3)) * ARCSEC;
+    r3(-z) * r2(theta) * r3(-zeta)
+}
+
+pub fn mean_obliquity_iau76(jd_tt: f64) -> f64 {
+    let t = (jd_tt - J2000) / 36525.;
+    (84381.448 - 46.8150 * t - 0.00059 * t.powi(2) + 0.001813 * t.powi(3)) * ARCSEC
+}
+
+fn fundamental_arguments(t: f64) -> [f64; 5] {
+    // delaunay arguments l, l', F, D, Omega (rad) of the iau 1980 theory at tt centuries
+    let deg = PI / 180.;
+    let rev = 360.;
+    [
+        134.96298139 + (1325. * rev + 198.8673981) * t + 0.0086972 * t.powi(2),
+        357.52772333 + (99. * rev + 359.0503400) * t - 0.0001603 * t.powi(2),
+        93.27191028 + (1342. * rev + 82.0175381) * t - 0.0036825 * t.powi(2),
+        297.85036306 + (1236. * rev + 307.1114800) * t - 0.0019142 * t.powi(2),
+        125.04452222 - (5. * rev + 134.1362608) * t + 0.0020708 * t.powi(2),
+    ]
+    .map(|arg| arg * deg)
+}
+
+fn fundamental_arguments_2000b(t: f64) -> [f64; 5] {
+    // delaunay arguments l, l', F, D, Omega (rad) of the iau 2000b model at tt centuries
+    [
+        485868.249036 + 1717915923.2178 * t,
+        1287104.79305 + 129596581.0481 * t,
+        335779.526232 + 1739527262.8478 * t,
+        1072260.70369 + 1602961601.2090 * t,
+        450160.398036 - 6962890.5431 * t,
+    ]
+    .map(|arg| arg.rem_euclid(1296000.) * ARCSEC)
+}
+
+fn series_argument(multipliers: &[f64; 5], args: &[f64; 5]) -> f64 {
+    multipliers
+        .iter()
+        .zip(args.iter())
+        .map(|(k, a)| k * a)
+        .sum()
+}
+
+pub fn nutation_iau80(jd_tt: f64) -> (f64, f64) {
+    // nutation in longitude and obliquity (rad) from the full iau 1980 series
+    let t = (jd_tt - J2000) / 36525.;
+    let args = fundamental_arguments(t);
+    let mut dpsi = 0.;
+    let mut deps = 0.;
+    for (multipliers, coefs) in NUTATION_1980_TERMS.iter() {
+        let arg = series_argument(multipliers, &args);
+        dpsi += (coefs[0] + coefs[1] * t) * arg.sin();
+        deps += (coefs[2] + coefs[3] * t) * arg.cos();
+    }
+    (dpsi * 1e-4 * ARCSEC, deps * 1e-4 * ARCSEC)
+}
+
+pub fn nutation_iau2000b(jd_tt: f64) -> (f64, f64) {
+    // nutation in longitude and obliquity (rad) from the iau 2000b series with its fixed
+    // planetary offsets, within a milliarcsecond of iau 2000a
+    let t = (jd_tt - J2000) / 36525.;
+    let args = fundamental_arguments_2000b(t);
+    let mut dpsi = 0.;
+    let mut deps = 0.;
+    for (multipliers, coefs) in NUTATION_2000B_TERMS.iter() {
+        let (sin, cos) = series_argument(multipliers, &args).sin_cos();
+        dpsi += (coefs[0] + coefs[1] * t) * sin + coefs[2] * cos;
+        deps += (coefs[3] + coefs[4] * t) * cos + coefs[5] * sin;
+    }
+    (
+        dpsi * 1e-7 * ARCSEC - 0.135e-3 * ARCSEC,
+        deps * 1e-7 * ARCSEC + 0.388e-3 * ARCSEC,
+    )
+}
+
+pub fn nutation_matrix(eps_mean: f64, dpsi: f64, deps: f64) -> Matrix3<f64> {
+    // [TOD <- MOD] true of date from mean of date
+    r1(-(eps_mean + deps)) * r3(-dpsi) * r1(eps_mean)
+}
+
+pub fn teme2inertial(jd: f64) -> Matrix3<f64> {
+    // [J2000 <- TEME] for sgp4 output, teme differs from true of date by the equation of the
+    // equinoxes (no kinematic terms), at a tt julian date
+    let eps_mean = mean_obliquity_iau76(jd);
+    let (dpsi, deps) = nutation_iau80(jd);
+    let eq_equinox = dpsi * eps_mean.cos();
+    let teme2tod = r3(-eq_equinox);
+    let mod2tod = nutation_matrix(eps_mean, dpsi, deps);
+    precession_iau76(jd).transpose() * mod2tod.transpose() * teme2tod
+}
+
+pub fn gmst_1982(jd_ut1: f64) -> f64 {
+    // greenwich mean sidereal time (rad) from the iau 1982 model
+    let t = (jd_ut1 - J2000) / 36525.;
+    let seconds = -6.2e-6 * t.powi(3)
+        + 0.093104 * t.powi(2)
+        + (876600. * 3600. + 8640184.812866) * t
+        + 67310.54841;
+    (seconds * PI / 180. / 240.).rem_euclid(2. * PI)
+}
+
+pub fn equation_of_equinoxes_1994(jd_tt: f64, eps_mean: f64, dpsi: f64) -> f64 {
+    // gast - gmst (rad) with the kinematic terms in use since 1997
+    let omega = fundamental_arguments((jd_tt - J2000) / 36525.)[4];
+    dpsi * eps_mean.cos() + (0.00264 * omega.sin() + 0.000063 * (2. * omega).sin()) * ARCSEC
+}
+
+pub fn earth_rotation_angle(jd_ut1: f64) -> f64 {
+    // angle (rad) between the cio and the tio, iau 2000
+    let du = jd_ut1 - J2000;
+    (2. * PI * (0.7790572732640 + 0.00273781191135448 * du + du.rem_euclid(1.))).rem_euclid(2. * PI)
+}
+
+pub fn polar_motion(xp: f64, yp: f64, sp: f64) -> Matrix3<f64> {
+    // [ITRF <- TIRS] from the pole coordinates and the tio locator s' (rad)
+    r1(-yp) * r2(-xp) * r3(sp)
+}
+
+pub fn fukushima_williams_2006(jd_tt: f64) -> (f64, f64, f64, f64) {
+    // iau 2006 precession angles gamma_bar, phi_bar, psi_bar and the mean obliquity eps_a
+    // (rad), these include the gcrs frame bias
+    let t = (jd_tt - J2000) / 36525.;
+    let poly =
+        |c: [f64; 6]| -> f64 { c.iter().rev().fold(0., |sum, coef| sum * t + coef) * ARCSEC };
+    (
+        poly([
+            -0.052928,
+            10.556378,
+            0.4932044,
+            -0.00031238,
+            -0.000002788,
+            0.0000000260,
+        ]),
+        poly([
+            84381.412819,
+            -46.811016,
+            0.0511268,
+            0.00053289,
+            -0.000000440,
+            -0.0000000176,
+        ]),
+        poly([
+            -0.041775,
+            5038.481484,
+            1.5584175,
+            -0.00018522,
+            -0.000026452,
+            -0.0000000148,
+        ]),
+        poly([
+            84381.406,
+            -46.836769,
+            -0.0001831,
+            0.00200340,
+            -0.000000576,
+            -0.0000000434,
+        ]),
+    )
+}
+
+pub fn cip_xy(jd_tt: f64, dpsi: f64, deps: f64) -> (f64, f64) {
+    // cip coordinates X, Y (rad) in the gcrs from iau 2006 precession and the given nutation,
+    // the third row of the bias-precession-nutation matrix
+    let (gamma, phi, psi, eps) = fukushima_williams_2006(jd_tt);
+    let npb = r1(-(eps + deps)) * r3(-(psi + dpsi)) * r1(phi) * r3(gamma);
+    (npb[(2, 0)], npb[(2, 1)])
+}
+
+pub fn cio_locator(jd_tt: f64, x: f64, y: f64) -> f64 {
+    // cio locator s (rad) from the largest terms of the iau 2006 series, good to about a
+    // microarcsecond
+    let t = (jd_tt - J2000) / 36525.;
+    let args = fundamental_arguments(t);
+    let polynomial = [94., 3808.65, -122.68, -72574.11, 27.98, 15.62]
+        .iter()
+        .rev()
+        .fold(0., |sum, coef| sum * t + coef);
+    let periodic = CIO_LOCATOR_TERMS
+        .iter()
+        .map(|(multipliers, [power, sine, cosine])| {
+            let arg = series_argument(multipliers, &args);
+            t.powf(*power) * (sine * arg.sin() + cosine * arg.cos())
+        })
+        .sum::<f64>();
+    (polynomial + periodic) * MICROARCSEC - x * y / 2.
+}
+
+pub fn gcrs2cirs(x: f64, y: f64, s: f64) -> Matrix3<f64> {
+    // [CIRS <- GCRS] from the cip coordinates and the cio locator
+    let r2_sum = x * x + y * y;
+    let e = if r2_sum > 0. { y.atan2(x) } else { 0. };
+    let d = (r2_sum / (1. - r2_sum)).sqrt().atan();
+    r3(-(e + s)) * r2(d) * r3(e)
+}
+
+pub fn gcrf2itrf_fk5(
+    jd_tt: f64,
+    jd_ut1: f64,
+    eop: &EopRecord,
+    nutation: (f64, f64),
+) -> Matrix3<f64> {
+    // [ITRF <- GCRF] with iau 1976 precession, 1980 nutation (from nutation_iau80) corrected by
+    // the eop offsets, apparent sidereal time and polar motion. the gcrf is taken as the J2000
+    // mean equator and equinox, the 20 mas frame bias is left out as in the fk5 reduction
+    let eps_mean = mean_obliquity_iau76(jd_tt);
+    let (dpsi, deps) = (nutation.0 + eop.dpsi, nutation.1 + eop.deps);
+    let gast = gmst_1982(jd_ut1) + equation_of_equinoxes_1994(jd_tt, eps_mean, dpsi);
+    polar_motion(eop.xp, eop.yp, 0.)
+        * r3(gast)
+        * nutation_matrix(eps_mean, dpsi, deps)
+        * precession_iau76(jd_tt)
+}
+
+pub fn gcrf2itrf_cio(
+    jd_tt: f64,
+    jd_ut1: f64,
+    eop: &EopRecord,
+    nutation: (f64, f64),
+) -> Matrix3<f64> {
+    // [ITRF <- GCRF] cio based, cip from iau 2006 precession and iau 2000b nutation (from
+    // nutation_iau2000b) corrected by the eop dX/dY pole offsets (given against iau 2006/2000a,
+    // which 2000b follows to a milliarcsecond), earth rotation angle and polar motion with the
+    // tio locator
+    let t = (jd_tt - J2000) / 36525.;
+    // nutation adjusted to the iau 2006 precession (J2 rate and the 2006 obliquity)
+    let fj2 = -2.7774e-6 * t;
+    let (dpsi, deps) = (nutation.0 * (1. + 0.4697e-6 + fj2), nutation.1 * (1. + fj2));
+    let (x, y) = cip_xy(jd_tt, dpsi, deps);
+    let (x, y) = (x + eop.dx, y + eop.dy);
+    let sp = -47. * MICROARCSEC * t;
+    polar_motion(eop.xp, eop.yp, sp)
+        * r3(earth_rotation_angle(jd_ut1))
+        * gcrs2cirs(x, y, cio_locator(jd_tt, x, y))
+}
+
+impl EarthOrientation {
+    pub fn new() -> Self {
+        EarthOrientation {
+            model: FrameModel::Rotation,
+            eop: vec![],
+            held: Cell::new(None),
+        }
+    }
+
+    pub fn hold_nutation(&self, jd_start: f64, jd_end: f64) {
+        // evaluate the nutation series once for an integration step (tt julian dates) instead of
+        // at every rk4 stage, taken at the middle of the step where it is off by well under a
+        // milliarcsecond for steps up to an hour
+        let held = match self.model {
+            FrameModel::Rotation => None,
+            model => {
+                let (dpsi, deps) = self.series_nutation(0.5 * (jd_start + jd_end));
+                Some(HeldNutation {
+                    model,
+                    jd_start,
+                    jd_end,
+                    dpsi,
+                    deps,
+                })
+            }
+        };
+        self.held.set(held);
+    }
+
+    fn series_nutation(&self, jd_tt: f64) -> (f64, f64) {
+        match self.model {
+            FrameModel::Iau2006Cio => nutation_iau2000b(jd_tt),
+            _ => nutation_iau80(jd_tt),
+        }
+    }
+
+    fn nutation(&self, jd_tt: f64) -> (f64, f64) {
+        // the held values inside the current step, the series anywhere else
+        match self.held.get() {
+            Some(held)
+                if held.model == self.model && jd_tt >= held.jd_start && jd_tt <= held.jd_end =>
+            {
+                (held.dpsi, held.deps)
+            }
+            _ => self.series_nutation(jd_tt),
+        }
+    }
+
+    pub fn read_finals(&mut self, file_path: &str, iau2000a: bool) -> io::Result<()> {
+        // iers finals (rapid service/prediction) file, fixed columns: mjd, pole x/y (arcsec),
+        // ut1 - utc (s) and the celestial pole offsets (mas) from bulletin a. the offsets are
+        // dpsi/deps in finals.all and dX/dY in finals2000A.all, picked by iau2000a. rows
+        // without polar motion end the table
+        let file = File::open(file_path)?;
+        let reader = BufReader::new(file);
+        let field = |line: &str, start: usize, end: usize| -> Option<f64> {
+            line.get(start..end.min(line.len()))?
+                .trim()
+                .parse::<f64>()
+                .ok()
+        };
+
+        let mut eop = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            let (mjd, xp, yp, ut1_utc) = match (
+                field(&line, 7, 15),
+                field(&line, 18, 27),
+                field(&line, 37, 46),
+                field(&line, 58, 68),
+            ) {
+                (Some(mjd), Some(xp), Some(yp), Some(ut1_utc)) => (mjd, xp, yp, ut1_utc),
+                _ => break,
+            };
+            let offset1 = field(&line, 97, 106).unwrap_or(0.) * 1e-3 * ARCSEC;
+            let offset2 = field(&line, 116, 125).unwrap_or(0.) * 1e-3 * ARCSEC;
+            let (dpsi, deps, dx, dy) = if iau2000a {
+                (0., 0., offset1, offset2)
+            } else {
+                (offset1, offset2, 0., 0.)
+            };
+            eop.push(EopRecord {
+                mjd,
+                xp: xp * ARCSEC,
+                yp: yp * ARCSEC,
+                ut1_utc,
+                dpsi,
+                deps,
+                dx,
+                dy,
+            });
+        }
+        if eop.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no earth orientation parameters found in file",
+            ));
+        }
+        eop.sort_by(|a, b| a.mjd.partial_cmp(&b.mjd).unwrap());
+        self.eop = eop;
+        Ok(())
+    }
+
+    pub fn eop_at(&self, mjd_utc: f64) -> EopRecord {
+        // parameters interpolated linearly between the daily values, held constant past the
+        // ends of the table. ut1 - utc is interpolated separately through the time data
+        let (first, last) = match (self.eop.first(), self.eop.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return EopRecord::default(),
+        };
+        if mjd_utc <= first.mjd {
+            return *first;
+        }
+        if mjd_utc >= last.mjd {
+            return *last;
+        }
+        let k = self.eop.partition_point(|record| record.mjd <= mjd_utc);
+        let (a, b) = (&self.eop[k - 1], &self.eop[k]);
+        let w = (mjd_utc - a.mjd) / (b.mjd - a.mjd);
+        let lerp = |u: f64, v: f64| u + (v - u) * w;
+        EopRecord {
+            mjd: mjd_utc,
+            xp: lerp(a.xp, b.xp),
+            yp: lerp(a.yp, b.yp),
+            ut1_utc: lerp(a.ut1_utc, b.ut1_utc),
+            dpsi: lerp(a.dpsi, b.dpsi),
+            deps: lerp(a.deps, b.deps),
+            dx: lerp(a.dx, b.dx),
+            dy: lerp(a.dy, b.dy),
+        }
+    }
+
+    pub fn gcrf2itrf(&self, jd_tt: f64, jd_ut1: f64, mjd_utc: f64) -> Option<Matrix3<f64>> {
+        // [ITRF <- GCRF] for the precession-nutation models, None for the simple rotation
+        if self.model == FrameModel::Rotation {
+            return None;
+        }
+        let eop = self.eop_at(mjd_utc);
+        let nutation = self.nutation(jd_tt);
+        match self.model {
+            FrameModel::Iau76Fk5 => Some(gcrf2itrf_fk5(jd_tt, jd_ut1, &eop, nutation)),
+            _ => Some(gcrf2itrf_cio(jd_tt, jd_ut1, &eop, nutation)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoch::*;
+
+    #[test]
+    fn nutation_iau2000b_matches_sofa() {
+        // sofa t_sofa.c check of iauNut00b at 2400000.5 + 53736.0 tt
+        let (dpsi, deps) = nutation_iau2000b(2400000.5 + 53736.0);
+        assert!((dpsi - -0.963_255_229_114_836_3e-5).abs() < 1e-13);
+        assert!((deps - 0.406_319_710_662_115_9e-4).abs() < 1e-13);
+    }
+
+    #[test]
+    fn cio_reduction_matches_vallado_example_3_14() {
+        let time_data = TimeData::new();
+        let epoch = Epoch::parse("2004-04-06T07:51:28.386009Z", &time_data).unwrap();
+        let mjd_utc = epoch.mjd(TimeScale::Utc, &time_data);
+        let jd_ut1 = epoch.jd(TimeScale::Utc, &time_data) - 0.4399619 / 86400.;
+        let eop = EopRecord {
+            mjd: mjd_utc,
+            xp: -0.140682 * ARCSEC,
+            yp: 0.333309 * ARCSEC,
+            ut1_utc: -0.4399619,
+            dx: -0.000205 * ARCSEC,
+            dy: -0.000136 * ARCSEC,
+            ..Default::default()
+        };
+        let r_itrf = vector![-1033.4793830, 7901.2952754, 6380.3565958];
+        let jd_tt = epoch.jd(TimeScale::Tt, &time_data);
+        let gcrf2itrf = gcrf2itrf_cio(jd_tt, jd_ut1, &eop, nutation_iau2000b(jd_tt));
+        let r_gcrf = gcrf2itrf.transpose() * r_itrf;
+        // 2000b stays within a milliarcsecond (3 cm here) of the 2000a result
+        let expected = vector![5102.508959, 6123.011403, 6378.136925];
+        assert!(
+            (r_gcrf - expected).norm() < 1e-4,
+            "{}",
+            (r_gcrf - expected).norm()
+        );
+    }
+
+    #[test]
+    fn nutation_iau80_matches_sofa() {
+        // sofa t_sofa.c check of iauNut80 at 2400000.5 + 53736.0 tt
+        let (dpsi, deps) = nutation_iau80(2400000.5 + 53736.0);
+        assert!((dpsi - -0.964_365_835_322_656_4e-5).abs() < 1e-13);
+        assert!((deps - 0.406_005_100_687_971_3e-4).abs() < 1e-13);
+    }
+
+    #[test]
+    fn fk5_reduction_matches_vallado_example_3_15() {
+        let time_data = TimeData::new();
+        let epoch = Epoch::parse("2004-04-06T07:51:28.386009Z", &time_data).unwrap();
+        let jd_tt = epoch.jd(TimeScale::Tt, &time_data);
+        let jd_ut1 = epoch.jd(TimeScale::Utc, &time_data) - 0.4399619 / 86400.;
+        let eop = EopRecord {
+            mjd: epoch.mjd(TimeScale::Utc, &time_data),
+            xp: -0.140682 * ARCSEC,
+            yp: 0.333309 * ARCSEC,
+            ut1_utc: -0.4399619,
+            dpsi: -0.052195 * ARCSEC,
+            deps: -0.003875 * ARCSEC,
+            ..Default::default()
+        };
+        let r_itrf = vector![-1033.4793830, 7901.2952754, 6380.3565958];
+        let gcrf2itrf = gcrf2itrf_fk5(jd_tt, jd_ut1, &eop, nutation_iau80(jd_tt));
+        let r_gcrf = gcrf2itrf.transpose() * r_itrf;
+        let expected = vector![5102.508958, 6123.011401, 6378.136928];
+        assert!(
+            (r_gcrf - expected).norm() < 1e-5,
+            "{}",
+            (r_gcrf - expected).norm()
+        );
+    }
+
+    #[test]
+    fn held_nutation_is_only_used_inside_its_step() {
+        let mut orientation = EarthOrientation::new();
+        orientation.model = FrameModel::Iau76Fk5;
+        let (jd_start, jd_end) = (2453101.5, 2453101.5 + 60. / 86400.);
+        orientation.hold_nutation(jd_start, jd_end);
+        let held = nutation_iau80(0.5 * (jd_start + jd_end));
+        assert_eq!(orientation.nutation(jd_start), held);
+        assert_eq!(orientation.nutation(jd_end), held);
+        // evaluated fresh outside the step and for another model
+        let later = jd_end + 1.;
+        assert_eq!(orientation.nutation(later), nutation_iau80(later));
+        orientation.model = FrameModel::Iau2006Cio;
+        assert_eq!(orientation.nutation(jd_start), nutation_iau2000b(jd_start));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::centralbody::CentralBody;
+use crate::satbody::SatBody;
+use nalgebra::*;
+use std::f64::consts::PI;
+
+const MAX_ITERATIONS: usize = 10;
+const LATITUDE_TOL: f64 = 1e-15; // rad
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Geodetic {
+    pub lat: f64, // rad, geodetic latitude
+    pub lon: f64, // rad, (-pi, pi]
+    pub alt: f64, // km above the ellipsoid
+}
+
+#[derive(Clone, Debug)]
+pub struct GroundStation {
+    pub name: String,
+    pub location: Geodetic,
+    pub min_elevation: f64, // rad, elevation mask for visibility
+}
+
+impl Geodetic {
+    pub fn new() -> Self {
+        Geodetic {
+            lat: 0.,
+            lon: 0.,
+            alt: 0.,
+        }
+    }
+}
+
+pub fn ecef2geodetic(r_ecef: &Vector3<f64>, central_body: &CentralBody) -> Geodetic {
+    // geodetic coordinates on the body's reference ellipsoid with bowring's method, iterated on
+    // the parametric latitude to machine precision (one pass is already good to a millimeter
+    // for earth orbits)
+    let a = central_body.equatorial_radius;
+    let f = central_body.flattening;
+    let b = a * (1. - f);
+    let e2 = f * (2. - f);
+    let ep2 = e2 / (1. - e2);
+
+    let p = (r_ecef[0].powi(2) + r_ecef[1].powi(2)).sqrt();
+    let z = r_ecef[2];
+    let lon = r_ecef[1].atan2(r_ecef[0]);
+    if p < 1e-12 * a {
+        // on the polar axis
+        return Geodetic {
+            lat: PI / 2. * z.signum(),
+            lon,
+            alt: z.abs() - b,
+        };
+    }
+
+    let mut beta = z.atan2((1. - f) * p);
+    let mut lat = beta;
+    for _ in 0..MAX_ITERATIONS {
+        lat = (z + ep2 * b * beta.sin().powi(3)).atan2(p - e2 * a * beta.cos().powi(3));
+        let beta_new = ((1. - f) * lat.sin()).atan2(lat.cos());
+        let converged = (beta_new - beta).abs() < LATITUDE_TOL;
+        beta = beta_new;
+        if converged {
+            break;
+        }
+    }
+    // altitude form that stays well conditioned at all latitudes
+    let n = a / (1. - e2 * lat.sin().powi(2)).sqrt();
+    let alt = p * lat.cos() + (z + e2 * n * lat.sin()) * lat.sin() - n;
+    Geodetic { lat, lon, alt }
+}
+
+pub fn geodetic2ecef(geodetic: &Geodetic, central_body: &CentralBody) -> Vector3<f64> {
+    let a = central_body.equatorial_radius;
+    let e2 = central_body.flattening * (2. - central_body.flattening);
+    let (slat, clat) = geodetic.lat.sin_cos();
+    let (slon, clon) = geodetic.lon.sin_cos();
+    // prime vertical radius of curvature
+    let n = a / (1. - e2 * slat.powi(2)).sqrt();
+    Vector3::new(
+        (n + geodetic.alt) * clat * clon,
+        (n + geodetic.alt) * clat * slon,
+        (n * (1. - e2) + geodetic.alt) * slat,
+    )
+}
+
+pub fn eci2geodetic(r_eci: &Vector3<f64>, time: &f64, central_body: &CentralBody) -> Geodetic {
+    // sub-satellite point and altitude at simulation time
+    ecef2geodetic(&(central_body.gen_eci2ecef(time) * r_eci), central_body)
+}
+
+pub fn ecef2enu(geodetic: &Geodetic) -> Matrix3<f64> {
+    // [ENU <- ECEF] local east, north, up axes at a geodetic location
+    let (slat, clat) = geodetic.lat.sin_cos();
+    let (slon, clon) = geodetic.lon.sin_cos();
+    matrix![-slon, clon, 0.;
+            -slat * clon, -slat * slon, clat;
+            clat * clon, clat * slon, slat]
+}
+
+pub fn ground_track(satellite: &SatBody, central_body: &CentralBody) -> Vec<(f64, Geodetic)> {
+    // (simulation time, sub-satellite point) for each stored state
+    satellite
+        .time_history
+        .iter()
+        .zip(satellite.state_history.iter())
+        .map(|(time, state)| {
+            let r_eci = Vector3::new(state[0], state[1], state[2]);
+            (*time, eci2geodetic(&r_eci, time, central_body))
+        })
+        .collect()
+}
+
+impl GroundStation {
+    pub fn new() -> Self {
+        GroundStation {
+            name: String::from(""),
+            location: Geodetic::new(),
+            min_elevation: 0.,
+        }
+    }
+
+    pub fn position(&self, central_body: &CentralBody) -> Vector3<f64> {
+        // station position in the body fixed frame (km)
+        geodetic2ecef(&self.location, central_body)
+    }
+
+    pub fn look_angles(
+        &self,
+        r_eci: &Vector3<f64>,
+        time: &f64,
+        central_body: &CentralBody,
+    ) -> (f64, f64, f64) {
+        // azimuth (rad, from north through east), elevation (rad) and range (km) of a target
+        let r_ecef = central_body.gen_eci2ecef(time) * r_eci;
+        let rho = ecef2enu(&self.location) * (r_ecef - self.position(central_body));
+        let range = rho.norm();
+        let azimuth = rho[0].atan2(rho[1]).rem_euclid(2. * PI);
+        let elevation = (rho[2] / range).asin();
+        (azimuth, elevation, range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epoch::{Epoch, TimeData, TimeScale};
+
+    fn earth() -> CentralBody {
+        // wgs84 ellipsoid, the body fixed frame coincides with the inertial frame
+        let mut central_body = CentralBody::new();
+        central_body.equatorial_radius = 6378.137;
+        central_body.flattening = 1. / 298.257223563;
+        central_body
+    }
+
+    #[test]
+    fn ecef2geodetic_matches_vallado_example_3_3() {
+        let geodetic = ecef2geodetic(&vector![6524.834, 6862.875, 6448.296], &earth());
+        assert!((geodetic.lat.to_degrees() - 34.352496).abs() < 1e-6);
+        assert!((geodetic.lon.to_degrees() - 46.4464).abs() < 1e-4);
+        assert!((geodetic.alt - 5085.22).abs() < 1e-2);
+    }
+
+    #[test]
+    fn geodetic_round_trip() {
+        let central_body = earth();
+        for lat in [-90., -89.9999, -45., -1e-7, 0., 30., 60., 89.99, 90.] {
+            for lon in [-179.9, -90., 0., 45., 180.] {
+                for alt in [-5., 0., 0.5, 400., 36000., 400000.] {
+                    let geodetic = Geodetic {
+                        lat: f64::to_radians(lat),
+                        lon: f64::to_radians(lon),
+                        alt,
+                    };
+                    let r_ecef = geodetic2ecef(&geodetic, &central_body);
+                    let recovered = ecef2geodetic(&r_ecef, &central_body);
+                    assert!(
+                        (recovered.lat - geodetic.lat).abs() < 1e-12,
+                        "{} {} {}",
+                        lat,
+                        lon,
+                        alt
+                    );
+                    assert!(
+                        (recovered.alt - alt).abs() < 1e-8,
+                        "{} {} {}",
+                        lat,
+                        lon,
+                        alt
+                    );
+                    // longitude is undefined on the poles
+                    if lat.abs() < 90. {
+                        let dlon = (recovered.lon - geodetic.lon + PI).rem_euclid(2. * PI) - PI;
+                        assert!(dlon.abs() < 1e-12);
+                    }
+                    assert!((geodetic2ecef(&recovered, &central_body) - r_ecef).norm() < 1e-8);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn look_angles_point_at_the_satellite() {
+        let central_body = earth();
+        let mut station = GroundStation::new();
+        station.location = Geodetic {
+            lat: f64::to_radians(40.),
+            lon: f64::to_radians(-105.),
+            alt: 1.6,
+        };
+        let up = ecef2enu(&station.location).transpose();
+        let site = station.position(&central_body);
+        // overhead, due north and due east at 30 deg elevation
+        let cases = [
+            (vector![0., 0., 1.], 0., 90.),
+            (vector![0., 3_f64.sqrt(), 1.], 0., 30.),
+            (vector![3_f64.sqrt(), 0., 1.], 90., 30.),
+            (vector![-1., -1., 0.], 225., 0.),
+        ];
+        for (enu, azimuth, elevation) in cases {
+            let r_eci = site + up * enu.normalize() * 1000.;
+            let (az, el, range) = station.look_angles(&r_eci, &0., &central_body);
+            assert!((range - 1000.).abs() < 1e-9);
+            assert!((el.to_degrees() - elevation).abs() < 1e-9);
+            if elevation < 90. {
+                let daz = (az.to_degrees() - azimuth + 180.).rem_euclid(360.) - 180.;
+                assert!(daz.abs() < 1e-9, "{} {}", az.to_degrees(), azimuth);
+            }
+        }
+    }
+
+    #[test]
+    fn rotating_earth_matches_vallado_example_3_5() {
+        // gmst as a uniform rotation from its J2000 value, 1992 aug 20 12:14 ut1 has a gmst of
+        // 152.578787810 deg (the dropped t^2 term is 2e-6 deg here)
+        let mut central_body = earth();
+        central_body.omega = 360.98564736629_f64.to_radians() / 86400.;
+        central_body.prime_meridian = 280.46061837504_f64.to_radians();
+        let gmst = 152.578787810_f64.to_radians();
+        let time =
+            Epoch::from_jd(2448855.009722222, TimeScale::Ut1, &TimeData::new()).j2000_seconds();
+
+        // a satellite over the equator at a right ascension 60 deg east of gmst
+        let right_ascension = gmst + 60_f64.to_radians();
+        let r_eci = vector![right_ascension.cos(), right_ascension.sin(), 0.] * 7000.;
+        let geodetic = eci2geodetic(&r_eci, &time, &central_body);
+        assert!(geodetic.lat.abs() < 1e-12);
+        assert!((geodetic.lon.to_degrees() - 60.).abs() < 1e-5);
+        assert!((geodetic.alt - (7000. - 6378.137)).abs() < 1e-9);
+
+        // straight overhead of a station under it and due east of one 10 deg further west
+        let mut station = GroundStation::new();
+        station.location = Geodetic {
+            lat: 0.,
+            lon: 60_f64.to_radians(),
+            alt: 0.,
+        };
+        let (_, elevation, range) = station.look_angles(&r_eci, &time, &central_body);
+        assert!((elevation.to_degrees() - 90.).abs() < 1e-4);
+        assert!((range - (7000. - 6378.137)).abs() < 1e-6);
+        station.location.lon = 50_f64.to_radians();
+        let (azimuth, elevation, _) = station.look_angles(&r_eci, &time, &central_body);
+        assert!((azimuth.to_degrees() - 90.).abs() < 1e-9);
+        assert!(elevation > 0. && elevation.to_degrees() < 90.);
+    }
+}
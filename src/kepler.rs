@@ -0,0 +1,239 @@
+use crate::math::*;
+use nalgebra::*;
+use std::f64::consts::PI;
+
+const PARABOLIC_TOL: f64 = 1e-8; // |e - 1| below which orbits are treated as parabolic
+const STUMPFF_TOL: f64 = 1e-6; // |psi| below which the stumpff series are used
+
+pub fn mean2hyperbolic(mean_anom: f64, ecc: f64) -> f64 {
+    // hyperbolic anomaly from kepler's equation M = e sinh(H) - H, hyperbolic orbits
+    let mut hyp_anom = if ecc < 1.6 {
+        mean_anom
+    } else {
+        mean_anom.signum() * (2. * mean_anom.abs() / ecc + 1.8).ln()
+    };
+    for _ in 0..50 {
+        let step = (ecc * hyp_anom.sinh() - hyp_anom - mean_anom) / (ecc * hyp_anom.cosh() - 1.);
+        hyp_anom -= step;
+        if step.abs() < 1e-14 * hyp_anom.abs().max(1.) {
+            break;
+        }
+    }
+    hyp_anom
+}
+
+pub fn mean2parabolic(mean_anom: f64) -> f64 {
+    // parabolic anomaly D = tan(ta / 2) from barker's equation M = D + D^3 / 3 (closed form)
+    let b = 1.5 * mean_anom;
+    let root = (1. + b.powi(2)).sqrt();
+    (b + root).cbrt() + (b - root).cbrt()
+}
+
+pub fn true2mean(ta: f64, ecc: f64) -> f64 {
+    // mean anomaly of any conic: elliptic M = E - e sin(E), hyperbolic M = e sinh(H) - H and
+    // parabolic M = D + D^3 / 3 (barker)
+    if (ecc - 1.).abs() < PARABOLIC_TOL {
+        let d = (ta / 2.).tan();
+        d + d.powi(3) / 3.
+    } else if ecc < 1. {
+        let ecc_anom = 2. * (((1. - ecc) / (1. + ecc)).sqrt() * (ta / 2.).tan()).atan();
+        ecc_anom - ecc * ecc_anom.sin()
+    } else {
+        let hyp_anom = 2. * (((ecc - 1.) / (ecc + 1.)).sqrt() * (ta / 2.).tan()).atanh();
+        ecc * hyp_anom.sinh() - hyp_anom
+    }
+}
+
+pub fn mean2true(mean_anom: f64, ecc: f64) -> f64 {
+    // inverse of true2mean, the true anomaly is in (-pi, pi]
+    if (ecc - 1.).abs() < PARABOLIC_TOL {
+        2. * mean2parabolic(mean_anom).atan()
+    } else if ecc < 1. {
+        let ecc_anom = mean2eccentric(mean_anom, ecc);
+        2. * (((1. + ecc) / (1. - ecc)).sqrt() * (ecc_anom / 2.).tan()).atan()
+    } else {
+        let hyp_anom = mean2hyperbolic(mean_anom, ecc);
+        2. * (((ecc + 1.) / (ecc - 1.)).sqrt() * (hyp_anom / 2.).tanh()).atan()
+    }
+}
+
+pub fn stumpff_c2(psi: f64) -> f64 {
+    // c2(psi) = (1 - cos(sqrt(psi))) / psi, series near zero
+    if psi > STUMPFF_TOL {
+        (1. - psi.sqrt().cos()) / psi
+    } else if psi < -STUMPFF_TOL {
+        (1. - (-psi).sqrt().cosh()) / psi
+    } else {
+        0.5 - psi / 24. + psi.powi(2) / 720.
+    }
+}
+
+pub fn stumpff_c3(psi: f64) -> f64 {
+    // c3(psi) = (sqrt(psi) - sin(sqrt(psi))) / sqrt(psi^3), series near zero
+    if psi > STUMPFF_TOL {
+        let sqrt_psi = psi.sqrt();
+        (sqrt_psi - sqrt_psi.sin()) / sqrt_psi.powi(3)
+    } else if psi < -STUMPFF_TOL {
+        let sqrt_psi = (-psi).sqrt();
+        (sqrt_psi.sinh() - sqrt_psi) / sqrt_psi.powi(3)
+    } else {
+        1. / 6. - psi / 120. + psi.powi(2) / 5040.
+    }
+}
+
+pub fn kepler_state(state: &Vector6<f64>, dt: f64, mu: f64) -> Vector6<f64> {
+    // two-body state after dt (s) with the universal variable formulation and lagrange f and g
+    // coefficients, valid for every conic (Vallado, Fundamentals of Astrodynamics, algorithm 8)
+    if dt == 0. {
+        return *state;
+    }
+    let r0_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+    let v0_vec: Vector3<f64> = state.fixed_rows::<3>(3).into();
+    let r0 = r0_vec.norm();
+    let rv0 = r0_vec.dot(&v0_vec);
+    let sqrt_mu = mu.sqrt();
+    let alpha = 2. / r0 - v0_vec.norm_squared() / mu; // 1 / sma
+
+    // initial guess for the universal variable chi
+    let mut chi = if alpha > STUMPFF_TOL {
+        sqrt_mu * dt * alpha
+    } else if alpha < -STUMPFF_TOL {
+        let sma = 1. / alpha;
+        dt.signum()
+            * (-sma).sqrt()
+            * ((-2. * mu * alpha * dt)
+                / (rv0 + dt.signum() * (-mu * sma).sqrt() * (1. - r0 * alpha)))
+                .ln()
+    } else {
+        let param = r0_vec.cross(&v0_vec).norm_squared() / mu;
+        let s = 0.5 * (PI / 2. - (3. * (mu / param.powi(3)).sqrt() * dt).atan());
+        let w = s.tan().cbrt().atan();
+        param.sqrt() * 2. / (2. * w).tan()
+    };
+    if !chi.is_finite() {
+        chi = sqrt_mu * dt / r0;
+    }
+
+    let mut r = r0;
+    let mut psi = 0.;
+    for _ in 0..100 {
+        psi = chi.powi(2) * alpha;
+        let c2 = stumpff_c2(psi);
+        let c3 = stumpff_c3(psi);
+        r = chi.powi(2) * c2 + rv0 / sqrt_mu * chi * (1. - psi * c3) + r0 * (1. - psi * c2);
+        let step = (sqrt_mu * dt
+            - chi.powi(3) * c3
+            - rv0 / sqrt_mu * chi.powi(2) * c2
+            - r0 * chi * (1. - psi * c3))
+            / r;
+        chi += step;
+        if step.abs() < 1e-12 * chi.abs().max(1.) {
+            psi = chi.powi(2) * alpha;
+            let c2 = stumpff_c2(psi);
+            let c3 = stumpff_c3(psi);
+            r = chi.powi(2) * c2 + rv0 / sqrt_mu * chi * (1. - psi * c3) + r0 * (1. - psi * c2);
+            break;
+        }
+    }
+
+    let c2 = stumpff_c2(psi);
+    let c3 = stumpff_c3(psi);
+    let f = 1. - chi.powi(2) / r0 * c2;
+    let g = dt - chi.powi(3) / sqrt_mu * c3;
+    let f_dot = sqrt_mu / (r * r0) * chi * (psi * c3 - 1.);
+    let g_dot = 1. - chi.powi(2) / r * c2;
+
+    let mut state_new = Vector6::zeros();
+    state_new
+        .fixed_rows_mut::<3>(0)
+        .copy_from(&(f * r0_vec + g * v0_vec));
+    state_new
+        .fixed_rows_mut::<3>(3)
+        .copy_from(&(f_dot * r0_vec + g_dot * v0_vec));
+    state_new
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MU: f64 = 398600.4418;
+
+    fn rk4(state: &Vector6<f64>, dt: f64, steps: usize) -> Vector6<f64> {
+        // fixed step two-body reference solution
+        let dxdt = |x: &Vector6<f64>| -> Vector6<f64> {
+            let r_vec: Vector3<f64> = x.fixed_rows::<3>(0).into();
+            let accel = -MU / r_vec.norm().powi(3) * r_vec;
+            vector![x[3], x[4], x[5], accel[0], accel[1], accel[2]]
+        };
+        let h = dt / steps as f64;
+        let mut x = *state;
+        for _ in 0..steps {
+            let k1 = dxdt(&x);
+            let k2 = dxdt(&(x + k1 * h / 2.));
+            let k3 = dxdt(&(x + k2 * h / 2.));
+            let k4 = dxdt(&(x + k3 * h));
+            x += (k1 + (k2 + k3) * 2. + k4) * h / 6.;
+        }
+        x
+    }
+
+    #[test]
+    fn kepler_state_matches_vallado_example_2_4() {
+        let state = vector![1131.340, -2282.343, 6672.423, -5.64305, 4.30333, 2.42879];
+        let state_new = kepler_state(&state, 40. * 60., MU);
+        let expected = vector![-4219.7527, 4363.0292, -3958.7666, 3.689866, -1.916735, -6.112511];
+        assert!((state_new.fixed_rows::<3>(0) - expected.fixed_rows::<3>(0)).norm() < 1e-3);
+        assert!((state_new.fixed_rows::<3>(3) - expected.fixed_rows::<3>(3)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn kepler_state_matches_rk4_for_every_conic() {
+        let r = 7000.;
+        let v_circular = (MU / r).sqrt();
+        // circular, elliptic, near parabolic, hyperbolic and backwards in time
+        let cases = [
+            (v_circular, 5000.),
+            (1.2 * v_circular, 20000.),
+            (2_f64.sqrt() * v_circular, 5000.),
+            (1.6 * v_circular, 5000.),
+            (1.2 * v_circular, -7000.),
+        ];
+        for (v, dt) in cases {
+            let state = vector![r, 0., 0., 0., v * 0.8, v * 0.6];
+            let state_new = kepler_state(&state, dt, MU);
+            let reference = rk4(&state, dt, 20000);
+            let r_error = (state_new.fixed_rows::<3>(0) - reference.fixed_rows::<3>(0)).norm();
+            let v_error = (state_new.fixed_rows::<3>(3) - reference.fixed_rows::<3>(3)).norm();
+            assert!(
+                r_error < 1e-6 && v_error < 1e-9,
+                "v = {}: {} {}",
+                v,
+                r_error,
+                v_error
+            );
+            // propagating back returns to the start
+            let back = kepler_state(&state_new, -dt, MU);
+            assert!((back - state).norm() < 1e-7);
+        }
+    }
+
+    #[test]
+    fn mean2true_inverts_true2mean() {
+        for ecc in [0., 0.3, 0.95, 1., 1.5, 5.] {
+            // hyperbolic true anomalies stay inside the asymptotes
+            let ta_max = if ecc > 1. { (-1. / ecc).acos() } else { PI };
+            for ta in
+                [-2.5, -0.4, 0., 1., 2.].map(|ta: f64| ta.clamp(-0.95 * ta_max, 0.95 * ta_max))
+            {
+                let mean_anom = true2mean(ta, ecc);
+                assert!(
+                    (mean2true(mean_anom, ecc) - ta).abs() < 1e-10,
+                    "{} {}",
+                    ecc,
+                    ta
+                );
+            }
+        }
+    }
+}
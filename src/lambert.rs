@@ -0,0 +1,313 @@
+use nalgebra::*;
+use std::f64::consts::PI;
+
+// Izzo, "Revisiting Lambert's problem", Celestial Mechanics and Dynamical Astronomy 121 (2015)
+
+const MAX_ITERATIONS: usize = 35;
+const X_TOL: f64 = 1e-11;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LambertBranch {
+    Single, // zero revolution solution
+    Left,   // multi revolution solution left of the minimum time of flight (smaller x)
+    Right,  // multi revolution solution right of the minimum time of flight (larger x)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LambertSolution {
+    pub revs: usize,
+    pub branch: LambertBranch,
+    pub v1: Vector3<f64>, // departure velocity
+    pub v2: Vector3<f64>, // arrival velocity
+}
+
+pub fn lambert(
+    r1: &Vector3<f64>,
+    r2: &Vector3<f64>,
+    tof: f64,
+    mu: f64,
+    prograde: bool,
+    max_revs: usize,
+) -> Vec<LambertSolution> {
+    // every solution of lambert's problem from r1 to r2 in tof with up to max_revs complete
+    // revolutions, units follow mu (km, km/s with km^3/s^2). prograde transfers move counter
+    // clockwise about the inertial z axis. mu is the central body's or, for transfers about a
+    // moon, the OtherBody's. no solutions are returned for a non-positive time of flight or
+    // when r1 and r2 are collinear (the transfer plane is undefined)
+    let r1_mag = r1.norm();
+    let r2_mag = r2.norm();
+    let chord = (r2 - r1).norm();
+    let h_vec = r1.cross(r2);
+    if tof <= 0. || r1_mag == 0. || r2_mag == 0. || h_vec.norm() <= 1e-12 * r1_mag * r2_mag {
+        return vec![];
+    }
+
+    let s = (r1_mag + r2_mag + chord) / 2.;
+    let ir1 = r1 / r1_mag;
+    let ir2 = r2 / r2_mag;
+    let ih = h_vec.normalize();
+    let mut lambda = (1. - chord / s).max(0.).sqrt();
+
+    // transfer angles past 180 deg flip lambda and the tangential directions
+    let (mut it1, mut it2) = if ih[2] < 0. {
+        lambda = -lambda;
+        (ir1.cross(&ih), ir2.cross(&ih))
+    } else {
+        (ih.cross(&ir1), ih.cross(&ir2))
+    };
+    if !prograde {
+        lambda = -lambda;
+        it1 = -it1;
+        it2 = -it2;
+    }
+
+    let t_nondim = (2. * mu / s.powi(3)).sqrt() * tof;
+    let gamma = (mu * s / 2.).sqrt();
+    let rho = (r1_mag - r2_mag) / chord;
+    let sigma = (1. - rho.powi(2)).max(0.).sqrt();
+
+    find_xy(lambda, t_nondim, max_revs)
+        .into_iter()
+        .map(|(revs, branch, x)| {
+            let y = (1. - lambda.powi(2) * (1. - x.powi(2))).sqrt();
+            let vr1 = gamma * ((lambda * y - x) - rho * (lambda * y + x)) / r1_mag;
+            let vr2 = -gamma * ((lambda * y - x) + rho * (lambda * y + x)) / r2_mag;
+            let vt = gamma * sigma * (y + lambda * x);
+            LambertSolution {
+                revs,
+                branch,
+                v1: vr1 * ir1 + vt / r1_mag * it1,
+                v2: vr2 * ir2 + vt / r2_mag * it2,
+            }
+        })
+        .collect()
+}
+
+fn find_xy(lambda: f64, t: f64, max_revs: usize) -> Vec<(usize, LambertBranch, f64)> {
+    // x of every solution for the non-dimensional time of flight t
+    let mut revs_max = (t / PI).floor() as usize;
+    let t00 = lambda.acos() + lambda * (1. - lambda.powi(2)).sqrt();
+    let t0 = t00 + revs_max as f64 * PI;
+    if t < t0 && revs_max > 0 {
+        // the time of flight may fall below the minimum of the highest revolution count
+        let t_min = time_of_flight_min(lambda, revs_max);
+        if t_min > t {
+            revs_max -= 1;
+        }
+    }
+    let revs_max = revs_max.min(max_revs);
+
+    // zero revolution initial guess
+    let t1 = 2. / 3. * (1. - lambda.powi(3));
+    let x0 = if t >= t00 {
+        (t00 / t).powf(2. / 3.) - 1.
+    } else if t < t1 {
+        2.5 * t1 / t * (t1 - t) / (1. - lambda.powi(5)) + 1.
+    } else {
+        (t00 / t).powf(2f64.ln() / (t1 / t00).ln()) - 1.
+    };
+    let mut solutions = vec![(0, LambertBranch::Single, householder(t, x0, 0, lambda))];
+
+    for revs in 1..=revs_max {
+        let nf = revs as f64;
+        let tmp = ((nf * PI + PI) / (8. * t)).powf(2. / 3.);
+        let x_left = householder(t, (tmp - 1.) / (tmp + 1.), revs, lambda);
+        let tmp = ((8. * t) / (nf * PI)).powf(2. / 3.);
+        let x_right = householder(t, (tmp - 1.) / (tmp + 1.), revs, lambda);
+        solutions.push((revs, LambertBranch::Left, x_left));
+        solutions.push((revs, LambertBranch::Right, x_right));
+    }
+    solutions
+}
+
+fn householder(t: f64, x0: f64, revs: usize, lambda: f64) -> f64 {
+    // third order root finding on the time of flight curve
+    let mut x = x0;
+    for _ in 0..MAX_ITERATIONS {
+        let tof = x2tof(x, revs, lambda);
+        let (dt, ddt, dddt) = dtdx(x, tof, lambda);
+        let delta = tof - t;
+        let dt2 = dt.powi(2);
+        let x_new = x - delta * (dt2 - delta * ddt / 2.)
+            / (dt * (dt2 - delta * ddt) + dddt * delta.powi(2) / 6.);
+        let err = (x - x_new).abs();
+        x = x_new;
+        if err < X_TOL {
+            break;
+        }
+    }
+    x
+}
+
+fn time_of_flight_min(lambda: f64, revs: usize) -> f64 {
+    // minimum time of flight for revs complete revolutions (halley iterations on dT/dx = 0)
+    if lambda == 1. {
+        return x2tof(0., revs, lambda);
+    }
+    let mut x = 0.1;
+    let mut tof = x2tof(x, revs, lambda);
+    for _ in 0..MAX_ITERATIONS {
+        let (dt, ddt, dddt) = dtdx(x, tof, lambda);
+        if dt == 0. {
+            break;
+        }
+        let x_new = x - dt * ddt / (ddt.powi(2) - dt * dddt / 2.);
+        let err = (x - x_new).abs();
+        x = x_new;
+        tof = x2tof(x, revs, lambda);
+        if err < 1e-13 {
+            break;
+        }
+    }
+    tof
+}
+
+fn dtdx(x: f64, t: f64, lambda: f64) -> (f64, f64, f64) {
+    // first three derivatives of the time of flight with respect to x
+    let l2 = lambda.powi(2);
+    let l3 = l2 * lambda;
+    let umx2 = 1. - x.powi(2);
+    let y = (1. - l2 * umx2).sqrt();
+    let y2 = y.powi(2);
+    let y3 = y2 * y;
+    let dt = (3. * t * x - 2. + 2. * l3 * x / y) / umx2;
+    let ddt = (3. * t + 5. * x * dt + 2. * (1. - l2) * l3 / y3) / umx2;
+    let dddt = (7. * x * ddt + 8. * dt - 6. * (1. - l2) * l2 * l3 * x / y3 / y2) / umx2;
+    (dt, ddt, dddt)
+}
+
+fn x2tof(x: f64, revs: usize, lambda: f64) -> f64 {
+    // non-dimensional time of flight, battin's series close to the parabola and lagrange's
+    // equation near it to avoid the cancellation in the general expression
+    let battin = 0.01;
+    let lagrange = 0.2;
+    let dist = (x - 1.).abs();
+    if dist < lagrange && dist > battin {
+        return x2tof_lagrange(x, revs, lambda);
+    }
+    let nf = revs as f64;
+    let k = lambda.powi(2);
+    let e = x.powi(2) - 1.;
+    let rho = e.abs();
+    let z = (1. + k * e).sqrt();
+    if dist < battin {
+        let eta = z - lambda * x;
+        let s1 = 0.5 * (1. - lambda - x * eta);
+        let q = 4. / 3. * hypergeometric_f(s1, 1e-11);
+        (eta.powi(3) * q + 4. * lambda * eta) / 2. + nf * PI / rho.powf(1.5)
+    } else {
+        let y = rho.sqrt();
+        let g = x * z - lambda * e;
+        let d = if e < 0. {
+            nf * PI + g.clamp(-1., 1.).acos()
+        } else {
+            let f = y * (z - lambda * x);
+            (f + g).ln()
+        };
+        (x - lambda * z - d / y) / e
+    }
+}
+
+fn x2tof_lagrange(x: f64, revs: usize, lambda: f64) -> f64 {
+    let a = 1. / (1. - x.powi(2));
+    if a > 0. {
+        let alpha = 2. * x.acos();
+        let beta = 2. * (lambda.powi(2) / a).sqrt().asin() * lambda.signum();
+        a * a.sqrt() * ((alpha - alpha.sin()) - (beta - beta.sin()) + 2. * PI * revs as f64) / 2.
+    } else {
+        let alpha = 2. * x.acosh();
+        let beta = 2. * (-lambda.powi(2) / a).sqrt().asinh() * lambda.signum();
+        -a * (-a).sqrt() * ((beta - beta.sinh()) - (alpha - alpha.sinh())) / 2.
+    }
+}
+
+fn hypergeometric_f(z: f64, tol: f64) -> f64 {
+    // gauss hypergeometric function 2F1(3, 1, 5/2, z) by its series
+    let mut sum = 1.;
+    let mut term = 1.;
+    let mut j = 0.;
+    while term.abs() > tol && j < 1000. {
+        term *= (3. + j) * (1. + j) / (2.5 + j) * z / (j + 1.);
+        sum += term;
+        j += 1.;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kepler::kepler_state;
+
+    const MU: f64 = 398600.4418;
+
+    fn assert_transfer(r1: &Vector3<f64>, r2: &Vector3<f64>, tof: f64, solution: &LambertSolution) {
+        // the departure state flown for tof has to arrive at r2 with v2
+        let state = vector![
+            r1[0],
+            r1[1],
+            r1[2],
+            solution.v1[0],
+            solution.v1[1],
+            solution.v1[2]
+        ];
+        let arrival = kepler_state(&state, tof, MU);
+        let r_error = (arrival.fixed_rows::<3>(0) - r2).norm();
+        let v_error = (arrival.fixed_rows::<3>(3) - solution.v2).norm();
+        assert!(
+            r_error < 1e-5 && v_error < 1e-8,
+            "{} revs {:?}: {} {}",
+            solution.revs,
+            solution.branch,
+            r_error,
+            v_error
+        );
+    }
+
+    #[test]
+    fn lambert_matches_curtis_example_5_2() {
+        let r1 = vector![5000., 10000., 2100.];
+        let r2 = vector![-14600., 2500., 7000.];
+        let solutions = lambert(&r1, &r2, 3600., 398600., true, 0);
+        assert_eq!(solutions.len(), 1);
+        let expected_v1 = vector![-5.9925, 1.9254, 3.2456];
+        let expected_v2 = vector![-3.3125, -4.1966, -0.38529];
+        assert!((solutions[0].v1 - expected_v1).norm() < 1e-4);
+        assert!((solutions[0].v2 - expected_v2).norm() < 1e-4);
+    }
+
+    #[test]
+    fn every_solution_reaches_the_target() {
+        let r1 = vector![7000., 0., 0.];
+        // short and long way transfers with up to 5 revolutions
+        for r2 in [
+            vector![-2000., 9000., 1500.],
+            vector![3000., -8000., -2500.],
+        ] {
+            for prograde in [true, false] {
+                let tof = 5.5 * 2. * PI * (10000_f64.powi(3) / MU).sqrt();
+                let solutions = lambert(&r1, &r2, tof, MU, prograde, 5);
+                assert_eq!(solutions.len(), 11);
+                for solution in solutions.iter() {
+                    assert_transfer(&r1, &r2, tof, solution);
+                    // the transfer direction follows the requested sense about z
+                    let h_z = r1.cross(&solution.v1)[2];
+                    assert_eq!(h_z > 0., prograde);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn revolutions_are_limited_by_the_time_of_flight() {
+        let r1 = vector![7000., 0., 0.];
+        let r2 = vector![0., 7500., 500.];
+        // a bit over a quarter of a 7000 km orbit allows no complete revolution
+        let solutions = lambert(&r1, &r2, 1800., MU, true, 5);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].branch, LambertBranch::Single);
+        assert_transfer(&r1, &r2, 1800., &solutions[0]);
+        assert!(lambert(&r1, &r2, -1800., MU, true, 5).is_empty());
+        assert!(lambert(&r1, &(2. * r1), 1800., MU, true, 5).is_empty());
+    }
+}
@@ -0,0 +1,371 @@
+use crate::centralbody::*;
+use crate::ephemeris::*;
+use nalgebra::*;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::rc::Rc;
+
+pub const GEOMAGNETIC_RADIUS: f64 = 6371.2; // km, reference radius of the geomagnetic models
+pub const IGRF_FILE: &str = "igrf13coeffs.txt";
+
+// degree 1 coefficients of IGRF-13 at 2020.0 (nT)
+const DIPOLE_G10: f64 = -29404.8;
+const DIPOLE_G11: f64 = -1450.9;
+const DIPOLE_H11: f64 = 4652.5;
+
+pub fn dipole_field(central_body: &CentralBody, r_eci: &Vector3<f64>, time: &f64) -> Vector3<f64> {
+    // tilted dipole field (T) in the inertial frame at position r_eci (km)
+    // B = (a / r)^3 [3 (m . r_hat) r_hat - m] with m = [g11, h11, g10] in the fixed frame
+    let eci2ecef = central_body.gen_eci2ecef(time);
+    let r_ecef = eci2ecef * r_eci;
+    let r = r_ecef.norm();
+    if r == 0. {
+        return Vector3::zeros();
+    }
+    let r_hat = r_ecef / r;
+    let moment = Vector3::new(DIPOLE_G11, DIPOLE_H11, DIPOLE_G10);
+    let b_ecef =
+        (GEOMAGNETIC_RADIUS / r).powi(3) * (3. * moment.dot(&r_hat) * r_hat - moment) * 1e-9;
+    eci2ecef.transpose() * b_ecef
+}
+
+#[derive(Clone, Debug)]
+pub struct IgrfModel {
+    // igrf spherical harmonic main field coefficients (nT, schmidt semi-normalized) for each
+    // model epoch, secular variation (nT/yr) is used past the last epoch
+    pub max_deg: usize,
+    pub epochs: Vec<f64>,      // decimal years
+    pub g: Vec<Vec<Vec<f64>>>, // [epoch][n][m]
+    pub h: Vec<Vec<Vec<f64>>>, // [epoch][n][m]
+    pub g_sv: Vec<Vec<f64>>,   // [n][m]
+    pub h_sv: Vec<Vec<f64>>,   // [n][m]
+}
+
+#[derive(Clone, Debug)]
+pub enum MagneticModel {
+    Dipole,
+    Igrf(Rc<IgrfModel>),
+}
+
+impl MagneticModel {
+    pub fn field(
+        &self,
+        central_body: &CentralBody,
+        r_eci: &Vector3<f64>,
+        time: &f64,
+    ) -> Vector3<f64> {
+        // T in the inertial frame
+        match self {
+            MagneticModel::Dipole => dipole_field(central_body, r_eci, time),
+            MagneticModel::Igrf(model) => model.field(central_body, r_eci, time),
+        }
+    }
+}
+
+impl IgrfModel {
+    pub fn read(file_path: &str) -> io::Result<Self> {
+        // igrf13coeffs.txt layout: '#' comments, a "g/h n m 1900.0 ... 2020.0 2020-25" header
+        // with the model epochs and the secular variation column last, then one "g n m ..." or
+        // "h n m ..." row per coefficient
+        let file = File::open(file_path)?;
+        let reader = BufReader::new(file);
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut epochs: Vec<f64> = vec![];
+        let mut rows: Vec<(bool, usize, usize, Vec<f64>)> = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.is_empty() || parts[0].starts_with('#') {
+                continue;
+            }
+            if parts[0] == "g/h" {
+                // the last column is the secular variation interval, not an epoch
+                epochs = parts[3..parts.len() - 1]
+                    .iter()
+                    .filter_map(|part| part.parse::<f64>().ok())
+                    .collect();
+                continue;
+            }
+            if (parts[0] == "g" || parts[0] == "h") && parts.len() > 3 {
+                let n = parts[1]
+                    .parse::<usize>()
+                    .map_err(|_| invalid("bad degree"))?;
+                let m = parts[2]
+                    .parse::<usize>()
+                    .map_err(|_| invalid("bad order"))?;
+                let values: Vec<f64> = parts[3..]
+                    .iter()
+                    .map(|part| part.parse::<f64>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| invalid("bad coefficient"))?;
+                rows.push((parts[0] == "g", n, m, values));
+            }
+        }
+        if epochs.is_empty() || rows.is_empty() {
+            return Err(invalid("no igrf coefficients found"));
+        }
+
+        let max_deg = rows.iter().map(|row| row.1).max().unwrap();
+        let zeros = vec![vec![0.; max_deg + 1]; max_deg + 1];
+        let mut model = IgrfModel {
+            max_deg,
+            epochs: epochs.clone(),
+            g: vec![zeros.clone(); epochs.len()],
+            h: vec![zeros.clone(); epochs.len()],
+            g_sv: zeros.clone(),
+            h_sv: zeros,
+        };
+        for (is_g, n, m, values) in rows.iter() {
+            if values.len() != epochs.len() + 1 || m > n {
+                return Err(invalid("coefficient row does not match the header"));
+            }
+            let (coefs, sv) = if *is_g {
+                (&mut model.g, &mut model.g_sv)
+            } else {
+                (&mut model.h, &mut model.h_sv)
+            };
+            for (k, value) in values[..epochs.len()].iter().enumerate() {
+                coefs[k][*n][*m] = *value;
+            }
+            sv[*n][*m] = values[epochs.len()];
+        }
+        Ok(model)
+    }
+
+    pub fn coefficients(&self, year: f64) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+        // linear interpolation between model epochs, secular variation past the last one
+        // (clamped to the first epoch before the model starts)
+        let last = self.epochs.len() - 1;
+        if year >= self.epochs[last] || last == 0 {
+            let dt = (year - self.epochs[last]).max(0.);
+            let extrapolate = |coefs: &Vec<Vec<f64>>, sv: &Vec<Vec<f64>>| -> Vec<Vec<f64>> {
+                coefs
+                    .iter()
+                    .zip(sv.iter())
+                    .map(|(row, sv_row)| {
+                        row.iter()
+                            .zip(sv_row.iter())
+                            .map(|(c, s)| c + s * dt)
+                            .collect()
+                    })
+                    .collect()
+            };
+            return (
+                extrapolate(&self.g[last], &self.g_sv),
+                extrapolate(&self.h[last], &self.h_sv),
+            );
+        }
+        let year = year.max(self.epochs[0]);
+        let k = self.epochs.partition_point(|epoch| *epoch <= year) - 1;
+        let frac = (year - self.epochs[k]) / (self.epochs[k + 1] - self.epochs[k]);
+        let interpolate = |coefs: &Vec<Vec<Vec<f64>>>| -> Vec<Vec<f64>> {
+            coefs[k]
+                .iter()
+                .zip(coefs[k + 1].iter())
+                .map(|(row0, row1)| {
+                    row0.iter()
+                        .zip(row1.iter())
+                        .map(|(c0, c1)| c0 + (c1 - c0) * frac)
+                        .collect()
+                })
+                .collect()
+        };
+        (interpolate(&self.g), interpolate(&self.h))
+    }
+
+    #[allow(non_snake_case)]
+    pub fn field(
+        &self,
+        central_body: &CentralBody,
+        r_eci: &Vector3<f64>,
+        time: &f64,
+    ) -> Vector3<f64> {
+        // main field (T) in the inertial frame at position r_eci (km), B = -grad V with
+        // V = a sum (a/r)^(n+1) sum (g cos(m lam) + h sin(m lam)) P_nm(sin(phi)), undefined
+        // exactly at the poles like the gravity model
+        let eci2ecef = central_body.gen_eci2ecef(time);
+        let x = eci2ecef * r_eci;
+        let r = x.norm();
+        if r == 0. {
+            return Vector3::zeros();
+        }
+        let year = 2000. + (time2jd(time) - J2000) / 365.25;
+        let (g, h) = self.coefficients(year);
+
+        let phi = (x[2] / r).asin();
+        let lam = x[1].atan2(x[0]);
+        let tan_phi = phi.tan();
+
+        // schmidt semi-normalized functions are the fully normalized ones over sqrt(2n + 1),
+        // the derivative relation with the scale factors holds for both
+        let (P, scale_factor) = central_body.norm_legendre(phi, self.max_deg, self.max_deg);
+
+        let r_ratio = GEOMAGNETIC_RADIUS / r;
+        let mut r_ratio_n = r_ratio.powi(2);
+        let mut B_r = 0.;
+        let mut B_phi = 0.;
+        let mut B_lam = 0.;
+        for n in 1..=self.max_deg {
+            r_ratio_n *= r_ratio;
+            let nf = n as f64;
+            let schmidt = 1. / (2. * nf + 1.).sqrt();
+            let mut sum_r = 0.;
+            let mut sum_phi = 0.;
+            let mut sum_lam = 0.;
+            for m in 0..=n {
+                let mf = m as f64;
+                let (sin_m, cos_m) = (mf * lam).sin_cos();
+                let S = P[n][m] * schmidt;
+                let dS = (P[n][m + 1] * scale_factor[n][m] - mf * tan_phi * P[n][m]) * schmidt;
+                let gh = g[n][m] * cos_m + h[n][m] * sin_m;
+                sum_r += gh * S;
+                sum_phi += gh * dS;
+                sum_lam += mf * (g[n][m] * sin_m - h[n][m] * cos_m) * S;
+            }
+            B_r += (nf + 1.) * r_ratio_n * sum_r;
+            B_phi -= r_ratio_n * sum_phi;
+            B_lam += r_ratio_n * sum_lam;
+        }
+        B_lam /= phi.cos();
+
+        // local up, north, east to the fixed frame
+        let (sphi, cphi) = phi.sin_cos();
+        let (slam, clam) = lam.sin_cos();
+        let up = Vector3::new(cphi * clam, cphi * slam, sphi);
+        let north = Vector3::new(-sphi * clam, -sphi * slam, cphi);
+        let east = Vector3::new(-slam, clam, 0.);
+        let b_ecef = (B_r * up + B_phi * north + B_lam * east) * 1e-9;
+        eci2ecef.transpose() * b_ecef
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // igrf-13 degree 1 and 2 rows for the last two model epochs, laid out as in igrf13coeffs.txt
+    const IGRF13_EXCERPT: &str = "\
+# 13th Generation International Geomagnetic Reference Field Schmidt semi-normalised spherical harmonic coefficients, degree n=1,13
+c/s deg ord DGRF IGRF SV
+g/h n m 2015.0 2020.0 2020-25
+g  1  0 -29441.46 -29404.8   5.7
+g  1  1  -1501.77  -1450.9   7.4
+h  1  1   4795.99   4652.5 -25.9
+g  2  0  -2445.88  -2499.6 -11.0
+g  2  1   3012.20   2982.0  -7.0
+h  2  1  -2845.41  -2991.6 -30.2
+g  2  2   1676.35   1677.0  -2.1
+h  2  2   -642.17   -734.6 -22.4
+";
+
+    fn read_text(name: &str, text: &str) -> io::Result<IgrfModel> {
+        let path = std::env::temp_dir().join(format!("rbody_{}.txt", name));
+        std::fs::write(&path, text)?;
+        let model = IgrfModel::read(path.to_str().unwrap());
+        std::fs::remove_file(&path)?;
+        model
+    }
+
+    fn potential(g: &[Vec<f64>], h: &[Vec<f64>], x: &Vector3<f64>) -> f64 {
+        // degree 2 main field potential (nT km) with the schmidt functions written out
+        let a = GEOMAGNETIC_RADIUS;
+        let r = x.norm();
+        let (s, c) = (x[2] / r, (x[0].powi(2) + x[1].powi(2)).sqrt() / r);
+        let lam = x[1].atan2(x[0]);
+        let p = [
+            vec![1.],
+            vec![s, c],
+            vec![
+                (3. * s * s - 1.) / 2.,
+                3_f64.sqrt() * s * c,
+                3_f64.sqrt() / 2. * c * c,
+            ],
+        ];
+        let mut v = 0.;
+        for n in 1..=2 {
+            for m in 0..=n {
+                let (sin_m, cos_m) = (m as f64 * lam).sin_cos();
+                v += a * (a / r).powi(n as i32 + 1) * (g[n][m] * cos_m + h[n][m] * sin_m) * p[n][m];
+            }
+        }
+        v
+    }
+
+    #[test]
+    fn reads_the_coefficient_table() {
+        let model = read_text("igrf_excerpt", IGRF13_EXCERPT).unwrap();
+        assert_eq!(model.max_deg, 2);
+        assert_eq!(model.epochs, vec![2015., 2020.]);
+        assert_eq!(model.h[1][2][1], -2991.6);
+        assert_eq!(model.g_sv[1][0], 5.7);
+        // halfway between the epochs and 2.5 years of secular variation past the last
+        let (g, h) = model.coefficients(2017.5);
+        assert!((g[1][0] - (-29441.46 - 29404.8) / 2.).abs() < 1e-9);
+        assert!((h[2][2] - (-642.17 - 734.6) / 2.).abs() < 1e-9);
+        let (g, h) = model.coefficients(2022.5);
+        assert!((g[1][1] - (-1450.9 + 2.5 * 7.4)).abs() < 1e-9);
+        assert!((h[1][1] - (4652.5 - 2.5 * 25.9)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn field_is_minus_the_gradient_of_the_potential() {
+        let model = read_text("igrf_gradient", IGRF13_EXCERPT).unwrap();
+        let central_body = CentralBody::new();
+        let time = 20. * 365.25 * 86400.; // 2020.0
+        let (g, h) = model.coefficients(2020.);
+        let x = vector![4100., -2900., 4500.];
+        let b = model.field(&central_body, &x, &time) * 1e9;
+        let step = 1e-2;
+        for axis in 0..3 {
+            let mut dx = Vector3::zeros();
+            dx[axis] = step;
+            let gradient =
+                (potential(&g, &h, &(x + dx)) - potential(&g, &h, &(x - dx))) / (2. * step);
+            assert!(
+                (b[axis] + gradient).abs() < 1e-5,
+                "{} {}",
+                b[axis],
+                -gradient
+            );
+        }
+    }
+
+    #[test]
+    fn degree_one_is_the_dipole() {
+        let dipole_rows: String = IGRF13_EXCERPT
+            .lines()
+            .filter(|line| !line.starts_with("g  2") && !line.starts_with("h  2"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let model = read_text("igrf_dipole", &dipole_rows).unwrap();
+        assert_eq!(model.max_deg, 1);
+        let mut central_body = CentralBody::new();
+        central_body.omega = 7.292115e-5;
+        let time = 20. * 365.25 * 86400.; // 2020.0, the epoch of the dipole coefficients
+        for x in [
+            vector![7000., 0., 0.],
+            vector![-3000., 5000., -4000.],
+            vector![100., 200., 6800.],
+        ] {
+            let igrf = model.field(&central_body, &x, &time);
+            let dipole = dipole_field(&central_body, &x, &time);
+            assert!((igrf - dipole).norm() < 1e-12 * dipole.norm());
+        }
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        let bad_value = IGRF13_EXCERPT.replace("-2445.88", "-2445.8x");
+        assert!(read_text("igrf_bad_value", &bad_value).is_err());
+        let short_row = IGRF13_EXCERPT.replace("-734.6 -22.4", "-734.6");
+        assert!(read_text("igrf_short_row", &short_row).is_err());
+        let no_header: String = IGRF13_EXCERPT
+            .lines()
+            .filter(|line| !line.starts_with("g/h"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert!(read_text("igrf_no_header", &no_header).is_err());
+        assert!(IgrfModel::read("no_such_igrf_file.txt").is_err());
+    }
+}
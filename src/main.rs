@@ -1,14 +1,26 @@
 #[allow(unused_imports)]
 use std::f64::consts::*;
+mod adcs;
 mod app;
 mod attitude;
 mod centralbody;
 mod dynamical_system;
 mod eoms;
+mod ephemeris;
+mod epoch;
+mod frames;
+mod geodetic;
+mod kepler;
+mod lambert;
+mod magnetic;
+mod maneuver;
 mod math;
+mod mean_elements;
 mod orbitalelements;
 mod otherbody;
 mod satbody;
+mod sgp4;
+mod tle;
 // use math as ma; // use math with ma:: notation
 // use math::*; // use math without math:: notation
 // use attitude::*;
@@ -65,7 +77,6 @@ fn main() -> eframe::Result<()> {
     //     mu: 4.9048695e12,
     //     id: 1,
     //     pos_old: Vector3::zeros(),
-    //     propagate_flag: true,
     //     state: vector![moon_distance_from_earth, 0., 0., 0., moonv0, 0.,], // Assuming moon starts on the x-axis and other velocities will be set elsewhere
     //     state_history: vec![],
     //     time_history: vec![],
@@ -77,7 +88,6 @@ fn main() -> eframe::Result<()> {
     //     mu: 4.9048695e12,
     //     id: 2,
     //     pos_old: Vector3::zeros(),
-    //     propagate_flag: true,
     //     state: vector![-moon_distance_from_earth, 0., 0., 0., -moonv0, 0.,], // Assuming moon starts on the x-axis and other velocities will be set elsewhere
     //     state_history: vec![],
     //     time_history: vec![],
@@ -101,7 +111,6 @@ fn main() -> eframe::Result<()> {
     //         moonv0 + v0_sat,
     //         0.,
     //     ],
-    //     propagate_flag: true,
     //     state_history: vec![],
     //     time_history: vec![time_0],
     // };
@@ -116,7 +125,6 @@ fn main() -> eframe::Result<()> {
     //         v0 * 0.5.sin(),
     //         v0 * 0.5.cos(),
     //     ], // m, m/s
-    //     propagate_flag: true,
     //     state_history: vec![],
     //     time_history: vec![time_0],
     // };
@@ -132,7 +140,6 @@ fn main() -> eframe::Result<()> {
     //         -(moonv0 + v0_sat),
     //         0.,
     //     ],
-    //     propagate_flag: true,
     //     state_history: vec![],
     //     time_history: vec![time_0],
     // };
@@ -207,8 +214,11 @@ fn main() -> eframe::Result<()> {
 //         0.230813085987612,
 //         0.948979454430948
 //     ])),
+//     mrp: None,
+//     crp: None,
+//     prv: None,
 //     dcm: Matrix3::zeros(),
-//     main_rep: String::from("quat"),
+//     main_rep: AttitudeRep::Quat,
 // };
 // sat1_attitude.gen_dcm();
 //
@@ -218,8 +228,11 @@ fn main() -> eframe::Result<()> {
 //         [3, 2, 1],
 //     )),
 //     quat: None,
+//     mrp: None,
+//     crp: None,
+//     prv: None,
 //     dcm: Matrix3::zeros(),
-//     main_rep: String::from("euler"),
+//     main_rep: AttitudeRep::Euler,
 // };
 // sat2_attitude.gen_dcm();
 //
@@ -0,0 +1,365 @@
+use crate::math::*;
+use nalgebra::*;
+use std::fmt;
+use std::ops::AddAssign;
+use std::rc::Rc;
+
+const G0: f64 = 9.80665; // m/s^2, standard gravity used with Isp
+const MASS_TOL: f64 = 1e-9; // kg, propellant left below which the tanks count as empty
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(dead_code)]
+pub enum ManeuverFrame {
+    Inertial, // delta v given directly in the inertial frame
+    Vnb,      // velocity, orbit normal, binormal
+    Rtn,      // radial, transverse, orbit normal
+}
+
+#[derive(Clone, Debug)]
+pub struct ImpulsiveManeuver {
+    pub epoch: f64, // s, simulation time at which the burn is applied
+    pub delta_v: Vector3<f64>,
+    pub frame: ManeuverFrame,
+    pub applied: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ManeuverEvent {
+    Impulsive,          // impulsive delta v applied
+    PropellantDepleted, // finite burns cut off at the dry mass
+    Skipped,            // impulsive maneuver before the propagation start, never applied
+}
+
+#[derive(Clone, Debug)]
+pub struct ManeuverRecord {
+    pub epoch: f64,
+    pub delta_v: Vector3<f64>, // inertial delta v that was actually applied
+    pub frame: ManeuverFrame,  // frame the maneuver was specified in
+    pub state: Vector6<f64>,   // state right after the burn
+    pub event: ManeuverEvent,
+}
+
+// user supplied thrust direction from (state, time), does not need to be normalized
+pub type SteeringFn = Rc<dyn Fn(&Vector6<f64>, f64) -> Vector3<f64>>;
+
+#[derive(Clone)]
+#[allow(dead_code)]
+pub enum SteeringLaw {
+    VelocityAligned,
+    AntiVelocity,
+    InertiallyFixed(Vector3<f64>),
+    Custom(SteeringFn),
+}
+
+#[derive(Clone, Debug)]
+pub struct FiniteBurn {
+    pub start: f64,    // s, simulation time the burn starts
+    pub duration: f64, // s
+    pub thrust: f64,   // N
+    pub isp: f64,      // s
+    pub steering: SteeringLaw,
+}
+
+#[derive(Clone, Debug)]
+pub struct ManeuverPlan {
+    pub impulsive: Vec<ImpulsiveManeuver>,
+    pub finite: Vec<FiniteBurn>,
+    pub dry_mass: f64, // kg, finite burns stop once the mass is down to it
+    pub log: Vec<ManeuverRecord>,
+}
+
+impl fmt::Debug for SteeringLaw {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SteeringLaw::VelocityAligned => write!(f, "VelocityAligned"),
+            SteeringLaw::AntiVelocity => write!(f, "AntiVelocity"),
+            SteeringLaw::InertiallyFixed(dir) => {
+                write!(f, "InertiallyFixed([{}, {}, {}])", dir[0], dir[1], dir[2])
+            }
+            SteeringLaw::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl ImpulsiveManeuver {
+    pub fn new(epoch: f64, delta_v: Vector3<f64>, frame: ManeuverFrame) -> Self {
+        Self {
+            epoch,
+            delta_v,
+            frame,
+            applied: false,
+        }
+    }
+
+    pub fn inertial_delta_v(&self, state: &Vector6<f64>) -> Vector3<f64> {
+        // rotate the delta v into the inertial frame using the state at the maneuver epoch
+        let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+        let v_vec: Vector3<f64> = state.fixed_rows::<3>(3).into();
+        match self.frame {
+            ManeuverFrame::Inertial => self.delta_v,
+            ManeuverFrame::Vnb => vnb2eci(&r_vec, &v_vec) * self.delta_v,
+            ManeuverFrame::Rtn => rtn2eci(&r_vec, &v_vec) * self.delta_v,
+        }
+    }
+
+    pub fn apply(&self, state: &Vector6<f64>) -> Vector6<f64> {
+        let mut state_new = *state;
+        let dv = self.inertial_delta_v(state);
+        state_new.fixed_rows_mut::<3>(3).add_assign(&dv);
+        state_new
+    }
+}
+
+#[allow(dead_code)]
+impl SteeringLaw {
+    pub fn direction(&self, state: &Vector6<f64>, time: f64) -> Vector3<f64> {
+        // unit thrust direction in the inertial frame
+        let v_vec: Vector3<f64> = state.fixed_rows::<3>(3).into();
+        let dir = match self {
+            SteeringLaw::VelocityAligned => v_vec,
+            SteeringLaw::AntiVelocity => -v_vec,
+            SteeringLaw::InertiallyFixed(dir) => *dir,
+            SteeringLaw::Custom(law) => law(state, time),
+        };
+        let dir_mag = dir.norm();
+        if dir_mag > 0. {
+            dir / dir_mag
+        } else {
+            Vector3::zeros()
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl FiniteBurn {
+    pub fn new(start: f64, duration: f64, thrust: f64, isp: f64, steering: SteeringLaw) -> Self {
+        Self {
+            start,
+            duration,
+            thrust,
+            isp,
+            steering,
+        }
+    }
+
+    pub fn end(&self) -> f64 {
+        self.start + self.duration
+    }
+
+    pub fn is_active(&self, time: f64) -> bool {
+        time >= self.start && time < self.end()
+    }
+
+    pub fn mass_flow(&self) -> f64 {
+        // kg/s of propellant used while the burn is active
+        self.thrust / (self.isp * G0)
+    }
+
+    pub fn acceleration(&self, state: &Vector6<f64>, mass: f64, time: f64) -> Vector3<f64> {
+        // thrust acceleration in km/s^2 (thrust is in N, states are in km)
+        if !self.is_active(time) || mass <= 0. {
+            return Vector3::zeros();
+        }
+        self.steering.direction(state, time) * (self.thrust / mass / 1000.)
+    }
+}
+
+#[allow(dead_code)]
+impl ManeuverPlan {
+    pub fn new() -> Self {
+        ManeuverPlan {
+            impulsive: vec![],
+            finite: vec![],
+            dry_mass: 0.,
+            log: vec![],
+        }
+    }
+
+    pub fn add_finite(&mut self, burn: FiniteBurn) {
+        self.finite.push(burn);
+    }
+
+    pub fn thrust(&self, state: &Vector6<f64>, mass: f64, time: f64) -> (Vector3<f64>, f64) {
+        // total thrust acceleration and mass rate of change from every active burn
+        let mut accel = Vector3::zeros();
+        let mut mass_dot = 0.;
+        if mass <= self.dry_mass + MASS_TOL {
+            return (accel, mass_dot);
+        }
+        for burn in self.finite.iter().filter(|b| b.is_active(time)) {
+            accel += burn.acceleration(state, mass, time);
+            mass_dot -= burn.mass_flow();
+        }
+        (accel, mass_dot)
+    }
+
+    pub fn breakpoints(&self, t_start: f64, t_end: f64) -> Vec<f64> {
+        // times inside [t_start, t_end) where the dynamics are discontinuous (impulsive
+        // maneuvers, burn ignition and cutoff), sorted and without duplicates
+        let mut times: Vec<f64> = self
+            .impulsive
+            .iter()
+            .filter(|m| !m.applied && m.epoch >= t_start && m.epoch < t_end)
+            .map(|m| m.epoch)
+            .collect();
+        for burn in self.finite.iter() {
+            for edge in [burn.start, burn.end()] {
+                if edge > t_start && edge < t_end {
+                    times.push(edge);
+                }
+            }
+        }
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        times.dedup();
+        times
+    }
+
+    pub fn depletion_time(&self, t_start: f64, t_end: f64, mass: f64) -> Option<f64> {
+        // time inside (t_start, t_end) at which the active burns use up the propellant above
+        // the dry mass, the mass flow is piecewise constant between burn edges
+        let mut propellant = mass - self.dry_mass;
+        if propellant <= MASS_TOL {
+            return None;
+        }
+        let mut edges = self.breakpoints(t_start, t_end);
+        edges.push(t_end);
+        let mut time = t_start;
+        for edge in edges.into_iter().filter(|edge| *edge > t_start) {
+            let flow = self
+                .finite
+                .iter()
+                .filter(|b| b.is_active(time))
+                .map(|b| b.mass_flow())
+                .sum::<f64>();
+            if flow > 0. && propellant <= flow * (edge - time) {
+                return Some(time + propellant / flow);
+            }
+            propellant -= flow * (edge - time);
+            time = edge;
+        }
+        None
+    }
+
+    pub fn log_depletion(&mut self, time: f64, state: &Vector6<f64>) {
+        self.log.push(ManeuverRecord {
+            epoch: time,
+            delta_v: Vector3::zeros(),
+            frame: ManeuverFrame::Inertial,
+            state: *state,
+            event: ManeuverEvent::PropellantDepleted,
+        });
+    }
+
+    pub fn add_impulsive(&mut self, epoch: f64, delta_v: Vector3<f64>, frame: ManeuverFrame) {
+        // keep maneuvers sorted by epoch so they are applied in order within a step
+        let maneuver = ImpulsiveManeuver::new(epoch, delta_v, frame);
+        let index = self.impulsive.partition_point(|m| m.epoch <= epoch);
+        self.impulsive.insert(index, maneuver);
+    }
+
+    pub fn pending(&self, t_start: f64, t_end: f64) -> Vec<(usize, ImpulsiveManeuver)> {
+        // (index, maneuver) for maneuvers that have not been applied yet and fall inside
+        // [t_start, t_end)
+        self.impulsive
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| !m.applied && m.epoch >= t_start && m.epoch < t_end)
+            .map(|(index, m)| (index, m.clone()))
+            .collect()
+    }
+
+    pub fn mark_applied(&mut self, index: usize, record: ManeuverRecord) {
+        // index into impulsive as returned by pending
+        self.impulsive[index].applied = true;
+        self.log.push(record);
+    }
+
+    pub fn remove_missed(&mut self, time: f64, state: &Vector6<f64>) {
+        // maneuvers that were never applied and are already in the past at time can not be
+        // executed anymore, they are taken out of the plan and logged as skipped with the
+        // state at time
+        let (missed, kept): (Vec<ImpulsiveManeuver>, _) = std::mem::take(&mut self.impulsive)
+            .into_iter()
+            .partition(|m| !m.applied && m.epoch < time);
+        self.impulsive = kept;
+        for maneuver in missed {
+            self.log.push(ManeuverRecord {
+                epoch: maneuver.epoch,
+                delta_v: Vector3::zeros(),
+                frame: maneuver.frame,
+                state: *state,
+                event: ManeuverEvent::Skipped,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maneuvers_sharing_an_epoch_are_all_applied() {
+        let mut plan = ManeuverPlan::new();
+        plan.add_impulsive(10., Vector3::new(0.1, 0., 0.), ManeuverFrame::Inertial);
+        plan.add_impulsive(10., Vector3::new(0., 0.2, 0.), ManeuverFrame::Inertial);
+        let pending = plan.pending(0., 20.);
+        assert_eq!(pending.len(), 2);
+        for (index, maneuver) in pending {
+            let state = maneuver.apply(&Vector6::zeros());
+            plan.mark_applied(
+                index,
+                ManeuverRecord {
+                    epoch: maneuver.epoch,
+                    delta_v: maneuver.delta_v,
+                    frame: maneuver.frame,
+                    state,
+                    event: ManeuverEvent::Impulsive,
+                },
+            );
+        }
+        assert!(plan.impulsive.iter().all(|m| m.applied));
+        assert_eq!(plan.log.len(), 2);
+        assert!(plan.pending(0., 20.).is_empty());
+    }
+
+    #[test]
+    fn depletion_time_stops_at_the_dry_mass() {
+        let mut plan = ManeuverPlan::new();
+        plan.dry_mass = 60.;
+        plan.add_finite(FiniteBurn::new(
+            100.,
+            1e6,
+            10.,
+            300.,
+            SteeringLaw::VelocityAligned,
+        ));
+        let flow = plan.finite[0].mass_flow();
+        let expected = 100. + 40. / flow;
+        assert_eq!(plan.depletion_time(0., 1e3, 100.), None);
+        let depletion = plan.depletion_time(0., 2e4, 100.).unwrap();
+        assert!((depletion - expected).abs() < 1e-9);
+        let state = Vector6::new(7000., 0., 0., 0., 7.5, 0.);
+        let (accel, mass_dot) = plan.thrust(&state, 60., 200.);
+        assert_eq!(accel, Vector3::zeros());
+        assert_eq!(mass_dot, 0.);
+        let (accel, mass_dot) = plan.thrust(&state, 61., 200.);
+        assert!(accel.norm() > 0.);
+        assert!((mass_dot + flow).abs() < 1e-15);
+    }
+
+    #[test]
+    fn past_maneuvers_are_removed() {
+        let mut plan = ManeuverPlan::new();
+        plan.add_impulsive(-5., Vector3::new(0.1, 0., 0.), ManeuverFrame::Inertial);
+        plan.add_impulsive(5., Vector3::new(0.1, 0., 0.), ManeuverFrame::Inertial);
+        plan.remove_missed(0., &Vector6::zeros());
+        assert_eq!(plan.log.len(), 1);
+        assert_eq!(plan.log[0].epoch, -5.);
+        assert_eq!(plan.log[0].event, ManeuverEvent::Skipped);
+        assert_eq!(plan.log[0].delta_v, Vector3::zeros());
+        assert_eq!(plan.impulsive.len(), 1);
+        assert_eq!(plan.impulsive[0].epoch, 5.);
+    }
+}
@@ -1,7 +1,11 @@
+use crate::orbitalelements::*;
 use nalgebra::*;
 use std::f64::consts::*;
 use std::fmt::Display;
 use std::ops::Mul;
+
+const COE_TOL: f64 = 1e-8; // eccentricity and inclination below which angles are undefined
+
 // pub fn magnitude(vector: &Vec<f64>) -> f64 {
 //     let mut vector_mag: f64 = 0.;
 //
@@ -26,9 +30,9 @@ where
 }
 
 #[allow(dead_code)]
-pub fn normalize(vector: &Vec<f64>) -> Vec<f64> {
+pub fn normalize(vector: &[f64]) -> Vec<f64> {
     let mut normalized_vector = vec![0.0; vector.len()];
-    let vector_mag = magnitude(&vector);
+    let vector_mag = magnitude(vector);
     for i in 0..vector.len() {
         normalized_vector[i] = vector[i] / vector_mag;
     }
@@ -37,7 +41,7 @@ pub fn normalize(vector: &Vec<f64>) -> Vec<f64> {
 
 // TODO: adjust to allow vec<vec<>> AND vec<> input for the second value
 #[allow(dead_code)]
-pub fn matmul(a: &Vec<Vec<f64>>, b: &Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+pub fn matmul(a: &[Vec<f64>], b: &[Vec<f64>]) -> Vec<Vec<f64>> {
     let a_rows = a.len();
     let a_cols = a[0].len();
     // let b_rows = b.len();
@@ -65,7 +69,7 @@ pub fn cross_operator(vector: Vec<f64>) -> Vec<Vec<f64>> {
     ]
 }
 #[allow(dead_code)]
-pub fn colvec(vector: &Vec<f64>) -> Vec<Vec<f64>> {
+pub fn colvec(vector: &[f64]) -> Vec<Vec<f64>> {
     let mut newvec = vec![vec![0.]; vector.len()];
     for i in 0..vector.len() {
         newvec[i][0] = vector[i];
@@ -86,7 +90,7 @@ pub fn print_smatrix<T: Display, const R: usize, const C: usize>(mat: &SMatrix<T
             println!();
         }
     }
-    println!("");
+    println!();
 }
 
 #[allow(dead_code)]
@@ -105,7 +109,7 @@ pub fn print_dmatrix<T: Display>(mat: &DMatrix<T>) {
             println!();
         }
     }
-    println!("");
+    println!();
 }
 
 #[allow(dead_code)]
@@ -118,133 +122,503 @@ pub fn print_vec_of_vecs<T: std::fmt::Display>(vecs: &Vec<Vec<T>>) {
     }
 }
 
-#[allow(non_snake_case, dead_code)]
-pub fn state2coes(state: Vector6<f64>, mu: f64) -> [f64; 7] {
-    let mut r_vec = Vector3::zeros();
-    let mut v_vec = Vector3::zeros();
-    r_vec
-        .fixed_rows_mut::<3usize>(0)
-        .copy_from(&state.fixed_rows::<3usize>(0)); // consistently faster
-    v_vec
-        .fixed_rows_mut::<3usize>(0)
-        .copy_from(&state.fixed_rows::<3usize>(3));
+pub fn state2coes(state: &Vector6<f64>, mu: f64) -> OrbitalElements {
+    // classical orbital elements from an inertial state, angles in [0, 2pi)
+    // undefined angles are set to zero and absorbed by the next one: circular orbits use the
+    // argument of latitude as the true anomaly, equatorial orbits (prograde or retrograde)
+    // measure from the inertial x axis instead of the node (longitude of periapsis in aop,
+    // true longitude in ta when also circular). sma is negative for hyperbolic and infinite
+    // for parabolic orbits, param = h^2 / mu is always defined
+    let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+    let v_vec: Vector3<f64> = state.fixed_rows::<3>(3).into();
+    let r_mag = r_vec.norm();
 
     let h_vec = r_vec.cross(&v_vec);
+    let h_mag = h_vec.norm();
+    let h_hat = h_vec / h_mag;
+    let n_vec = Vector3::new(-h_vec[1], h_vec[0], 0.); // z cross h
+    let e_vec = ((v_vec.norm_squared() - mu / r_mag) * r_vec - r_vec.dot(&v_vec) * v_vec) / mu;
+    let ecc = e_vec.norm();
 
-    let h_mag: f64 = h_vec.norm();
-    let v_mag = v_vec.norm();
-    let r_mag = r_vec.norm();
-    let n_vec = Vector3::new(0., 0., 1.).cross(&h_vec);
-    let n_mag = n_vec.norm();
-    let e_vec = ((v_mag.powi(2) - mu / r_mag) * r_vec - r_vec.dot(&v_vec) * v_vec) / mu;
-    let e = e_vec.norm();
-
-    let energy = v_mag.powi(2) / 2. - mu / r_mag;
-    let a: f64;
-    let p: f64;
-    if e != 1.0 {
-        a = -mu / 2. / energy;
-        p = a * (1. - e.powi(2));
+    let param = h_mag.powi(2) / mu;
+    let sma = if (ecc - 1.).abs() < COE_TOL {
+        f64::INFINITY
+    } else {
+        param / (1. - ecc.powi(2))
+    };
+    let inc = (h_hat[2]).clamp(-1., 1.).acos();
+
+    // angle from a to b measured about the orbit normal
+    let angle = |a: &Vector3<f64>, b: &Vector3<f64>| -> f64 {
+        h_hat.dot(&a.cross(b)).atan2(a.dot(b)).rem_euclid(2. * PI)
+    };
+
+    let equatorial = inc < COE_TOL || PI - inc < COE_TOL;
+    let circular = ecc < COE_TOL;
+    let (raan, node_hat) = if equatorial {
+        (0., Vector3::x())
+    } else {
+        (
+            n_vec[1].atan2(n_vec[0]).rem_euclid(2. * PI),
+            n_vec.normalize(),
+        )
+    };
+    let (aop, periapsis_hat) = if circular {
+        (0., node_hat)
+    } else {
+        (angle(&node_hat, &e_vec), e_vec / ecc)
+    };
+    let ta = angle(&periapsis_hat, &r_vec);
+
+    OrbitalElements {
+        sma,
+        ecc,
+        inc,
+        raan,
+        aop,
+        ta,
+        param,
+    }
+}
+
+pub fn coes2state(coes: &OrbitalElements, mu: f64) -> Vector6<f64> {
+    // inertial state from classical orbital elements using the same conventions as state2coes,
+    // param is used when it is set so parabolic orbits work, otherwise it comes from sma
+    let OrbitalElements {
+        sma,
+        ecc,
+        inc,
+        raan,
+        aop,
+        ta,
+        param,
+    } = *coes;
+    let p = if param > 0. {
+        param
+    } else {
+        sma * (1. - ecc.powi(2))
+    };
+
+    let r_pqw = vector![
+        p * ta.cos() / (1. + ecc * ta.cos()),
+        p * ta.sin() / (1. + ecc * ta.cos()),
+        0.
+    ];
+    let v_pqw = vector![
+        -(mu / p).sqrt() * ta.sin(),
+        (mu / p).sqrt() * (ecc + ta.cos()),
+        0.
+    ];
+
+    let pqw2eci = matrix![
+        raan.cos() * aop.cos() - raan.sin() * aop.sin() * inc.cos(), -raan.cos() * aop.sin() - raan.sin() * aop.cos() * inc.cos(), raan.sin() * inc.sin();
+        raan.sin() * aop.cos() + raan.cos() * aop.sin() * inc.cos(), -raan.sin() * aop.sin() + raan.cos() * aop.cos() * inc.cos(), -raan.cos() * inc.sin();
+        aop.sin() * inc.sin(), aop.cos() * inc.sin(), inc.cos();];
+
+    let mut state = Vector6::zeros();
+    state.fixed_rows_mut::<3>(0).copy_from(&(pqw2eci * r_pqw));
+    state.fixed_rows_mut::<3>(3).copy_from(&(pqw2eci * v_pqw));
+    state
+}
+
+pub fn mean2eccentric(mean_anom: f64, ecc: f64) -> f64 {
+    // eccentric anomaly from kepler's equation M = E - e sin(E), elliptic orbits
+    let mean_anom = (mean_anom + PI).rem_euclid(2. * PI) - PI;
+    let mut ecc_anom = if ecc < 0.8 {
+        mean_anom
     } else {
-        p = h_mag.powi(2) / mu;
-        a = f64::INFINITY;
+        PI.copysign(mean_anom)
+    };
+    for _ in 0..50 {
+        let step = (ecc_anom - ecc * ecc_anom.sin() - mean_anom) / (1. - ecc * ecc_anom.cos());
+        ecc_anom -= step;
+        if step.abs() < 1e-14 {
+            break;
+        }
     }
+    ecc_anom
+}
 
-    let i = (h_vec[2] / h_mag).acos();
-    let mut RAAN = (n_vec[0] / n_mag).acos();
-    if n_vec[1] < 0. {
-        RAAN = 2. * PI - RAAN;
+fn equinoctial_frame(h: f64, k: f64) -> (Vector3<f64>, Vector3<f64>) {
+    // in plane unit vectors f and g of the equinoctial frame from the mee h and k
+    let s_sq = 1. + h.powi(2) + k.powi(2);
+    let f_hat = Vector3::new(1. - k.powi(2) + h.powi(2), 2. * h * k, -2. * k) / s_sq;
+    let g_hat = Vector3::new(2. * h * k, 1. + k.powi(2) - h.powi(2), 2. * h) / s_sq;
+    (f_hat, g_hat)
+}
+
+pub fn state2mee(state: &Vector6<f64>, mu: f64) -> ModifiedEquinoctialElements {
+    // modified equinoctial elements straight from the state, true longitude in [0, 2pi)
+    let r_vec: Vector3<f64> = state.fixed_rows::<3>(0).into();
+    let v_vec: Vector3<f64> = state.fixed_rows::<3>(3).into();
+    let h_vec = r_vec.cross(&v_vec);
+    let h_hat = h_vec.normalize();
+    let e_vec =
+        ((v_vec.norm_squared() - mu / r_vec.norm()) * r_vec - r_vec.dot(&v_vec) * v_vec) / mu;
+
+    let h = -h_hat[1] / (1. + h_hat[2]);
+    let k = h_hat[0] / (1. + h_hat[2]);
+    let (f_hat, g_hat) = equinoctial_frame(h, k);
+    ModifiedEquinoctialElements {
+        param: h_vec.norm_squared() / mu,
+        f: e_vec.dot(&f_hat),
+        g: e_vec.dot(&g_hat),
+        h,
+        k,
+        true_lon: r_vec
+            .dot(&g_hat)
+            .atan2(r_vec.dot(&f_hat))
+            .rem_euclid(2. * PI),
     }
-    if RAAN.is_nan() {
-        RAAN = 0.;
+}
+
+pub fn mee2state(mee: &ModifiedEquinoctialElements, mu: f64) -> Vector6<f64> {
+    let (f_hat, g_hat) = equinoctial_frame(mee.h, mee.k);
+    let (sin_l, cos_l) = mee.true_lon.sin_cos();
+    let r_mag = mee.param / (1. + mee.f * cos_l + mee.g * sin_l);
+    let r_vec = r_mag * (cos_l * f_hat + sin_l * g_hat);
+    let v_vec = (mu / mee.param).sqrt() * (-(mee.g + sin_l) * f_hat + (mee.f + cos_l) * g_hat);
+
+    let mut state = Vector6::zeros();
+    state.fixed_rows_mut::<3>(0).copy_from(&r_vec);
+    state.fixed_rows_mut::<3>(3).copy_from(&v_vec);
+    state
+}
+
+pub fn mee2equinoctial(mee: &ModifiedEquinoctialElements) -> EquinoctialElements {
+    // elliptic orbits only, the mean longitude comes from kepler's equation
+    let ecc_sq = mee.f.powi(2) + mee.g.powi(2);
+    let ecc = ecc_sq.sqrt();
+    let lon_peri = mee.g.atan2(mee.f);
+    let ta = mee.true_lon - lon_peri;
+    let ecc_anom = 2. * (((1. - ecc) / (1. + ecc)).sqrt() * (ta / 2.).tan()).atan();
+    let mean_anom = ecc_anom - ecc * ecc_anom.sin();
+    EquinoctialElements {
+        sma: mee.param / (1. - ecc_sq),
+        h: mee.g,
+        k: mee.f,
+        p: mee.k,
+        q: mee.h,
+        mean_lon: (lon_peri + mean_anom).rem_euclid(2. * PI),
     }
-    let mut AOP = (n_vec.dot(&e_vec) / (n_mag * e)).acos();
-    if e_vec[2] < 0. {
-        AOP = 2. * PI - AOP;
+}
+
+pub fn equinoctial2mee(elements: &EquinoctialElements) -> ModifiedEquinoctialElements {
+    let ecc_sq = elements.h.powi(2) + elements.k.powi(2);
+    let ecc = ecc_sq.sqrt();
+    let lon_peri = elements.h.atan2(elements.k);
+    let ecc_anom = mean2eccentric(elements.mean_lon - lon_peri, ecc);
+    let ta = 2. * (((1. + ecc) / (1. - ecc)).sqrt() * (ecc_anom / 2.).tan()).atan();
+    ModifiedEquinoctialElements {
+        param: elements.sma * (1. - ecc_sq),
+        f: elements.k,
+        g: elements.h,
+        h: elements.q,
+        k: elements.p,
+        true_lon: (lon_peri + ta).rem_euclid(2. * PI),
     }
-    if AOP.is_nan() {
-        AOP = 0.;
+}
+
+pub fn equinoctial2state(elements: &EquinoctialElements, mu: f64) -> Vector6<f64> {
+    mee2state(&equinoctial2mee(elements), mu)
+}
+
+pub fn coes2mee(coes: &OrbitalElements) -> ModifiedEquinoctialElements {
+    let lon_peri = coes.raan + coes.aop;
+    let tan_half = (coes.inc / 2.).tan();
+    let param = if coes.param > 0. {
+        coes.param
+    } else {
+        coes.sma * (1. - coes.ecc.powi(2))
+    };
+    ModifiedEquinoctialElements {
+        param,
+        f: coes.ecc * lon_peri.cos(),
+        g: coes.ecc * lon_peri.sin(),
+        h: tan_half * coes.raan.cos(),
+        k: tan_half * coes.raan.sin(),
+        true_lon: (lon_peri + coes.ta).rem_euclid(2. * PI),
     }
-    let mut TA = (e_vec.dot(&r_vec) / (e * r_mag)).acos();
-    if r_vec.dot(&v_vec) < 0. {
-        TA = 2. * PI - TA;
-        println!("{}", TA);
+}
+
+pub fn mee2coes(mee: &ModifiedEquinoctialElements) -> OrbitalElements {
+    // same conventions as state2coes for the undefined angles of circular and equatorial orbits
+    let ecc = (mee.f.powi(2) + mee.g.powi(2)).sqrt();
+    let tan_half = (mee.h.powi(2) + mee.k.powi(2)).sqrt();
+    let inc = 2. * tan_half.atan();
+    let raan = if inc < COE_TOL {
+        0.
+    } else {
+        mee.k.atan2(mee.h).rem_euclid(2. * PI)
+    };
+    let aop = if ecc < COE_TOL {
+        0.
+    } else {
+        (mee.g.atan2(mee.f) - raan).rem_euclid(2. * PI)
+    };
+    let sma = if (ecc - 1.).abs() < COE_TOL {
+        f64::INFINITY
+    } else {
+        mee.param / (1. - ecc.powi(2))
+    };
+    OrbitalElements {
+        sma,
+        ecc,
+        inc,
+        raan,
+        aop,
+        ta: (mee.true_lon - raan - aop).rem_euclid(2. * PI),
+        param: mee.param,
     }
+}
 
-    // special cases
-    if e.abs() < 1e-8 && i.abs() < 1e-8 {
-        // circular equatorial
-        RAAN = 0.;
-        AOP = 0.;
-        TA = (r_vec[0] / r_mag).acos();
-        if r_vec[1] > 0. {
-            TA = 2. * PI - TA;
-        }
+pub fn coes2equinoctial(coes: &OrbitalElements) -> EquinoctialElements {
+    mee2equinoctial(&coes2mee(coes))
+}
+
+pub fn equinoctial2coes(elements: &EquinoctialElements) -> OrbitalElements {
+    mee2coes(&equinoctial2mee(elements))
+}
+
+#[allow(dead_code)]
+pub fn rtn2eci(r_vec: &Vector3<f64>, v_vec: &Vector3<f64>) -> Matrix3<f64> {
+    // columns are the radial, transverse (along-track) and orbit normal unit vectors
+    // expressed in the inertial frame, [NO] dcm (from RTN to inertial)
+    let r_hat = r_vec.normalize();
+    let n_hat = r_vec.cross(v_vec).normalize();
+    let t_hat = n_hat.cross(&r_hat);
+    Matrix3::from_columns(&[r_hat, t_hat, n_hat])
+}
+
+#[allow(dead_code)]
+pub fn vnb2eci(r_vec: &Vector3<f64>, v_vec: &Vector3<f64>) -> Matrix3<f64> {
+    // columns are the velocity, orbit normal and binormal unit vectors expressed in the
+    // inertial frame, [NV] dcm (from VNB to inertial)
+    let v_hat = v_vec.normalize();
+    let n_hat = r_vec.cross(v_vec).normalize();
+    let b_hat = v_hat.cross(&n_hat);
+    Matrix3::from_columns(&[v_hat, n_hat, b_hat])
+}
+
+#[allow(dead_code)]
+pub fn arg_of_latitude(r_vec: &Vector3<f64>, v_vec: &Vector3<f64>) -> f64 {
+    // angle from the ascending node to the position vector in [0, 2pi), measured from the
+    // inertial x axis (true longitude) for equatorial orbits
+    let h_hat = r_vec.cross(v_vec).normalize();
+    let n_vec = Vector3::new(-h_hat[1], h_hat[0], 0.); // z cross h
+    let n_hat = if n_vec.norm() > 1e-11 {
+        n_vec.normalize()
+    } else {
+        Vector3::x()
+    };
+    let u = n_hat.cross(r_vec).dot(&h_hat).atan2(n_hat.dot(r_vec));
+    u.rem_euclid(2. * PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MU: f64 = 398600.4418;
+
+    fn assert_states_match(state: &Vector6<f64>, expected: &Vector6<f64>) {
+        let r_error = (state.fixed_rows::<3>(0) - expected.fixed_rows::<3>(0)).norm();
+        let v_error = (state.fixed_rows::<3>(3) - expected.fixed_rows::<3>(3)).norm();
+        assert!(
+            r_error < 1e-8 && v_error < 1e-11,
+            "{} != {}",
+            state,
+            expected
+        );
     }
-    if e.abs() < 1e-8 && i.abs() > 1e-8 {
-        // circular inclined
-        AOP = 0.;
-        TA = (n_vec.dot(&r_vec) / (n_mag * r_mag)).acos();
-        if r_vec[2] < 0. {
-            TA = 2. * PI - TA;
-        }
+
+    #[test]
+    fn state2coes_matches_vallado_example_2_5() {
+        let state = vector![6524.834, 6862.875, 6448.296, 4.901327, 5.533756, -1.976341];
+        let coes = state2coes(&state, MU);
+        assert!((coes.param - 11067.790).abs() < 1e-2);
+        assert!((coes.sma - 36127.343).abs() < 1e-2);
+        assert!((coes.ecc - 0.832853).abs() < 1e-6);
+        assert!((coes.inc.to_degrees() - 87.870).abs() < 1e-3);
+        assert!((coes.raan.to_degrees() - 227.898).abs() < 1e-3);
+        assert!((coes.aop.to_degrees() - 53.38).abs() < 1e-2);
+        assert!((coes.ta.to_degrees() - 92.335).abs() < 1e-3);
+        assert_states_match(&coes2state(&coes, MU), &state);
     }
-    if e.abs() > 1e-8 && i.abs() < 1e-8 {
-        // elliptical equatorial
-        RAAN = 0.;
-        AOP = (e_vec[0] / e).acos();
-        if e_vec[1] < 0. {
-            AOP = 2. * PI - AOP;
+
+    #[test]
+    fn coes_round_trip_for_every_orbit_type() {
+        let deg = PI / 180.;
+        let cases = [
+            // elliptic inclined, circular inclined, elliptic equatorial, circular equatorial
+            (7000., 0.1, 30., 40., 50., 60.),
+            (7000., 0., 51.6, 120., 0., 200.),
+            (8000., 0.2, 0., 0., 75., 10.),
+            (42164., 0., 0., 0., 0., 300.),
+            // retrograde, retrograde equatorial, hyperbolic
+            (7200., 0.05, 98.7, 310., 90., 45.),
+            (9000., 0.3, 180., 0., 20., 330.),
+            (-20000., 1.5, 60., 10., 20., 30.),
+        ];
+        for (sma, ecc, inc, raan, aop, ta) in cases {
+            let coes = OrbitalElements {
+                sma,
+                ecc,
+                inc: inc * deg,
+                raan: raan * deg,
+                aop: aop * deg,
+                ta: ta * deg,
+                param: 0.,
+            };
+            let state = coes2state(&coes, MU);
+            let recovered = state2coes(&state, MU);
+            assert!((recovered.sma - sma).abs() < 1e-6 * sma.abs());
+            assert!((recovered.ecc - ecc).abs() < 1e-12);
+            assert!((recovered.inc - coes.inc).abs() < 1e-12);
+            assert!((recovered.raan - coes.raan).abs() < 1e-10);
+            assert!((recovered.aop - coes.aop).abs() < 1e-10);
+            assert!((recovered.ta - coes.ta).abs() < 1e-10);
+            assert_states_match(&coes2state(&recovered, MU), &state);
         }
     }
 
-    [p, e, i, RAAN, AOP, TA, a]
-}
-
-#[allow(non_snake_case, dead_code)]
-pub fn coes2state(
-    p: f64,
-    e: f64,
-    i: f64,
-    RAAN: &mut f64,
-    AOP: &mut f64,
-    TA: f64,
-    mu: f64,
-) -> Vector6<f64> {
-    if e.abs() < 1e-8 && i.abs() < 1e-8 {
-        // circular equatorial
-        *AOP = 0.;
-        *RAAN = 0.;
-    } else if e.abs() < 1e-8 && i.abs() > 1e-8 {
-        // circular inclined
-        *AOP = 0.;
-    } else if e.abs() > 1e-8 && i.abs() < 1e-8 {
-        // elliptical equatorial
-        *RAAN = 0.;
-    }
-
-    let mut r = vector![
-        p * TA.cos() / (1. + e * TA.cos()),
-        p * TA.sin() / (1. + e * TA.cos()),
-        0.
-    ];
+    #[test]
+    fn undefined_angles_move_into_the_next_defined_angle() {
+        // circular inclined: argument of latitude in ta
+        let state = coes2state(
+            &OrbitalElements {
+                sma: 7000.,
+                ecc: 0.,
+                inc: 0.5,
+                raan: 1.,
+                aop: 0.4,
+                ta: 0.3,
+                param: 0.,
+            },
+            MU,
+        );
+        let coes = state2coes(&state, MU);
+        assert_eq!(coes.aop, 0.);
+        assert!((coes.ta - 0.7).abs() < 1e-10);
 
-    let mut v = vector![
-        -(mu / p).sqrt() * TA.sin(),
-        (mu / p).sqrt() * (e + TA.cos()),
-        0.
-    ];
+        // elliptic equatorial: longitude of periapsis in aop
+        let state = coes2state(
+            &OrbitalElements {
+                sma: 7000.,
+                ecc: 0.1,
+                inc: 0.,
+                raan: 1.,
+                aop: 0.4,
+                ta: 0.3,
+                param: 0.,
+            },
+            MU,
+        );
+        let coes = state2coes(&state, MU);
+        assert_eq!(coes.raan, 0.);
+        assert!((coes.aop - 1.4).abs() < 1e-10);
+        assert!((coes.ta - 0.3).abs() < 1e-10);
+    }
 
-    let pqw2eci = matrix![
-        RAAN.cos() * AOP.cos() - RAAN.sin() * AOP.sin() * i.cos(), -RAAN.cos() * AOP.sin() - RAAN.sin() * AOP.cos() * i.cos(), RAAN.sin() * i.sin();
-        RAAN.sin() * AOP.cos() + RAAN.cos() * AOP.sin() * i.cos(), -RAAN.sin() * AOP.sin() + RAAN.cos() * AOP.cos() * i.cos(), -RAAN.cos() * i.sin();
-        AOP.sin()*i.sin(), AOP.cos()*i.sin(), i.cos();];
+    #[test]
+    fn parabolic_orbits_use_the_semi_latus_rectum() {
+        let coes = OrbitalElements {
+            sma: f64::INFINITY,
+            ecc: 1.,
+            inc: 0.8,
+            raan: 2.,
+            aop: 1.,
+            ta: 1.5,
+            param: 14000.,
+        };
+        let state = coes2state(&coes, MU);
+        // escape speed everywhere on a parabola
+        let r = state.fixed_rows::<3>(0).norm();
+        assert!((state.fixed_rows::<3>(3).norm() - (2. * MU / r).sqrt()).abs() < 1e-12);
+        let recovered = state2coes(&state, MU);
+        assert!(recovered.sma.is_infinite());
+        assert!((recovered.ecc - 1.).abs() < 1e-12);
+        assert!((recovered.param - 14000.).abs() < 1e-8);
+        assert!((recovered.ta - 1.5).abs() < 1e-10);
+        assert_states_match(&coes2state(&recovered, MU), &state);
+    }
 
-    r = pqw2eci * r;
-    v = pqw2eci * v;
+    #[test]
+    fn mee_round_trip_for_every_conic() {
+        // circular equatorial, elliptic retrograde, hyperbolic and parabolic
+        let cases = [
+            (7000., 0., 0., 0., 0., 1.),
+            (9000., 0.3, 2.5, 1., 2., 3.),
+            (-20000., 1.5, 0.5, 4., 1., 0.2),
+        ];
+        for (sma, ecc, inc, raan, aop, ta) in cases {
+            let coes = OrbitalElements {
+                sma,
+                ecc,
+                inc,
+                raan,
+                aop,
+                ta,
+                param: 0.,
+            };
+            let state = coes2state(&coes, MU);
+            let mee = state2mee(&state, MU);
+            assert_states_match(&mee2state(&mee, MU), &state);
+            assert_states_match(&coes2state(&mee2coes(&mee), MU), &state);
+            assert_states_match(&mee2state(&coes2mee(&coes), MU), &state);
+        }
+        let mee = ModifiedEquinoctialElements {
+            param: 14000.,
+            f: 0.6,
+            g: -0.8,
+            h: 0.2,
+            k: 0.3,
+            true_lon: 0.4,
+        };
+        let state = mee2state(&mee, MU);
+        let recovered = state2mee(&state, MU);
+        assert!((recovered.param - mee.param).abs() < 1e-8);
+        assert!((recovered.f - mee.f).abs() < 1e-12 && (recovered.g - mee.g).abs() < 1e-12);
+        assert!((recovered.h - mee.h).abs() < 1e-12 && (recovered.k - mee.k).abs() < 1e-12);
+        assert!((recovered.true_lon - mee.true_lon).abs() < 1e-12);
+    }
 
-    let mut state = Vector6::zeros();
-    state.fixed_rows_mut::<3>(0).copy_from(&r);
-    state.fixed_rows_mut::<3>(3).copy_from(&v);
-    state
+    #[test]
+    fn equinoctial_round_trip_and_known_values() {
+        let coes = OrbitalElements {
+            sma: 7000.,
+            ecc: 0.1,
+            inc: 0.6,
+            raan: 0.7,
+            aop: 0.8,
+            ta: 0.,
+            param: 0.,
+        };
+        // at periapsis the mean longitude is raan + aop
+        let elements = coes2equinoctial(&coes);
+        assert!((elements.sma - 7000.).abs() < 1e-8);
+        assert!((elements.h - 0.1 * 1.5_f64.sin()).abs() < 1e-14);
+        assert!((elements.k - 0.1 * 1.5_f64.cos()).abs() < 1e-14);
+        assert!((elements.p - 0.3_f64.tan() * 0.7_f64.sin()).abs() < 1e-14);
+        assert!((elements.q - 0.3_f64.tan() * 0.7_f64.cos()).abs() < 1e-14);
+        assert!((elements.mean_lon - 1.5).abs() < 1e-12);
+
+        for mean_lon in [0., 1., 3., 5.] {
+            let elements = EquinoctialElements {
+                mean_lon,
+                ..elements
+            };
+            let state = equinoctial2state(&elements, MU);
+            let recovered = mee2equinoctial(&state2mee(&state, MU));
+            assert!((recovered.sma - elements.sma).abs() < 1e-8);
+            assert!((recovered.h - elements.h).abs() < 1e-12);
+            assert!((recovered.k - elements.k).abs() < 1e-12);
+            assert!((recovered.p - elements.p).abs() < 1e-12);
+            assert!((recovered.q - elements.q).abs() < 1e-12);
+            let lon_error = (recovered.mean_lon - mean_lon + PI).rem_euclid(2. * PI) - PI;
+            assert!(lon_error.abs() < 1e-12);
+            let coes = equinoctial2coes(&elements);
+            assert_states_match(&coes2state(&coes, MU), &state);
+        }
+    }
 }
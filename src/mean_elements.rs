@@ -0,0 +1,627 @@
+use crate::centralbody::*;
+use crate::kepler::*;
+use crate::math::*;
+use crate::orbitalelements::*;
+use nalgebra::*;
+use std::f64::consts::PI;
+
+// Brouwer, "Solution of the problem of artificial satellite theory without drag", Astronomical
+// Journal 64 (1959), with Lyddane's nonsingular form (Astronomical Journal 68, 1963). the j2
+// mapping follows Schaub and Junkins, Analytical Mechanics of Space Systems, appendix F, the
+// j3 to j5 long period terms come from the generating function of the averaged zonal potential
+
+const MAX_ITERATIONS: usize = 25;
+const MAP_TOL: f64 = 1e-13;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeanElementTheory {
+    BrouwerLyddane, // secular mean elements, short and long period terms removed
+    Kozai,          // only the j2 short period terms removed, long period motion is kept
+}
+
+struct ZonalTerm {
+    // one term c a^alpha e^ecc_pow eta^eta_pow sin(i)^sin_pow g(i) h(aop) of a function of the
+    // mean elements, eta = sqrt(1 - e^2). keeping the powers of e and sin(i) apart lets the
+    // variations below cancel them analytically for circular and equatorial orbits
+    coef: f64,
+    sma_pow: f64,
+    ecc_pow: i32,
+    eta_pow: f64,
+    sin_pow: i32,
+    incl: (f64, f64), // g and dg/di
+    arg: (f64, f64),  // h and dh/daop
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Variations {
+    // lagrange's planetary equations for a function of (a, e, i, aop), as rates for the
+    // averaged potential or as periodic corrections for a generating function. the node and
+    // perigee terms come premultiplied by sin(i) and e, which keeps them finite
+    ecc: f64,
+    inc: f64,
+    e_mean_anom: f64, // e dM
+    e_lon_peri: f64,  // e d(aop + raan)
+    sin_raan: f64,    // sin(i) draan
+    mean_lon: f64,    // d(M + aop + raan) without the mean motion
+}
+
+pub fn mean2osc(
+    mean: &OrbitalElements,
+    central_body: &CentralBody,
+    theory: MeanElementTheory,
+) -> OrbitalElements {
+    // osculating elements from brouwer-lyddane or kozai mean elements (elliptic orbits away from
+    // the critical inclination of 63.4 deg, where the long period terms are singular)
+    brouwer_map(mean, central_body, theory, 1.)
+}
+
+pub fn osc2mean(
+    osc: &OrbitalElements,
+    central_body: &CentralBody,
+    theory: MeanElementTheory,
+) -> OrbitalElements {
+    // inverse of mean2osc, the first order inverse map is refined by fixed point iterations in
+    // equinoctial elements until mean2osc reproduces the osculating elements
+    let target = coes2equinoctial(osc);
+    let mut mean = coes2equinoctial(&brouwer_map(osc, central_body, theory, -1.));
+    for _ in 0..MAX_ITERATIONS {
+        let mapped = coes2equinoctial(&brouwer_map(
+            &equinoctial2coes(&mean),
+            central_body,
+            theory,
+            1.,
+        ));
+        let mut residual = equinoctial2vec(&target) - equinoctial2vec(&mapped);
+        residual[5] = (residual[5] + PI).rem_euclid(2. * PI) - PI;
+        mean = vec2equinoctial(&(equinoctial2vec(&mean) + residual));
+        if residual[0].abs() / target.sma < MAP_TOL && residual.rows(1, 5).amax() < MAP_TOL {
+            break;
+        }
+    }
+    equinoctial2coes(&mean)
+}
+
+pub fn mean_element_rates(mean: &EquinoctialElements, central_body: &CentralBody) -> Vector6<f64> {
+    // rates of the kozai mean equinoctial elements (a, h, k, p, q, mean longitude) under the
+    // j2 to j5 zonal potential averaged over the mean anomaly (first order, the semimajor axis
+    // is constant)
+    let mu = central_body.mu;
+    let coes = equinoctial2coes(mean);
+    let mut variations = lagrange(&averaged_terms(&coes, central_body), &coes, mu);
+
+    // second order j2 secular terms (Brouwer), the first order averaging leaves the mean
+    // motion a few parts per million short
+    let n = (mu / mean.sma.powi(3)).sqrt();
+    let eta = (1. - coes.ecc.powi(2)).sqrt();
+    let eta2 = eta.powi(2);
+    let gamma2p = central_body.zonal(2) / 2. * (central_body.equatorial_radius / mean.sma).powi(2)
+        / eta.powi(4);
+    let (s, c) = coes.inc.sin_cos();
+    let c2 = c.powi(2);
+    let j2_sq = n * gamma2p.powi(2);
+    let dl = 3. / 32.
+        * j2_sq
+        * eta
+        * (-15.
+            + 16. * eta
+            + 25. * eta2
+            + (30. - 96. * eta - 90. * eta2) * c2
+            + (105. + 144. * eta + 25. * eta2) * c2.powi(2));
+    let dg = 3. / 32.
+        * j2_sq
+        * (-35.
+            + 24. * eta
+            + 25. * eta2
+            + (90. - 192. * eta - 126. * eta2) * c2
+            + (385. + 360. * eta + 45. * eta2) * c2.powi(2));
+    let dh = 3. / 8.
+        * j2_sq
+        * ((-5. + 12. * eta + 9. * eta2) * c + (-35. - 36. * eta - 5. * eta2) * c * c2);
+    variations.e_lon_peri += coes.ecc * (dg + dh);
+    variations.sin_raan += s * dh;
+    variations.mean_lon += dl + dg + dh;
+
+    let lon_peri = mean.h.atan2(mean.k);
+    let raan = mean.p.atan2(mean.q);
+    let half_sec2 = 1. + mean.p.powi(2) + mean.q.powi(2); // 1 / cos^2(i/2)
+    let mut rates = Vector6::zeros();
+    rates[1] = variations.ecc * lon_peri.sin() + variations.e_lon_peri * lon_peri.cos();
+    rates[2] = variations.ecc * lon_peri.cos() - variations.e_lon_peri * lon_peri.sin();
+    rates[3] = half_sec2 / 2. * (variations.inc * raan.sin() + variations.sin_raan * raan.cos());
+    rates[4] = half_sec2 / 2. * (variations.inc * raan.cos() - variations.sin_raan * raan.sin());
+    rates[5] = n + variations.mean_lon;
+    rates
+}
+
+pub fn propagate_mean(
+    mean: &EquinoctialElements,
+    dt: f64,
+    central_body: &CentralBody,
+) -> EquinoctialElements {
+    // rk4 step of the averaged equations, the mean elements change slowly enough for steps of
+    // a good fraction of an orbit
+    let x = equinoctial2vec(mean);
+    let f = |x: &Vector6<f64>| mean_element_rates(&vec2equinoctial(x), central_body);
+    let k1 = f(&x);
+    let k2 = f(&(x + k1 * dt / 2.));
+    let k3 = f(&(x + k2 * dt / 2.));
+    let k4 = f(&(x + k3 * dt));
+    let mut x_new = x + (k1 + (k2 + k3) * 2. + k4) * dt / 6.;
+    x_new[5] = x_new[5].rem_euclid(2. * PI);
+    vec2equinoctial(&x_new)
+}
+
+pub fn mean_element_state(
+    state: &Vector6<f64>,
+    dt: f64,
+    central_body: &CentralBody,
+) -> Vector6<f64> {
+    // semi-analytic step: osculating state to kozai mean elements, averaged equations over dt,
+    // then the short period terms are restored
+    let mu = central_body.mu;
+    let mean = osc2mean(
+        &state2coes(state, mu),
+        central_body,
+        MeanElementTheory::Kozai,
+    );
+    let mean = propagate_mean(&coes2equinoctial(&mean), dt, central_body);
+    let osc = mean2osc(
+        &equinoctial2coes(&mean),
+        central_body,
+        MeanElementTheory::Kozai,
+    );
+    coes2state(&osc, mu)
+}
+
+fn brouwer_map(
+    coes: &OrbitalElements,
+    central_body: &CentralBody,
+    theory: MeanElementTheory,
+    sgn: f64,
+) -> OrbitalElements {
+    // first order map between mean and osculating elements, sgn = 1 for mean to osculating and
+    // -1 for osculating to mean (Schaub and Junkins, appendix F, with the j3 to j5 terms added)
+    let j2 = central_body.zonal(2);
+    if j2 == 0. || coes.ecc >= 1. {
+        return *coes;
+    }
+    let long_period = theory == MeanElementTheory::BrouwerLyddane;
+    let (a, e, i) = (coes.sma, coes.ecc, coes.inc);
+    let (raan, aop, f) = (coes.raan, coes.aop, coes.ta);
+    let mean_anom = true2mean(f, e);
+
+    let gamma2 = sgn * j2 / 2. * (central_body.equatorial_radius / a).powi(2);
+    let eta = (1. - e.powi(2)).sqrt();
+    let gamma2p = gamma2 / eta.powi(4);
+    let a_r = (1. + e * f.cos()) / eta.powi(2);
+    let (ci, si) = (i.cos(), i.sin());
+    let ci2 = ci.powi(2);
+    let crit = 1. - 5. * ci2; // zero at the critical inclination
+                              // equation of the center plus e sin(f), wrapped since f and M may be a revolution apart
+    let center = (f - mean_anom + PI).rem_euclid(2. * PI) - PI + e * f.sin();
+    let sp_2w = 3. * (2. * aop + 2. * f).sin()
+        + 3. * e * (2. * aop + f).sin()
+        + e * (2. * aop + 3. * f).sin();
+    let cp_2w = 3. * (2. * aop + 2. * f).cos()
+        + 3. * e * (2. * aop + f).cos()
+        + e * (2. * aop + 3. * f).cos();
+    let cube = 3. * f.cos() + 3. * e * f.cos().powi(2) + e.powi(2) * f.cos().powi(3);
+
+    // short period terms
+    let sma = a + a
+        * gamma2
+        * ((3. * ci2 - 1.) * (a_r.powi(3) - 1. / eta.powi(3))
+            + 3. * (1. - ci2) * a_r.powi(3) * (2. * aop + 2. * f).cos());
+    let mut de = eta.powi(2) / 2.
+        * (gamma2
+            * ((3. * ci2 - 1.) / eta.powi(6) * (e * eta + e / (1. + eta) + cube)
+                + 3. * (1. - ci2) / eta.powi(6) * (e + cube) * (2. * aop + 2. * f).cos())
+            - gamma2p * (1. - ci2) * (3. * (2. * aop + f).cos() + (2. * aop + 3. * f).cos()));
+    let mut di = gamma2p / 2. * ci * si * cp_2w;
+    let ar_eta = (a_r * eta).powi(2) + a_r;
+    let mut e_dm = -gamma2p / 4.
+        * eta.powi(3)
+        * (2. * (3. * ci2 - 1.) * (ar_eta + 1.) * f.sin()
+            + 3. * (1. - ci2)
+                * ((-ar_eta + 1.) * (2. * aop + f).sin()
+                    + (ar_eta + 1. / 3.) * (2. * aop + 3. * f).sin()));
+    let mut draan = -gamma2p / 2. * ci * (6. * center - sp_2w);
+    let mut dlon = gamma2p / 4. * (-6. * crit * center + (3. - 5. * ci2) * sp_2w)
+        - gamma2p / 2. * ci * (6. * center - sp_2w);
+    let mut sin_half_draan = 0.;
+
+    if long_period {
+        // j2 squared long period terms
+        let lp = 1. - 11. * ci2 - 40. * ci2.powi(2) / crit;
+        let lp_node = 11. + 80. * ci2 / crit + 200. * ci2.powi(2) / crit.powi(2);
+        let de1 = gamma2p / 8. * e * eta.powi(2) * lp * (2. * aop).cos();
+        de += de1;
+        if si != 0. {
+            di -= e * de1 / eta.powi(2) * ci / si;
+        }
+        e_dm += gamma2p / 8. * e * eta.powi(3) * lp * (2. * aop).sin();
+        let draan_lp = -gamma2p / 8. * e.powi(2) * ci * lp_node * (2. * aop).sin();
+        draan += draan_lp;
+        dlon += gamma2p / 8. * eta.powi(3) * lp * (2. * aop).sin()
+            - gamma2p / 16.
+                * (2. + e.powi(2)
+                    - 11. * (2. + 3. * e.powi(2)) * ci2
+                    - 40. * (2. + 5. * e.powi(2)) * ci2.powi(2) / crit
+                    - 400. * e.powi(2) * ci2.powi(3) / crit.powi(2))
+                * (2. * aop).sin()
+            + draan_lp;
+
+        // j3 to j5 long period terms
+        let variations = lagrange(
+            &long_period_terms(coes, central_body),
+            coes,
+            central_body.mu,
+        );
+        de += sgn * variations.ecc;
+        di += sgn * variations.inc;
+        e_dm += sgn * variations.e_mean_anom;
+        dlon += sgn * variations.mean_lon;
+        sin_half_draan = sgn * variations.sin_raan / (2. * (i / 2.).cos());
+    }
+
+    // lyddane's nonsingular recombination
+    let d1 = (e + de) * mean_anom.sin() + e_dm * mean_anom.cos();
+    let d2 = (e + de) * mean_anom.cos() - e_dm * mean_anom.sin();
+    let mean_anom_new = d1.atan2(d2);
+    let ecc = (d1.powi(2) + d2.powi(2)).sqrt();
+    let sin_half = (i / 2.).sin() + (i / 2.).cos() * di / 2.;
+    let sin_half_draan = sin_half_draan + (i / 2.).sin() * draan;
+    let d3 = sin_half * raan.sin() + sin_half_draan * raan.cos();
+    let d4 = sin_half * raan.cos() - sin_half_draan * raan.sin();
+    let raan_new = d3.atan2(d4);
+    let inc = 2. * (d3.powi(2) + d4.powi(2)).sqrt().min(1.).asin();
+    let aop_new = mean_anom + aop + raan + dlon - mean_anom_new - raan_new;
+
+    OrbitalElements {
+        sma,
+        ecc,
+        inc,
+        raan: raan_new.rem_euclid(2. * PI),
+        aop: aop_new.rem_euclid(2. * PI),
+        ta: mean2true(mean_anom_new, ecc).rem_euclid(2. * PI),
+        param: sma * (1. - ecc.powi(2)),
+    }
+}
+
+fn averaged_terms(coes: &OrbitalElements, central_body: &CentralBody) -> Vec<ZonalTerm> {
+    // j2 to j5 potential averaged over the mean anomaly
+    let mu = central_body.mu;
+    let re = central_body.equatorial_radius;
+    let j = |n: usize| central_body.zonal(n) * re.powi(n as i32) * mu;
+    let (s, c) = coes.inc.sin_cos();
+    let s2 = s.powi(2);
+    let (sw, cw) = coes.aop.sin_cos();
+    let term = |coef, sma_pow, ecc_pow, eta_pow, sin_pow, incl, arg| ZonalTerm {
+        coef,
+        sma_pow,
+        ecc_pow,
+        eta_pow,
+        sin_pow,
+        incl,
+        arg,
+    };
+    vec![
+        term(
+            j(2) / 4.,
+            -3.,
+            0,
+            -3.,
+            0,
+            (2. - 3. * s2, -6. * s * c),
+            (1., 0.),
+        ),
+        term(
+            -3. / 8. * j(3),
+            -4.,
+            1,
+            -5.,
+            1,
+            (5. * s2 - 4., 10. * s * c),
+            (sw, cw),
+        ),
+        term(
+            -3. / 64. * j(4),
+            -5.,
+            0,
+            -7.,
+            0,
+            (35. * s2.powi(2) - 40. * s2 + 8., (140. * s2 - 80.) * s * c),
+            (1., 0.),
+        ),
+        term(
+            -3. / 128. * j(4),
+            -5.,
+            2,
+            -7.,
+            0,
+            (
+                35. * s2.powi(2) - 60. * s2 + 24.,
+                (140. * s2 - 120.) * s * c,
+            ),
+            (1., 0.),
+        ),
+        term(
+            -15. / 32. * j(4),
+            -5.,
+            2,
+            -7.,
+            2,
+            (7. * s2 - 6., 14. * s * c),
+            (sw.powi(2), 2. * sw * cw),
+        ),
+        term(
+            -15. / 32. * j(5),
+            -6.,
+            1,
+            -9.,
+            1,
+            (21. * s2.powi(2) - 28. * s2 + 8., (84. * s2 - 56.) * s * c),
+            (sw, cw),
+        ),
+        term(
+            -5. / 256. * j(5),
+            -6.,
+            3,
+            -9.,
+            1,
+            (
+                189. * s2.powi(2) - 336. * s2 + 144.,
+                (756. * s2 - 672.) * s * c,
+            ),
+            (sw, cw),
+        ),
+        term(
+            -35. / 64. * j(5),
+            -6.,
+            3,
+            -9.,
+            3,
+            (9. * s2 - 8., 18. * s * c),
+            (sw.powi(3), 3. * sw.powi(2) * cw),
+        ),
+    ]
+}
+
+fn long_period_terms(coes: &OrbitalElements, central_body: &CentralBody) -> Vec<ZonalTerm> {
+    // generating function of the j3 to j5 long period terms, the aop dependent part of the
+    // averaged potential integrated over the aop and divided by the j2 secular aop rate
+    let mu = central_body.mu;
+    let re = central_body.equatorial_radius;
+    let j2 = central_body.zonal(2);
+    let j = |n: usize| central_body.zonal(n) * re.powi(n as i32 - 2) * mu.sqrt() / j2;
+    let (s, c) = coes.inc.sin_cos();
+    let s2 = s.powi(2);
+    let (sw, cw) = coes.aop.sin_cos();
+    let crit = 4. - 5. * s2; // zero at the critical inclination
+                             // q(s) / crit and its derivative with respect to i from q and dq/ds
+    let over_crit = |q: f64, dq: f64| (q / crit, (dq * crit + 10. * s * q) / crit.powi(2) * c);
+    let term = |coef, sma_pow, ecc_pow, eta_pow, sin_pow, incl, arg| ZonalTerm {
+        coef,
+        sma_pow,
+        ecc_pow,
+        eta_pow,
+        sin_pow,
+        incl,
+        arg,
+    };
+    vec![
+        term(-j(3) / 2., -0.5, 1, -1., 1, (1., 0.), (cw, -sw)),
+        term(
+            5. / 32. * j(4),
+            -1.5,
+            2,
+            -3.,
+            2,
+            over_crit(7. * s2 - 6., 14. * s),
+            (2. * sw * cw, 2. * (cw.powi(2) - sw.powi(2))),
+        ),
+        term(
+            -5. / 8. * j(5),
+            -2.5,
+            1,
+            -5.,
+            1,
+            over_crit(21. * s2.powi(2) - 28. * s2 + 8., (84. * s2 - 56.) * s),
+            (-cw, sw),
+        ),
+        term(
+            -5. / 192. * j(5),
+            -2.5,
+            3,
+            -5.,
+            1,
+            over_crit(
+                -441. * s2.powi(2) + 560. * s2 - 144.,
+                (-1764. * s2 + 1120.) * s,
+            ),
+            (cw, -sw),
+        ),
+        term(
+            -35. / 144. * j(5),
+            -2.5,
+            3,
+            -5.,
+            3,
+            over_crit(9. * s2 - 8., 18. * s),
+            (cw.powi(3), -3. * cw.powi(2) * sw),
+        ),
+    ]
+}
+
+fn lagrange(terms: &[ZonalTerm], coes: &OrbitalElements, mu: f64) -> Variations {
+    // lagrange's planetary equations for a function that does not depend on the mean anomaly
+    // or the node
+    let (a, e) = (coes.sma, coes.ecc);
+    let (s, c) = coes.inc.sin_cos();
+    let eta2 = 1. - e.powi(2);
+    let eta = eta2.sqrt();
+    let na2 = (mu * a).sqrt();
+    let tan_half = (coes.inc / 2.).tan();
+
+    let mut dfda = 0.;
+    let mut dfde = 0.;
+    let mut dfdi = 0.;
+    let mut dfdw_es = 0.; // dF/daop / (e sin(i))
+    for t in terms.iter() {
+        let base = t.coef * a.powf(t.sma_pow) * eta.powf(t.eta_pow);
+        let ecc_pow = e.powi(t.ecc_pow);
+        let sin_pow = s.powi(t.sin_pow);
+        let value = base * ecc_pow * sin_pow * t.incl.0 * t.arg.0;
+        dfda += t.sma_pow / a * value;
+        // d(e^k eta^m)/de = e^(k-1) eta^m (k - m e^2 / eta^2)
+        let decc_pow = if t.ecc_pow > 0 {
+            t.ecc_pow as f64 * e.powi(t.ecc_pow - 1)
+        } else {
+            0.
+        };
+        dfde += base
+            * sin_pow
+            * t.incl.0
+            * t.arg.0
+            * (decc_pow - t.eta_pow * e.powi(t.ecc_pow + 1) / eta2);
+        let dsin_pow = if t.sin_pow > 0 {
+            t.sin_pow as f64 * s.powi(t.sin_pow - 1) * c
+        } else {
+            0.
+        };
+        dfdi += base * ecc_pow * t.arg.0 * (dsin_pow * t.incl.0 + sin_pow * t.incl.1);
+        if t.ecc_pow > 0 && t.sin_pow > 0 {
+            dfdw_es += base * e.powi(t.ecc_pow - 1) * s.powi(t.sin_pow - 1) * t.incl.0 * t.arg.1;
+        }
+    }
+
+    let lon_peri = eta / na2 * dfde + e * tan_half / (na2 * eta) * dfdi;
+    Variations {
+        ecc: -eta * s / na2 * dfdw_es,
+        inc: c * e / (na2 * eta) * dfdw_es,
+        e_mean_anom: -2. * e * (a / mu).sqrt() * dfda - eta2 / na2 * dfde,
+        e_lon_peri: lon_peri,
+        sin_raan: dfdi / (na2 * eta),
+        mean_lon: -2. * (a / mu).sqrt() * dfda
+            + eta * e / ((1. + eta) * na2) * dfde
+            + tan_half / (na2 * eta) * dfdi,
+    }
+}
+
+fn equinoctial2vec(elements: &EquinoctialElements) -> Vector6<f64> {
+    vector![
+        elements.sma,
+        elements.h,
+        elements.k,
+        elements.p,
+        elements.q,
+        elements.mean_lon
+    ]
+}
+
+fn vec2equinoctial(x: &Vector6<f64>) -> EquinoctialElements {
+    EquinoctialElements {
+        sma: x[0],
+        h: x[1],
+        k: x[2],
+        p: x[3],
+        q: x[4],
+        mean_lon: x[5],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn earth() -> CentralBody {
+        let mut central_body = CentralBody::new();
+        central_body.mu = 398600.4418;
+        central_body.equatorial_radius = 6378.137;
+        central_body.zonals = EARTH_ZONALS.to_vec();
+        central_body
+    }
+
+    fn coes(sma: f64, ecc: f64, inc: f64, raan: f64, aop: f64, ta: f64) -> OrbitalElements {
+        let deg = PI / 180.;
+        OrbitalElements {
+            sma,
+            ecc,
+            inc: inc * deg,
+            raan: raan * deg,
+            aop: aop * deg,
+            ta: ta * deg,
+            param: sma * (1. - ecc.powi(2)),
+        }
+    }
+
+    #[test]
+    fn osc2mean_inverts_mean2osc() {
+        let central_body = earth();
+        let mu = central_body.mu;
+        // leo, sun synchronous, eccentric, near circular and near equatorial orbits
+        let orbits = [
+            coes(6878., 0.001, 51.6, 30., 60., 10.),
+            coes(7078., 0.0012, 98.2, 200., 90., 250.),
+            coes(26560., 0.7, 28.5, 120., 270., 100.),
+            coes(7500., 1e-6, 45., 10., 0., 300.),
+            coes(8000., 0.05, 1e-4, 0., 135., 45.),
+        ];
+        for theory in [MeanElementTheory::BrouwerLyddane, MeanElementTheory::Kozai] {
+            for mean in orbits.iter() {
+                let osc = mean2osc(mean, &central_body, theory);
+                let recovered = osc2mean(&osc, &central_body, theory);
+                // compared as states since the angles are ill defined for small e and i
+                let error = coes2state(&recovered, mu) - coes2state(mean, mu);
+                assert!(
+                    error.fixed_rows::<3>(0).norm() < 1e-6
+                        && error.fixed_rows::<3>(3).norm() < 1e-9,
+                    "{:?} {:?}: {}",
+                    theory,
+                    mean,
+                    error.norm()
+                );
+                // the short period terms move a leo by kilometers
+                let offset = (coes2state(&osc, mu) - coes2state(mean, mu))
+                    .fixed_rows::<3>(0)
+                    .norm();
+                assert!(offset > 0.1 && offset < 50., "{}", offset);
+            }
+        }
+    }
+
+    #[test]
+    fn elements_are_unchanged_without_j2() {
+        let mut central_body = earth();
+        central_body.zonals = vec![];
+        let osc = coes(7000., 0.01, 40., 10., 20., 30.);
+        let mean = osc2mean(&osc, &central_body, MeanElementTheory::BrouwerLyddane);
+        assert!(
+            (coes2state(&mean, central_body.mu) - coes2state(&osc, central_body.mu)).norm() < 1e-9
+        );
+    }
+
+    #[test]
+    fn mean_rates_match_the_j2_secular_rates() {
+        // first order node and perigee rates, the second order terms are a fraction of j2
+        let mut central_body = earth();
+        central_body.zonals = vec![0., 0., central_body.zonal(2)];
+        let j2 = central_body.zonal(2);
+        let mean = coes(7000., 0.05, 60., 40., 30., 0.);
+        let rates = mean_element_rates(&coes2equinoctial(&mean), &central_body);
+        let elements = coes2equinoctial(&mean);
+
+        let n = (central_body.mu / mean.sma.powi(3)).sqrt();
+        let factor = 1.5 * n * j2 * (central_body.equatorial_radius / mean.param).powi(2);
+        let raan_dot = -factor * mean.inc.cos();
+        let lon_peri_dot = raan_dot + factor * (2. - 2.5 * mean.inc.sin().powi(2));
+
+        let raan_rate = (elements.q * rates[3] - elements.p * rates[4])
+            / (elements.p.powi(2) + elements.q.powi(2));
+        let lon_peri_rate = (elements.k * rates[1] - elements.h * rates[2])
+            / (elements.h.powi(2) + elements.k.powi(2));
+        assert!(rates[0] == 0.);
+        assert!((raan_rate - raan_dot).abs() < 2e-3 * raan_dot.abs());
+        assert!((lon_peri_rate - lon_peri_dot).abs() < 2e-3 * lon_peri_dot.abs());
+    }
+}
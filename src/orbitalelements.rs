@@ -1,3 +1,7 @@
+use crate::math::*;
+use nalgebra::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct OrbitalElements {
     pub sma: f64,
     pub ecc: f64,
@@ -7,3 +11,86 @@ pub struct OrbitalElements {
     pub ta: f64,
     pub param: f64,
 }
+
+impl OrbitalElements {
+    pub fn new() -> Self {
+        OrbitalElements {
+            sma: 0.,
+            ecc: 0.,
+            inc: 0.,
+            raan: 0.,
+            aop: 0.,
+            ta: 0.,
+            param: 0.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EquinoctialElements {
+    // non-singular for circular and equatorial orbits (prograde, retrograde factor of +1),
+    // elliptic orbits only
+    pub sma: f64,
+    pub h: f64,        // e sin(aop + raan)
+    pub k: f64,        // e cos(aop + raan)
+    pub p: f64,        // tan(inc/2) sin(raan)
+    pub q: f64,        // tan(inc/2) cos(raan)
+    pub mean_lon: f64, // raan + aop + mean anomaly
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModifiedEquinoctialElements {
+    // non-singular for circular and equatorial orbits (prograde), valid for every conic
+    pub param: f64,
+    pub f: f64,        // e cos(aop + raan)
+    pub g: f64,        // e sin(aop + raan)
+    pub h: f64,        // tan(inc/2) cos(raan)
+    pub k: f64,        // tan(inc/2) sin(raan)
+    pub true_lon: f64, // raan + aop + true anomaly
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InitialConditions {
+    // initial state of a body relative to its reference body in any supported element set
+    Cartesian(Vector6<f64>),
+    Classical(OrbitalElements),
+    Equinoctial(EquinoctialElements),
+    ModifiedEquinoctial(ModifiedEquinoctialElements),
+}
+
+impl EquinoctialElements {
+    pub fn new() -> Self {
+        EquinoctialElements {
+            sma: 0.,
+            h: 0.,
+            k: 0.,
+            p: 0.,
+            q: 0.,
+            mean_lon: 0.,
+        }
+    }
+}
+
+impl ModifiedEquinoctialElements {
+    pub fn new() -> Self {
+        ModifiedEquinoctialElements {
+            param: 0.,
+            f: 0.,
+            g: 0.,
+            h: 0.,
+            k: 0.,
+            true_lon: 0.,
+        }
+    }
+}
+
+impl InitialConditions {
+    pub fn to_state(self, mu: f64) -> Vector6<f64> {
+        match self {
+            InitialConditions::Cartesian(state) => state,
+            InitialConditions::Classical(coes) => coes2state(&coes, mu),
+            InitialConditions::Equinoctial(elements) => equinoctial2state(&elements, mu),
+            InitialConditions::ModifiedEquinoctial(elements) => mee2state(&elements, mu),
+        }
+    }
+}
@@ -1,39 +1,282 @@
 // use crate::math::*;
 use nalgebra::*;
+use std::rc::Rc;
 
+use crate::adcs::Adcs;
+use crate::attitude::*;
+use crate::centralbody::CentralBody;
+use crate::dynamical_system::Propagator;
+use crate::eoms::Perturbations;
+use crate::epoch::TimeData;
+use crate::maneuver::ManeuverPlan;
 use crate::orbitalelements::OrbitalElements;
+use crate::sgp4::*;
+use crate::tle::Tle;
 // use std::ops::AddAssign;
 // use std::time::Instant;
 
+// derivative of the auxiliary states (state rows 6 and up) from the full state and time
+pub type AuxDynamicsFn = Rc<dyn Fn(&DVector<f64>, f64) -> DVector<f64>>;
+
+#[derive(Clone, Debug)]
+pub struct Panel {
+    // flat plate used by the aerodynamic and solar radiation pressure models
+    pub area: f64,            // m^2
+    pub normal: Vector3<f64>, // outward unit normal in the body frame
+    pub center: Vector3<f64>, // m, center of pressure relative to the center of mass (body)
+    pub specular: f64,        // specular reflectivity
+    pub diffuse: f64,         // diffuse reflectivity, absorbed fraction is 1 - specular - diffuse
+}
+
 pub struct SatBody {
     pub name: String,
     pub mass: f64,
-    pub state: Vector6<f64>,
-    pub propagate_flag: bool,
+    pub state: DVector<f64>, // position and velocity followed by any auxiliary states
+    pub aux_dxdt: Option<AuxDynamicsFn>,
     pub state_history: Vec<Vec<f64>>,
     pub time_history: Vec<f64>,
     pub coes: OrbitalElements,
+    pub maneuvers: ManeuverPlan,
+    pub perturbations: Vec<Perturbations>,
+    pub attitude: Option<Attitude>,
+    pub omega: Vector3<f64>, // rad/s, body angular velocity in the body frame
+    pub inertia: Matrix3<f64>, // kg.m^2, body frame inertia tensor
+    pub attitude_history: Vec<Vec<f64>>,
+    pub panels: Vec<Panel>,
+    pub adcs: Option<Adcs>,
+    pub sgp4: Option<Sgp4>, // element set the satellite was created from, for residuals
 }
 
 #[allow(dead_code)]
 impl SatBody {
     pub fn new() -> Self {
         SatBody {
-            name: String::from(""),                  // match struct name
-            mass: 0.,                                //kg
-            state: vector![0., 0., 0., 0., 0., 0.,], // m, m/s
-            propagate_flag: true,
+            name: String::from(""),                   // match struct name
+            mass: 0.,                                 //kg
+            state: dvector![0., 0., 0., 0., 0., 0.,], // m, m/s
+            aux_dxdt: None,
             state_history: vec![],
-            time_history: vec![0.],
-            coes: OrbitalElements {
-                sma: 0.,
-                ecc: 0.,
-                inc: 0.,
-                raan: 0.,
-                aop: 0.,
-                ta: 0.,
-                param: 0.,
-            },
+            time_history: vec![],
+            coes: OrbitalElements::new(),
+            maneuvers: ManeuverPlan::new(),
+            perturbations: vec![],
+            attitude: None,
+            omega: Vector3::zeros(),
+            inertia: Matrix3::identity(),
+            attitude_history: vec![],
+            panels: vec![],
+            adcs: None,
+            sgp4: None,
+        }
+    }
+
+    pub fn from_tle(
+        tle: &Tle,
+        constants: GravityConstants,
+        time: &f64,
+        time_data: &TimeData,
+    ) -> Result<Self, Sgp4Error> {
+        // satellite named after the element set with its sgp4 state at simulation time (tt s
+        // past J2000) rotated from teme to the inertial frame
+        let sgp4 = Sgp4::new(tle, constants)?;
+        let state = sgp4.inertial_state(time, time_data)?;
+        let mut satellite = SatBody::new();
+        satellite.name = if tle.name.is_empty() {
+            tle.satnum.clone()
+        } else {
+            tle.name.clone()
+        };
+        satellite.state = DVector::from_column_slice(state.as_slice());
+        satellite.sgp4 = Some(sgp4);
+        Ok(satellite)
+    }
+
+    pub fn attitude_dcm(&self, packed: &DVector<f64>) -> Option<Matrix3<f64>> {
+        // [BN] from the attitude parameters of a packed state
+        let att_index = self.attitude_index();
+        match self.attitude_len() {
+            3 => Some(Mrps::new(packed.fixed_rows::<3>(att_index).into()).mrp2dcm()),
+            4 => {
+                let quaternion: Vector4<f64> = packed.fixed_rows::<4>(att_index).into();
+                Some(Quaternions::new(quaternion.normalize()).quat2dcm())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn mass_index(&self) -> usize {
+        // mass is integrated right after the satellite's own states
+        self.state.len()
+    }
+
+    pub fn setup_error(&self, propagator: Propagator) -> Option<String> {
+        // thrust, drag and radiation pressure scale with 1/mass and are switched off for a
+        // massless body, the analytic propagators never evaluate them at all
+        let mass_dependent = self.perturbations.iter().any(|perturbation| {
+            matches!(
+                perturbation,
+                Perturbations::Aerodynamic(_)
+                    | Perturbations::SolarRadiation(_)
+                    | Perturbations::EarthRadiation(_)
+            )
+        });
+        let burns = !self.maneuvers.finite.is_empty();
+        if (burns || mass_dependent) && self.mass <= 0. {
+            Some(format!(
+                "{} needs a positive mass for finite burns, drag and radiation pressure",
+                self.name
+            ))
+        } else if propagator != Propagator::Numerical && (burns || !self.perturbations.is_empty()) {
+            Some(format!(
+                "{} has finite burns or perturbations, which need the numerical propagator",
+                self.name
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn attitude_index(&self) -> usize {
+        // attitude parameters are integrated after the mass, followed by the angular velocity
+        self.mass_index() + 1
+    }
+
+    pub fn attitude_len(&self) -> usize {
+        // mrps are integrated when they are the main representation, anything else goes
+        // through the quaternion
+        match &self.attitude {
+            None => 0,
+            Some(attitude) if attitude.main_rep == AttitudeRep::Mrp => 3,
+            Some(_) => 4,
+        }
+    }
+
+    pub fn wheel_index(&self) -> usize {
+        // reaction wheel speeds follow the angular velocity
+        self.attitude_index() + self.attitude_len() + 3
+    }
+
+    pub fn wheel_count(&self) -> usize {
+        match (&self.attitude, &self.adcs) {
+            (Some(_), Some(adcs)) => adcs.wheels.len(),
+            _ => 0,
+        }
+    }
+
+    pub fn update_control(&mut self, central_body: &CentralBody, time: &f64) {
+        // evaluate the attitude controller on the current state, commands are held over the step
+        if let (Some(attitude), Some(adcs)) = (&self.attitude, &mut self.adcs) {
+            let translational: Vector6<f64> = self.state.fixed_rows::<6>(0).into();
+            adcs.update(
+                central_body,
+                &translational,
+                &attitude.dcm,
+                &self.omega,
+                &self.inertia,
+                time,
+            );
+        }
+    }
+
+    pub fn pack_state(&self) -> DVector<f64> {
+        // full integrated state: [state, mass] or [state, mass, attitude, omega, wheel speeds]
+        let n_att = self.attitude_len();
+        let n_rot = if n_att > 0 {
+            n_att + 3 + self.wheel_count()
+        } else {
+            0
+        };
+        let mut packed = DVector::zeros(self.state.len() + 1 + n_rot);
+        packed.rows_mut(0, self.state.len()).copy_from(&self.state);
+        packed[self.mass_index()] = self.mass;
+
+        if let Some(attitude) = &self.attitude {
+            let att_index = self.attitude_index();
+            if n_att == 3 {
+                let sigma = match &attitude.mrp {
+                    Some(mrp) => mrp.sigma,
+                    None => Mrps::dcm2mrp(&attitude.dcm).sigma,
+                };
+                packed.fixed_rows_mut::<3>(att_index).copy_from(&sigma);
+            } else {
+                let quaternion = match (&attitude.quat, attitude.main_rep) {
+                    (Some(quat), AttitudeRep::Quat) => quat.quaternion,
+                    _ => Quaternions::dcm2quat(&attitude.dcm).quaternion,
+                };
+                packed.fixed_rows_mut::<4>(att_index).copy_from(&quaternion);
+            }
+            packed
+                .fixed_rows_mut::<3>(att_index + n_att)
+                .copy_from(&self.omega);
+            if let Some(adcs) = &self.adcs {
+                let wheel_index = self.wheel_index();
+                for (i, wheel) in adcs.wheels.iter().enumerate() {
+                    packed[wheel_index + i] = wheel.speed;
+                }
+            }
         }
+        packed
+    }
+
+    pub fn unpack_state(&mut self, packed: &DVector<f64>) {
+        let n = self.state.len();
+        self.state.copy_from(&packed.rows(0, n));
+        self.mass = packed[self.mass_index()];
+
+        let n_att = self.attitude_len();
+        let att_index = self.attitude_index();
+        if let Some(attitude) = &mut self.attitude {
+            if n_att == 3 {
+                let mut mrp = Mrps::new(packed.fixed_rows::<3>(att_index).into());
+                mrp.switch_shadow();
+                attitude.set(mrp);
+            } else {
+                let quaternion: Vector4<f64> = packed.fixed_rows::<4>(att_index).into();
+                attitude.set(Quaternions::new(quaternion.normalize()));
+            }
+            self.omega = packed.fixed_rows::<3>(att_index + n_att).into();
+            if let Some(adcs) = &mut self.adcs {
+                let wheel_index = att_index + n_att + 3;
+                for (i, wheel) in adcs.wheels.iter_mut().enumerate() {
+                    wheel.speed = packed[wheel_index + i];
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eoms::AeroAccel;
+    use crate::maneuver::{FiniteBurn, SteeringLaw};
+
+    #[test]
+    fn setup_error_flags_dropped_thrust_and_drag() {
+        let mut satellite = SatBody::new();
+        assert_eq!(satellite.setup_error(Propagator::Keplerian), None);
+
+        // drag on a massless body is switched off in the equations of motion
+        satellite
+            .perturbations
+            .push(Perturbations::Aerodynamic(AeroAccel::new(2.2, 1.)));
+        assert!(satellite.setup_error(Propagator::Numerical).is_some());
+        satellite.mass = 100.;
+        assert_eq!(satellite.setup_error(Propagator::Numerical), None);
+        assert!(satellite.setup_error(Propagator::MeanElements).is_some());
+
+        // finite burns only run under the numerical propagator
+        satellite.perturbations.clear();
+        satellite.maneuvers.add_finite(FiniteBurn::new(
+            0.,
+            60.,
+            10.,
+            300.,
+            SteeringLaw::VelocityAligned,
+        ));
+        assert_eq!(satellite.setup_error(Propagator::Numerical), None);
+        assert!(satellite.setup_error(Propagator::Keplerian).is_some());
+        satellite.mass = 0.;
+        assert!(satellite.setup_error(Propagator::Numerical).is_some());
     }
 }
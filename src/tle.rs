@@ -0,0 +1,211 @@
+use crate::epoch::*;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+pub const TLE_FILE: &str = "tles.txt";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tle {
+    // two-line element set as published, mean elements in the sgp4 (teme) sense
+    pub name: String, // line 0 of three-line sets, empty otherwise
+    pub satnum: String,
+    pub classification: char,
+    pub intl_designator: String,
+    pub epoch_year: i32,     // four digit year
+    pub epoch_days: f64,     // day of year and fraction, utc (1.0 is jan 1 00:00)
+    pub ndot: f64,           // rev/day^2, first derivative of mean motion over 2
+    pub nddot: f64,          // rev/day^3, second derivative of mean motion over 6
+    pub bstar: f64,          // 1/earth radii, drag term
+    pub ephemeris_type: u32, // 0 for sgp4
+    pub element_number: u32, // element set number
+    pub inclination: f64,    // deg
+    pub raan: f64,           // deg
+    pub eccentricity: f64,   //
+    pub arg_perigee: f64,    // deg
+    pub mean_anomaly: f64,   // deg
+    pub mean_motion: f64,    // rev/day (kozai)
+    pub rev_number: u32,     // revolution number at epoch
+}
+
+impl Tle {
+    pub fn parse(line1: &str, line2: &str) -> io::Result<Self> {
+        // fixed column two-line format, each line has to end in a valid checksum (column 69)
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        if !line1.starts_with('1') || !line2.starts_with('2') {
+            return Err(invalid("tle lines must start with 1 and 2"));
+        }
+        for line in [line1, line2] {
+            let expected = checksum(line);
+            match line.chars().nth(68).and_then(|c| c.to_digit(10)) {
+                Some(found) if found == expected => {}
+                Some(found) => {
+                    return Err(invalid(&format!(
+                        "tle line {} checksum is {}, expected {}",
+                        &line[..1],
+                        found,
+                        expected
+                    )))
+                }
+                None => {
+                    return Err(invalid(&format!(
+                        "tle line {} has no checksum in column 69",
+                        &line[..1]
+                    )))
+                }
+            }
+        }
+        let number = |line: &str, start: usize, end: usize, name: &str| -> io::Result<f64> {
+            let text = column(line, start, end);
+            if text.is_empty() {
+                return Ok(0.);
+            }
+            text.parse::<f64>()
+                .map_err(|_| invalid(&format!("bad tle {}: '{}'", name, text)))
+        };
+        let integer = |line: &str, start: usize, end: usize| -> u32 {
+            column(line, start, end).parse::<u32>().unwrap_or(0)
+        };
+
+        let year = number(line1, 18, 20, "epoch year")? as i32;
+        let ecc_text = column(line2, 26, 33);
+        let eccentricity = format!("0.{}", ecc_text)
+            .parse::<f64>()
+            .map_err(|_| invalid(&format!("bad tle eccentricity: '{}'", ecc_text)))?;
+        Ok(Tle {
+            name: String::new(),
+            satnum: column(line1, 2, 7).to_string(),
+            classification: line1.chars().nth(7).unwrap_or('U'),
+            intl_designator: column(line1, 9, 17).to_string(),
+            epoch_year: if year < 57 { 2000 + year } else { 1900 + year },
+            epoch_days: number(line1, 20, 32, "epoch")?,
+            ndot: number(line1, 33, 43, "ndot")?,
+            nddot: implied_decimal(column(line1, 44, 52))
+                .ok_or_else(|| invalid("bad tle nddot"))?,
+            bstar: implied_decimal(column(line1, 53, 61))
+                .ok_or_else(|| invalid("bad tle bstar"))?,
+            ephemeris_type: integer(line1, 62, 63),
+            element_number: integer(line1, 64, 68),
+            inclination: number(line2, 8, 16, "inclination")?,
+            raan: number(line2, 17, 25, "raan")?,
+            eccentricity,
+            arg_perigee: number(line2, 34, 42, "argument of perigee")?,
+            mean_anomaly: number(line2, 43, 51, "mean anomaly")?,
+            mean_motion: number(line2, 52, 63, "mean motion")?,
+            rev_number: integer(line2, 63, 68),
+        })
+    }
+
+    pub fn read(file_path: &str) -> io::Result<Vec<Self>> {
+        // every element set in a file of two or three-line sets, a line that is not part of a
+        // set names the one that follows it
+        let file = File::open(file_path)?;
+        let reader = BufReader::new(file);
+
+        let mut tles = vec![];
+        let mut name = String::new();
+        let mut line1: Option<String> = None;
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with("1 ") && line.len() > 60 {
+                line1 = Some(line.to_string());
+            } else if line.starts_with("2 ") && line1.is_some() {
+                let mut tle = Tle::parse(line1.as_ref().unwrap(), line)?;
+                tle.name = std::mem::take(&mut name);
+                tles.push(tle);
+                line1 = None;
+            } else {
+                name = line.trim_start_matches("0 ").trim().to_string();
+            }
+        }
+        Ok(tles)
+    }
+
+    pub fn epoch_jd(&self) -> f64 {
+        // julian date (utc) of the element set epoch
+        let year = self.epoch_year as f64;
+        // julian date of jan 1 00:00, valid from 1900 to 2100
+        let jan1 = 367. * year - (7. * year / 4.).floor() + 30. + 1721014.5;
+        jan1 + self.epoch_days - 1.
+    }
+
+    pub fn epoch(&self, time_data: &TimeData) -> Epoch {
+        // element set epoch, e.g. to start a scenario at (epoch.j2000_seconds())
+        Epoch::from_jd(self.epoch_jd(), TimeScale::Utc, time_data)
+    }
+}
+
+fn checksum(line: &str) -> u32 {
+    // modulo 10 sum of the first 68 columns, digits count their value and minus signs one
+    line.chars()
+        .take(68)
+        .map(|c| match c {
+            '-' => 1,
+            _ => c.to_digit(10).unwrap_or(0),
+        })
+        .sum::<u32>()
+        % 10
+}
+
+fn column(line: &str, start: usize, end: usize) -> &str {
+    // zero based, end exclusive columns, clipped to the line length
+    let end = end.min(line.len());
+    if start >= end {
+        return "";
+    }
+    line.get(start..end).unwrap_or("").trim()
+}
+
+fn implied_decimal(text: &str) -> Option<f64> {
+    // "-11606-4" is -0.11606e-4, a blank field is zero
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(0.);
+    }
+    let (sign, digits) = match text.chars().next() {
+        Some('-') => (-1., &text[1..]),
+        Some('+') => (1., &text[1..]),
+        _ => (1., text),
+    };
+    let split = digits.rfind(['-', '+']).unwrap_or(digits.len());
+    let mantissa = format!("0.{}", digits[..split].trim())
+        .parse::<f64>()
+        .ok()?;
+    let exponent = if split < digits.len() {
+        digits[split..].parse::<i32>().ok()?
+    } else {
+        0
+    };
+    Some(sign * mantissa * 10f64.powi(exponent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE1: &str = "1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753";
+    const LINE2: &str = "2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667";
+
+    #[test]
+    fn valid_checksums_are_accepted() {
+        let tle = Tle::parse(LINE1, LINE2).unwrap();
+        assert_eq!(tle.satnum, "00005");
+        assert_eq!(tle.element_number, 475);
+        assert_eq!(tle.rev_number, 41366);
+    }
+
+    #[test]
+    fn bad_checksums_are_rejected() {
+        // a single changed digit in either line
+        let line1 = LINE1.replace("00179.78495062", "00179.78495063");
+        let error = Tle::parse(&line1, LINE2).unwrap_err();
+        assert_eq!(error.to_string(), "tle line 1 checksum is 3, expected 4");
+        let line2 = LINE2.replace("34.2682", "35.2682");
+        assert!(Tle::parse(LINE1, &line2).is_err());
+        // and a line cut off before the checksum column
+        assert!(Tle::parse(LINE1, &LINE2[..68]).is_err());
+    }
+}